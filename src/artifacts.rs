@@ -0,0 +1,62 @@
+//! Detecting files gemini wrote to a request's output directory.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Modification time and size of every file under a directory.
+#[derive(Debug, Default)]
+pub(crate) struct Snapshot(HashMap<PathBuf, (Option<SystemTime>, u64)>);
+
+impl Snapshot {
+    /// Record the files under `dir`, except in `.git` directories; a missing
+    /// directory has none.
+    pub(crate) fn take(dir: &Path) -> Self {
+        let mut files = HashMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    if entry.file_name() != ".git" {
+                        pending.push(entry.path());
+                    }
+                } else {
+                    files.insert(entry.path(), (metadata.modified().ok(), metadata.len()));
+                }
+            }
+        }
+        Self(files)
+    }
+
+    /// Whether `path` existed when the snapshot was taken.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.0.contains_key(path)
+    }
+
+    /// Files recorded in this snapshot that no longer exist.
+    pub(crate) fn missing(&self) -> Vec<PathBuf> {
+        self.0
+            .keys()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect()
+    }
+
+    /// Files under `dir` created or modified since this snapshot, sorted.
+    pub(crate) fn changed(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = Snapshot::take(dir)
+            .0
+            .into_iter()
+            .filter(|(path, state)| self.0.get(path) != Some(state))
+            .map(|(path, _)| path)
+            .collect();
+        changed.sort();
+        changed
+    }
+}
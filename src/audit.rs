@@ -0,0 +1,53 @@
+//! Audit logging of `gemini` calls, with configurable redaction.
+//!
+//! Entries are emitted as tracing events under the [`AUDIT_TARGET`] target so
+//! they can be routed to a dedicated sink by the subscriber.
+
+use crate::config::ServerConfig;
+use crate::tags::format_tags;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Tracing target of audit entries.
+pub const AUDIT_TARGET: &str = "gemini_mcp::audit";
+
+/// Replacement for redacted matches.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Replace every match of any pattern with [`REDACTED`].
+pub fn redact<'a>(text: &'a str, patterns: &[Regex]) -> Cow<'a, str> {
+    patterns.iter().fold(Cow::Borrowed(text), |text, pattern| {
+        match pattern.replace_all(&text, REDACTED) {
+            Cow::Borrowed(_) => text,
+            Cow::Owned(redacted) => Cow::Owned(redacted),
+        }
+    })
+}
+
+/// Write an audit entry for a prompt about to be sent to gemini.
+///
+/// Only the logged copy is redacted; the prompt itself is left untouched.
+pub(crate) fn log_prompt(
+    config: &ServerConfig,
+    request_id: &str,
+    prompt: &str,
+    cwd: &Path,
+    session_id: Option<&str>,
+    tags: &BTreeMap<String, String>,
+) {
+    if !config.audit_prompts {
+        return;
+    }
+
+    tracing::info!(
+        target: AUDIT_TARGET,
+        request_id,
+        cwd = %cwd.display(),
+        session_id = session_id.unwrap_or(""),
+        tags = %format_tags(tags),
+        prompt = %redact(prompt, &config.redact_patterns),
+        "gemini invocation"
+    );
+}
@@ -0,0 +1,87 @@
+//! Server-side storage of batch runs, so their transient failures can be
+//! retried without re-running the whole batch.
+
+use crate::gemini::GeminiResult;
+use crate::server::GeminiToolInput;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Number of batches kept before the oldest one is evicted.
+pub const DEFAULT_BATCH_CAPACITY: usize = 16;
+
+/// Results of a `gemini_batch` or `gemini_batch_retry` call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub batch_id: String,
+    /// Whether every entry succeeded.
+    pub success: bool,
+    /// One result per entry, in input order.
+    pub results: Vec<GeminiResult>,
+    /// Indices of the entries run again by `gemini_batch_retry`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retried: Vec<usize>,
+}
+
+/// The entries of a batch with their latest results.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub entries: Vec<GeminiToolInput>,
+    pub results: Vec<GeminiResult>,
+}
+
+impl Batch {
+    /// Indices of the entries that failed with a transient [`FailureKind`].
+    ///
+    /// [`FailureKind`]: crate::FailureKind
+    pub fn transient_failures(&self) -> Vec<usize> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.failure_kind.is_some_and(|kind| kind.is_transient()))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Bounded, in-memory store of batches keyed by batch id.
+#[derive(Debug)]
+pub struct BatchStore {
+    capacity: usize,
+    entries: Mutex<VecDeque<(String, Batch)>>,
+}
+
+impl BatchStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Store a batch, replacing one with the same id and evicting the oldest
+    /// one when the store is full.
+    pub fn insert(&self, batch_id: String, batch: Batch) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(id, _)| *id != batch_id);
+        entries.push_back((batch_id, batch));
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+
+    /// A copy of a stored batch.
+    pub fn get(&self, batch_id: &str) -> Option<Batch> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .find(|(id, _)| id == batch_id)
+            .map(|(_, batch)| batch.clone())
+    }
+}
+
+impl Default for BatchStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_BATCH_CAPACITY)
+    }
+}
@@ -0,0 +1,57 @@
+//! Request fingerprints and a server-side cache of responses keyed by them.
+
+use crate::gemini::GeminiResult;
+use crate::server::GeminiToolInput;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Input fields that do not change what gemini is asked to do.
+const UNFINGERPRINTED_FIELDS: &[&str] = &["REQUEST_ID", "TAGS", "pretty", "log_file"];
+
+/// SHA-256 (hex) over the normalized request: every input that affects the
+/// run, with the workspace canonicalized and the model resolved.
+pub fn fingerprint(input: &GeminiToolInput, model: Option<&str>) -> String {
+    let mut value = serde_json::to_value(input).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        for field in UNFINGERPRINTED_FIELDS {
+            fields.remove(*field);
+        }
+        let cd = std::fs::canonicalize(&input.cd).unwrap_or_else(|_| input.cd.clone());
+        fields.insert("cd".to_string(), cd.to_string_lossy().into());
+        fields.insert("model".to_string(), model.unwrap_or_default().into());
+    }
+
+    let digest = Sha256::digest(value.to_string().as_bytes());
+    format!("{:x}", digest)
+}
+
+/// In-memory cache of successful results, each kept for a fixed time.
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, GeminiResult)>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The result stored for `fingerprint`, unless it has expired.
+    pub fn get(&self, fingerprint: &str) -> Option<GeminiResult> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+        entries.get(fingerprint).map(|(_, result)| result.clone())
+    }
+
+    /// Store a result under `fingerprint`.
+    pub fn insert(&self, fingerprint: String, result: GeminiResult) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(fingerprint, (Instant::now(), result));
+    }
+}
@@ -0,0 +1,143 @@
+//! Probing what the installed gemini supports from its `--help` output.
+
+use crate::error::{GeminiError, Result};
+use crate::gemini::{find_gemini_executable, PromptPassing};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Time allowed for `gemini --help` and `gemini --version`.
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+/// Output format the server reads gemini's events in.
+pub const STREAM_JSON: &str = "stream-json";
+
+/// Features of the installed gemini.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Reported by `gemini --version`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Values accepted by `--output-format`; empty if the help text lists none.
+    pub output_formats: Vec<String>,
+    /// Whether the help text marks `--prompt` as deprecated.
+    pub prompt_flag_deprecated: bool,
+    /// Long options listed in the help text, e.g. `--model`.
+    pub options: Vec<String>,
+}
+
+impl Capabilities {
+    /// Whether gemini accepts `format`, assumed when the help text did not say.
+    pub fn supports_output_format(&self, format: &str) -> bool {
+        self.output_formats.is_empty() || self.output_formats.iter().any(|f| f == format)
+    }
+
+    /// Whether the help text lists the long option `option`, e.g. `--seed`.
+    pub fn supports_option(&self, option: &str) -> bool {
+        self.options.iter().any(|o| o == option)
+    }
+
+    /// How gemini prefers to get its prompt: positionally once `--prompt` is
+    /// deprecated, as `--prompt` before.
+    pub fn preferred_prompt_passing(&self) -> PromptPassing {
+        if self.prompt_flag_deprecated {
+            PromptPassing::Positional
+        } else {
+            PromptPassing::Flag
+        }
+    }
+}
+
+/// The description of `flag` in gemini's help text, up to the next option.
+fn option_help<'a>(help: &'a str, flag: &str) -> Option<&'a str> {
+    let start = help.find(&format!("{} ", flag))?;
+    let option = &help[start..];
+    Some(
+        option[1..]
+            .find("\n  -")
+            .map_or(option, |end| &option[..end + 1]),
+    )
+}
+
+/// Whether gemini's help text marks `--prompt` as deprecated, e.g.
+/// `[deprecated: Use the positional prompt instead.]`.
+pub fn parse_prompt_flag_deprecated(help: &str) -> bool {
+    option_help(help, "--prompt").is_some_and(|option| option.contains("deprecated"))
+}
+
+/// The long options listed in gemini's help text, from lines such as
+/// `  -m, --model   Model   [string]`.
+pub fn parse_options(help: &str) -> Vec<String> {
+    help.lines()
+        .map(str::trim_start)
+        .filter(|line| line.starts_with('-'))
+        .filter_map(|line| line.split("  ").next())
+        .flat_map(|names| names.split(", "))
+        .filter(|name| name.starts_with("--"))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
+/// The choices listed for `--output-format` in gemini's help text, e.g.
+/// `[string] [choices: "text", "json", "stream-json"]`, which may wrap onto
+/// the following lines.
+pub fn parse_output_formats(help: &str) -> Vec<String> {
+    let Some(start) = help.find("--output-format") else {
+        return Vec::new();
+    };
+    let option = &help[start..];
+    // The option's description ends where the next option starts
+    let option = option[1..]
+        .find("\n  -")
+        .map_or(option, |end| &option[..end + 1]);
+    let Some(choices) = option.split("choices:").nth(1) else {
+        return Vec::new();
+    };
+    let choices = choices.split(']').next().unwrap_or_default();
+    choices
+        .split(',')
+        .map(|choice| choice.trim().trim_matches('"').to_string())
+        .filter(|choice| !choice.is_empty())
+        .collect()
+}
+
+/// Run the gemini on PATH with `--help` and `--version` and read its
+/// capabilities.
+pub async fn probe() -> Result<Capabilities> {
+    let gemini = find_gemini_executable()?;
+    probe_executable(Path::new(&gemini)).await
+}
+
+/// Like [`probe`], for the gemini executable at `gemini`.
+pub async fn probe_executable(gemini: &Path) -> Result<Capabilities> {
+    let help = run(gemini, "--help").await?;
+    let version = run(gemini, "--version")
+        .await
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    Ok(Capabilities {
+        version,
+        output_formats: parse_output_formats(&help),
+        prompt_flag_deprecated: parse_prompt_flag_deprecated(&help),
+        options: parse_options(&help),
+    })
+}
+
+/// Standard output of `gemini <flag>`, run outside any workspace.
+async fn run(gemini: &Path, flag: &str) -> Result<String> {
+    let output = Command::new(gemini)
+        .arg(flag)
+        .current_dir(std::env::temp_dir())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), output)
+        .await
+        .map_err(|_| GeminiError::Other(format!("gemini {} timed out", flag)))?
+        .map_err(GeminiError::ProcessSpawnError)?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
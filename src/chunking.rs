@@ -0,0 +1,94 @@
+//! Splitting oversized prompts into parts sent as consecutive turns of one session.
+
+/// Split `prompt` into pieces of at most `max_bytes` bytes that concatenate
+/// back to it.
+///
+/// Pieces end on a paragraph break where possible, then on a line break, then
+/// on whitespace, and only as a last resort in the middle of a word.
+pub fn split_prompt(prompt: &str, max_bytes: usize) -> Vec<&str> {
+    let max_bytes = max_bytes.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = prompt;
+
+    while rest.len() > max_bytes {
+        let mut window_end = max_bytes;
+        while !rest.is_char_boundary(window_end) {
+            window_end -= 1;
+        }
+        if window_end == 0 {
+            // A single character wider than the limit
+            window_end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let window = &rest[..window_end];
+
+        let end = boundary_after(window, "\n\n")
+            .or_else(|| boundary_after(window, "\n"))
+            .or_else(|| {
+                window
+                    .rfind(char::is_whitespace)
+                    .filter(|&i| i > 0)
+                    .map(|i| i + window[i..].chars().next().map_or(1, char::len_utf8))
+            })
+            .unwrap_or(window_end);
+
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Split `prompt` like [`split_prompt`], leaving room in each piece for the
+/// text [`frame_chunk`] wraps it in, so framed parts stay within `max_bytes`.
+pub fn split_framed(prompt: &str, max_bytes: usize) -> Vec<&str> {
+    // The frame grows with the number of parts, which depends on the frame
+    let mut total = 2;
+    loop {
+        let overhead = frame_overhead(total);
+        let chunks = split_prompt(prompt, max_bytes.saturating_sub(overhead));
+        if frame_overhead(chunks.len()) <= overhead {
+            return chunks;
+        }
+        total = chunks.len();
+    }
+}
+
+/// Bytes [`frame_chunk`] adds to any part of a prompt split in `total` parts.
+fn frame_overhead(total: usize) -> usize {
+    let total = total.max(2);
+    // Part numbers are never wider than `total`
+    let widest_index = total.to_string().len() - 1;
+    frame_chunk("", 0, total)
+        .len()
+        .saturating_add(widest_index)
+        .max(frame_chunk("", total - 1, total).len())
+}
+
+/// Index just past the last `separator` in `window`, unless that would leave
+/// an empty piece.
+fn boundary_after(window: &str, separator: &str) -> Option<usize> {
+    window
+        .rfind(separator)
+        .map(|i| i + separator.len())
+        .filter(|&end| !window[..end].trim().is_empty())
+}
+
+/// The turn prompt for part `index` (0-based) of `total`.
+pub(crate) fn frame_chunk(chunk: &str, index: usize, total: usize) -> String {
+    if index + 1 < total {
+        format!(
+            "This is part {} of {} of a long prompt. Do not act on it yet; reply only with \"OK\".\n\n{}",
+            index + 1,
+            total,
+            chunk
+        )
+    } else {
+        format!(
+            "This is the last part ({} of {}) of a long prompt. Now carry out the instructions given across all parts.\n\n{}",
+            total, total, chunk
+        )
+    }
+}
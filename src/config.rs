@@ -0,0 +1,538 @@
+//! Server-wide configuration for the Gemini MCP server.
+
+use crate::context::DEFAULT_COMPRESSION_TARGET_BYTES;
+use crate::gemini::{EnvPolicy, NonUtf8Paths, OutputEncoding, OutsideWrites, PromptPassing};
+use crate::keys::{KeyRotation, DEFAULT_KEY_COOLDOWN_SECS};
+use crate::retry::RetryPolicy;
+use crate::scratch::{DEFAULT_SCRATCH_COPY_IGNORE, DEFAULT_SCRATCH_COPY_MAX_BYTES};
+use crate::spend::{ModelCost, DEFAULT_SPEND_WINDOW_SECS};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Model to use for prompts of at least `min_chars` characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelThreshold {
+    pub min_chars: usize,
+    pub model: String,
+}
+
+impl FromStr for ModelThreshold {
+    type Err = String;
+
+    /// Parse `MIN_CHARS=MODEL`, e.g. `20000=gemini-2.5-pro`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min_chars, model) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected MIN_CHARS=MODEL, got {:?}", s))?;
+        let min_chars = min_chars
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid MIN_CHARS {:?}: {}", min_chars, e))?;
+        let model = model.trim();
+        if model.is_empty() {
+            return Err(format!("missing MODEL in {:?}", s));
+        }
+        Ok(Self {
+            min_chars,
+            model: model.to_string(),
+        })
+    }
+}
+
+/// A named gemini executable requests can select with `binary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeminiBinary {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl FromStr for GeminiBinary {
+    type Err = String;
+
+    /// Parse `NAME=PATH`, e.g. `canary=/opt/gemini-canary/bin/gemini`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=PATH, got {:?}", s))?;
+        let (name, path) = (name.trim(), path.trim());
+        if name.is_empty() {
+            return Err(format!("missing NAME in {:?}", s));
+        }
+        if path.is_empty() {
+            return Err(format!("missing PATH in {:?}", s));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// A named command requests can pipe the reply through with `post_process`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostProcessor {
+    pub name: String,
+    /// Shell command reading the reply on stdin and writing the new one.
+    pub command: String,
+}
+
+impl FromStr for PostProcessor {
+    type Err = String;
+
+    /// Parse `NAME=COMMAND`, e.g. `upper=tr a-z A-Z`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, command) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=COMMAND, got {:?}", s))?;
+        let (name, command) = (name.trim(), command.trim());
+        if name.is_empty() {
+            return Err(format!("missing NAME in {:?}", s));
+        }
+        if command.is_empty() {
+            return Err(format!("missing COMMAND in {:?}", s));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            command: command.to_string(),
+        })
+    }
+}
+
+/// Description replacing the built-in one of a tool input field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDescription {
+    pub field: String,
+    pub description: String,
+}
+
+impl FromStr for FieldDescription {
+    type Err = String;
+
+    /// Parse `FIELD=TEXT`, e.g. `PROMPT=Aufgabe für gemini`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, description) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected FIELD=TEXT, got {:?}", s))?;
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(format!("missing FIELD in {:?}", s));
+        }
+        Ok(Self {
+            field: field.to_string(),
+            description: description.to_string(),
+        })
+    }
+}
+
+/// Default timeout for a single gemini run, before scaling with prompt size.
+pub const DEFAULT_TIMEOUT_BASE_SECS: u64 = 300;
+
+/// Default upper bound for the scaled timeout.
+pub const DEFAULT_TIMEOUT_MAX_SECS: u64 = 3600;
+
+/// Timeout of a gemini run as `base + per_kb * prompt_kb`, capped at `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeoutPolicy {
+    pub base_secs: u64,
+    /// Extra seconds per KiB of prompt. `0` disables scaling.
+    pub per_kb_secs: f64,
+    pub max_secs: u64,
+}
+
+impl TimeoutPolicy {
+    /// Timeout for a run with the given prompt.
+    pub fn for_prompt(&self, prompt: &str) -> Duration {
+        let prompt_kb = prompt.len() as f64 / 1024.0;
+        let secs = self.base_secs as f64 + self.per_kb_secs.max(0.0) * prompt_kb;
+        Duration::from_secs_f64(secs.min(self.max_secs.max(self.base_secs) as f64))
+    }
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self {
+            base_secs: DEFAULT_TIMEOUT_BASE_SECS,
+            per_kb_secs: 0.0,
+            max_secs: DEFAULT_TIMEOUT_MAX_SECS,
+        }
+    }
+}
+
+/// Environment variables gemini inherits under [`EnvPolicy::Allowlist`] by
+/// default: what it needs to run, find its configuration and reach the API.
+pub const DEFAULT_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LC_*",
+    "TERM",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "GEMINI_*",
+    "GOOGLE_*",
+];
+
+/// Words that make `auto_sandbox` enable the sandbox by default; a trailing
+/// `*` also matches words starting with the keyword.
+pub const DEFAULT_SANDBOX_KEYWORDS: &[&str] = &[
+    "delete*",
+    "remove*",
+    "rm",
+    "refactor*",
+    "rename*",
+    "move",
+    "moves",
+    "moved",
+    "write",
+    "writes",
+    "rewrite*",
+    "overwrite*",
+    "edit",
+    "edits",
+    "edited",
+    "modify",
+    "modifies",
+    "modified",
+    "change",
+    "changes",
+    "changed",
+    "replace*",
+    "create*",
+    "update*",
+    "fix",
+    "fixes",
+    "fixed",
+    "implement*",
+    "install*",
+];
+
+/// Default budget for gemini's first event before switching to `fallback_model`.
+pub const DEFAULT_FIRST_EVENT_TIMEOUT_SECS: u64 = 30;
+
+/// Default window for the per-session resume quota.
+pub const DEFAULT_SESSION_RESUME_WINDOW_SECS: u64 = 3600;
+
+/// Default limit on the entries of one `gemini_batch`.
+pub const DEFAULT_MAX_BATCH_ENTRIES: usize = 20;
+
+/// Default number of batch entries run at the same time.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// Configuration applied to every invocation handled by the server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Refuse to start when the gemini executable cannot be found in PATH.
+    pub require_gemini: bool,
+
+    /// Reject resuming a session from a different working directory instead
+    /// of only warning about it.
+    pub strict_session_cwd: bool,
+
+    /// Retry policy for invocations that fail before a session is created.
+    pub retry: RetryPolicy,
+
+    /// Timeout of runs without an explicit `TIMEOUT_SECS`.
+    pub timeout: TimeoutPolicy,
+
+    /// Budget for gemini's first event when a `fallback_model` is given.
+    pub first_event_timeout_secs: u64,
+
+    /// Prompt-size thresholds used to pick a model when `auto_model` is requested.
+    pub auto_models: Vec<ModelThreshold>,
+
+    /// JSON pointer to the session id in gemini events, checked when neither
+    /// `session_id` nor `SESSION_ID` is present.
+    pub session_id_pointer: Option<String>,
+
+    /// Maximum number of times a single session may be resumed per
+    /// `session_resume_window_secs`. `None` means unlimited.
+    pub session_resume_limit: Option<u32>,
+
+    /// Length of the window for `session_resume_limit`, in seconds.
+    pub session_resume_window_secs: u64,
+
+    /// Also return results as MCP structured content to clients whose protocol
+    /// version supports it (2025-06-18 and later).
+    pub structured_output: bool,
+
+    /// Describe the tool result envelope (content blocks and their sizes) in
+    /// a `_debug` field of `gemini` results.
+    pub debug_envelope: bool,
+
+    /// Address-space limit for gemini in MiB (Linux only). `None` means unlimited.
+    pub memory_limit_mb: Option<u64>,
+
+    /// Write every prompt to the audit log (tracing target `gemini_mcp::audit`).
+    pub audit_prompts: bool,
+
+    /// Patterns redacted from prompts before they are written to the audit log.
+    pub redact_patterns: Vec<Regex>,
+
+    /// API keys handed to gemini in turn (default: gemini's own configuration).
+    pub api_keys: Vec<String>,
+
+    /// How the next API key is chosen.
+    pub key_rotation: KeyRotation,
+
+    /// How long a key that hit its quota is skipped.
+    pub key_cooldown_secs: u64,
+
+    /// How long successful results are cached by request fingerprint
+    /// (default: no cache).
+    pub response_cache_ttl_secs: Option<u64>,
+
+    /// Largest `gemini` response in bytes; the overflow is stored for
+    /// `gemini_messages_page` (default: no limit).
+    pub max_payload_bytes: Option<usize>,
+
+    /// Most entries accepted in one `gemini_batch`.
+    pub max_batch_entries: usize,
+
+    /// Most batch entries running at the same time; the rest wait.
+    pub batch_concurrency: usize,
+
+    /// Largest prompt accepted in one turn, in bytes; larger prompts are
+    /// rejected unless the request sets `chunk_prompt` or `compress_context`.
+    pub max_prompt_bytes: Option<usize>,
+
+    /// Instruction of the `compress_context` pass, with `{target_bytes}` and
+    /// `{prompt}` replaced (default: built in).
+    pub compression_prompt: Option<String>,
+
+    /// Size `compress_context` asks gemini to compress `CONTEXT` to, in bytes.
+    pub compression_target_bytes: usize,
+
+    /// Message for a turn without assistant text, with `{tool_calls}`
+    /// replaced by a summary of gemini's tool calls (default: built in).
+    pub no_messages_template: Option<String>,
+
+    /// Subcommands a request may select with `SUBCOMMAND` (default: none).
+    pub subcommands: Vec<String>,
+
+    /// Non-JSON output lines to ignore rather than report as decode errors.
+    pub benign_output_patterns: Vec<Regex>,
+
+    /// End a run that sent this many events without completing the turn,
+    /// instead of waiting for the timeout.
+    pub max_events_without_completion: Option<usize>,
+
+    /// Fail a run with `stream_unparseable` once more than this many of its
+    /// output lines failed to decode.
+    pub max_decode_errors: Option<usize>,
+
+    /// Reject requests setting `raw_prompt`.
+    pub forbid_raw_prompt: bool,
+
+    /// Name the `gemini` tool is registered under; the other tools take it as
+    /// their prefix (e.g. `<name>_validate`). Lets several instances share a
+    /// client.
+    pub tool_name: Option<String>,
+
+    /// Description of the `gemini` tool, replacing the built-in one.
+    pub tool_description: Option<String>,
+
+    /// Descriptions replacing the built-in ones of input fields, in the
+    /// schemas of every tool taking the field.
+    pub field_descriptions: Vec<FieldDescription>,
+
+    /// Send every line gemini writes to stderr to the client as a logging
+    /// notification from the `stderr` logger, as it arrives.
+    pub forward_stderr: bool,
+
+    /// Words marking a prompt as likely to modify files, for `auto_sandbox`.
+    pub sandbox_keywords: Vec<String>,
+
+    /// Extensions of the files gemini may create or modify in the workspace;
+    /// writing any other file fails the request. Empty allows everything.
+    pub allowed_write_extensions: Vec<String>,
+
+    /// Undo forbidden writes where git makes that safe.
+    pub revert_forbidden_writes: bool,
+
+    /// Whether a non-UTF-8 workspace or include path is passed to gemini as
+    /// is or rejected.
+    pub non_utf8_paths: NonUtf8Paths,
+
+    /// Encoding gemini's output is decoded from. Under UTF-8, gemini is also
+    /// told to write UTF-8 where it honors [`UTF8_OUTPUT_ENV`].
+    ///
+    /// [`UTF8_OUTPUT_ENV`]: crate::gemini::UTF8_OUTPUT_ENV
+    pub output_encoding: OutputEncoding,
+
+    /// Whether files gemini's tool calls write outside the workspace are
+    /// ignored, reported or fail the call.
+    pub outside_writes: OutsideWrites,
+
+    /// Largest workspace copied for `scratch_copy`, in bytes.
+    pub scratch_copy_max_bytes: u64,
+
+    /// File and directory names left out of scratch copies.
+    pub scratch_copy_ignore: Vec<String>,
+
+    /// gemini executables requests can select by name with `binary`; the one
+    /// in PATH is used otherwise.
+    pub binaries: Vec<GeminiBinary>,
+
+    /// Cost per 1k tokens of each model, for the spend tracker.
+    pub model_costs: Vec<ModelCost>,
+
+    /// Largest spend per `spend_window_secs`; requests are refused once it
+    /// is reached (default: no cap).
+    pub spend_budget: Option<f64>,
+
+    /// Length of the window for `spend_budget`, in seconds.
+    pub spend_window_secs: u64,
+
+    /// Commands requests can pipe the reply through by name with
+    /// `post_process`. Empty disables post-processing.
+    pub post_processors: Vec<PostProcessor>,
+
+    /// How the prompt is handed to gemini; `Auto` follows the probed
+    /// capabilities.
+    pub prompt_passing: PromptPassing,
+
+    /// Whether gemini inherits the server's whole environment or only the
+    /// variables matching `env_allowlist`.
+    pub env_policy: EnvPolicy,
+
+    /// Variables gemini inherits under [`EnvPolicy::Allowlist`]; a trailing
+    /// `*` matches any suffix.
+    pub env_allowlist: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            require_gemini: false,
+            strict_session_cwd: false,
+            retry: RetryPolicy::default(),
+            timeout: TimeoutPolicy::default(),
+            first_event_timeout_secs: DEFAULT_FIRST_EVENT_TIMEOUT_SECS,
+            auto_models: Vec::new(),
+            session_id_pointer: None,
+            session_resume_limit: None,
+            session_resume_window_secs: DEFAULT_SESSION_RESUME_WINDOW_SECS,
+            structured_output: false,
+            debug_envelope: false,
+            memory_limit_mb: None,
+            audit_prompts: false,
+            redact_patterns: Vec::new(),
+            api_keys: Vec::new(),
+            key_rotation: KeyRotation::default(),
+            key_cooldown_secs: DEFAULT_KEY_COOLDOWN_SECS,
+            response_cache_ttl_secs: None,
+            max_payload_bytes: None,
+            max_batch_entries: DEFAULT_MAX_BATCH_ENTRIES,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            max_prompt_bytes: None,
+            compression_prompt: None,
+            compression_target_bytes: DEFAULT_COMPRESSION_TARGET_BYTES,
+            no_messages_template: None,
+            subcommands: Vec::new(),
+            benign_output_patterns: Vec::new(),
+            max_events_without_completion: None,
+            max_decode_errors: None,
+            forbid_raw_prompt: false,
+            tool_name: None,
+            tool_description: None,
+            field_descriptions: Vec::new(),
+            forward_stderr: false,
+            sandbox_keywords: DEFAULT_SANDBOX_KEYWORDS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            allowed_write_extensions: Vec::new(),
+            revert_forbidden_writes: false,
+            non_utf8_paths: NonUtf8Paths::default(),
+            output_encoding: OutputEncoding::default(),
+            outside_writes: OutsideWrites::default(),
+            scratch_copy_max_bytes: DEFAULT_SCRATCH_COPY_MAX_BYTES,
+            scratch_copy_ignore: DEFAULT_SCRATCH_COPY_IGNORE
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            binaries: Vec::new(),
+            model_costs: Vec::new(),
+            spend_budget: None,
+            spend_window_secs: DEFAULT_SPEND_WINDOW_SECS,
+            post_processors: Vec::new(),
+            prompt_passing: PromptPassing::default(),
+            env_policy: EnvPolicy::default(),
+            env_allowlist: DEFAULT_ENV_ALLOWLIST
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Path of the gemini executable configured as `name`.
+    pub fn binary(&self, name: &str) -> Option<&Path> {
+        self.binaries
+            .iter()
+            .find(|b| b.name == name)
+            .map(|b| b.path.as_path())
+    }
+
+    /// Command of the post-processor configured as `name`.
+    pub fn post_processor(&self, name: &str) -> Option<&str> {
+        self.post_processors
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.command.as_str())
+    }
+
+    /// Model with the largest threshold not exceeding the prompt length.
+    pub fn auto_model_for(&self, prompt: &str) -> Option<&str> {
+        let len = prompt.chars().count();
+        self.auto_models
+            .iter()
+            .filter(|t| t.min_chars <= len)
+            .max_by_key(|t| t.min_chars)
+            .map(|t| t.model.as_str())
+    }
+
+    /// The first sandbox keyword that is a word of the prompt, ignoring case,
+    /// without its `*`. Keywords ending in `*` also match words they start,
+    /// e.g. `delete*` matches "Deleted files" but `fix` does not match
+    /// "fixture". A best-effort guess at whether the prompt asks gemini to
+    /// modify files.
+    pub fn sandbox_keyword_in(&self, prompt: &str) -> Option<&str> {
+        let prompt = prompt.to_lowercase();
+        let words: Vec<&str> = prompt
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        self.sandbox_keywords
+            .iter()
+            .map(|keyword| match keyword.strip_suffix('*') {
+                Some(prefix) => (prefix, true),
+                None => (keyword.as_str(), false),
+            })
+            .find(|(keyword, prefix)| {
+                let keyword = keyword.to_lowercase();
+                words.iter().any(|word| {
+                    if *prefix {
+                        word.starts_with(&keyword)
+                    } else {
+                        *word == keyword
+                    }
+                })
+            })
+            .map(|(keyword, _)| keyword)
+    }
+}
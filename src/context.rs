@@ -0,0 +1,52 @@
+//! Embedding a request's structured `CONTEXT` in its prompt.
+//!
+//! gemini has no flag for structured input, so the context is appended to
+//! the prompt as pretty-printed JSON in a labeled, fenced block that the
+//! model can read and that parses back to the same value.
+
+/// Heading of the context block.
+pub const CONTEXT_LABEL: &str = "Context (JSON):";
+
+/// Heading of the context block once `compress_context` compressed it.
+pub const COMPRESSED_CONTEXT_LABEL: &str = "Context (compressed):";
+
+/// Size the compressed context is asked to fit in by default, in bytes.
+pub const DEFAULT_COMPRESSION_TARGET_BYTES: usize = 16 * 1024;
+
+/// Instruction of the compression pass by default; `{target_bytes}` and
+/// `{prompt}` are replaced, and the context follows it.
+pub const DEFAULT_COMPRESSION_PROMPT: &str = "Compress the context below to at most {target_bytes} bytes. Keep every fact, name, identifier, number and instruction the following task may need, drop the rest, and reply with the compressed context only.\n\nTask: {prompt}";
+
+/// Prompt of the pass compressing `context` for `prompt`, from `template`.
+pub fn compression_prompt(
+    template: &str,
+    prompt: &str,
+    context: &serde_json::Value,
+    target_bytes: usize,
+) -> String {
+    let instruction = template
+        .replace("{target_bytes}", &target_bytes.to_string())
+        .replace("{prompt}", prompt);
+    embed_context(&instruction, context)
+}
+
+/// `prompt` followed by the `compressed` context under its own heading.
+pub fn embed_compressed_context(prompt: &str, compressed: &str) -> String {
+    format!("{}\n\n{}\n{}", prompt, COMPRESSED_CONTEXT_LABEL, compressed)
+}
+
+/// `prompt` followed by `context` in a fenced `json` block.
+pub fn embed_context(prompt: &str, context: &serde_json::Value) -> String {
+    let json = serde_json::to_string_pretty(context).unwrap_or_else(|_| context.to_string());
+    // A fence longer than any run of backticks in the JSON cannot be closed by it
+    let longest_run = json
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!(
+        "{}\n\n{}\n{}json\n{}\n{}",
+        prompt, CONTEXT_LABEL, fence, json, fence
+    )
+}
@@ -0,0 +1,43 @@
+//! Absolute request deadlines, kept on the monotonic clock.
+//!
+//! A `DEADLINE` arrives as wall-clock time. It is compared with the wall clock
+//! once, when the request starts, and from then on only tokio's monotonic
+//! [`Instant`] is used, so an NTP correction or a clock jump mid-run neither
+//! stretches nor cuts short the time gemini gets.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// A point on the monotonic clock by which a request must be done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// The deadline `unix_secs` seconds after the Unix epoch, or how long ago
+    /// it passed.
+    pub fn from_unix_secs(unix_secs: u64) -> Result<Self, Duration> {
+        Self::from_wall_clock(
+            UNIX_EPOCH + Duration::from_secs(unix_secs),
+            SystemTime::now(),
+        )
+    }
+
+    /// The deadline `deadline` as seen from the wall-clock time `now`, or how
+    /// long ago it passed. This is the only place the wall clock is read.
+    pub fn from_wall_clock(deadline: SystemTime, now: SystemTime) -> Result<Self, Duration> {
+        match deadline.duration_since(now) {
+            Ok(remaining) => Ok(Self(Instant::now() + remaining)),
+            Err(passed) => Err(passed.duration()),
+        }
+    }
+
+    /// The deadline on the monotonic clock.
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
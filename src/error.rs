@@ -1,21 +1,148 @@
 //! Error types for the Gemini MCP server.
 
+use rmcp::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::Path;
 use thiserror::Error;
 
+/// Machine-readable category of a failed invocation, reported as `failure_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The tool input was rejected before running gemini.
+    InvalidInput,
+    /// The gemini executable could not be found or started.
+    SpawnFailed,
+    /// gemini or the workspace could not be accessed with the server's permissions.
+    PermissionDenied,
+    /// The disk is full.
+    StorageFull,
+    /// Resuming from a different working directory under `--strict-session-cwd`.
+    SessionCwdMismatch,
+    /// The session was resumed more often than the configured quota allows.
+    SessionQuotaExceeded,
+    /// The server's spend budget for the current window is used up.
+    BudgetExceeded,
+    /// The run exceeded its time limit.
+    Timeout,
+    /// gemini sent nothing within the first-event budget.
+    FirstEventTimeout,
+    /// gemini kept sending events without ever completing the turn.
+    NoCompletion,
+    /// More of gemini's output failed to decode than `max_decode_errors`
+    /// allows, most likely an incompatible event format.
+    StreamUnparseable,
+    /// The run was cancelled, e.g. because the client disconnected.
+    Cancelled,
+    /// gemini's API key ran out of quota.
+    QuotaExceeded,
+    /// gemini asked to log in again, which it cannot do non-interactively.
+    ReauthRequired,
+    /// gemini reported an error under `fail_fast_on_stream_error`.
+    StreamError,
+    /// gemini died after exceeding its memory limit.
+    MemoryLimit,
+    /// gemini never reported a session id.
+    NoSessionId,
+    /// gemini produced no assistant text.
+    NoAgentMessages,
+    /// The assistant text was shorter than `min_output_chars`.
+    OutputTooShort,
+    /// The reply held no valid JSON under `expect_json`.
+    InvalidJson,
+    /// The `post_process` command failed.
+    PostProcessFailed,
+    /// gemini wrote a file whose extension is not allowed.
+    ForbiddenWrite,
+    /// The server's content policy refused the prompt.
+    PolicyDenied,
+    /// gemini wrote a file outside the workspace and the include directories.
+    WriteOutsideWorkspace,
+    /// Anything else.
+    Internal,
+}
+
 #[derive(Error, Debug)]
 pub enum GeminiError {
+    #[error("{0}")]
+    InvalidInput(String),
+
     #[error("Workspace directory does not exist: {0}")]
     WorkspaceNotFound(String),
 
+    #[error("Path {0:?} is not valid UTF-8, and the server rejects such paths")]
+    NonUtf8Path(std::path::PathBuf),
+
     #[error("Failed to find gemini executable in PATH")]
     GeminiNotFound,
 
     #[error("Failed to spawn gemini process: {0}")]
     ProcessSpawnError(#[from] std::io::Error),
 
+    #[error(
+        "Permission denied starting {executable} in {cwd}: check that the executable is runnable and the directory is accessible to the server"
+    )]
+    SpawnPermissionDenied { executable: String, cwd: String },
+
+    #[error("No space left on device starting gemini in {0}: free up disk space and retry")]
+    StorageFull(String),
+
+    #[error("Failed to start gemini with a read-only workspace: {0}")]
+    ReadOnlyUnavailable(std::io::Error),
+
+    #[error("Failed to open log file {path}: {source}")]
+    LogFile {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to create output directory {path}: {source}")]
+    OutputDir {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to make a scratch copy of {path}: {source}")]
+    ScratchCopy {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("gemini does not support the {0} output format")]
+    UnsupportedOutputFormat(String),
+
     #[error("Failed to parse JSON output: {0}")]
     JsonParseError(#[from] serde_json::Error),
 
+    #[error("Session {session_id} was created in {expected} but resumed in {actual}")]
+    SessionCwdMismatch {
+        session_id: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error(
+        "Session {session_id} was resumed {limit} times in the last {window_secs}s, which is the limit"
+    )]
+    SessionQuotaExceeded {
+        session_id: String,
+        limit: u32,
+        window_secs: u64,
+    },
+
+    #[error(
+        "The server's spend budget of {budget} per {window_secs}s is used up ({spent:.4} spent); retry once older runs leave the window"
+    )]
+    BudgetExceeded {
+        spent: f64,
+        budget: f64,
+        window_secs: u64,
+    },
+
+    #[error("The prompt was refused by the server's content policy: {0}")]
+    PolicyDenied(String),
+
     #[error("Failed to get SESSION_ID from gemini session")]
     NoSessionId,
 
@@ -25,8 +152,73 @@ pub enum GeminiError {
     #[error("Process timeout")]
     ProcessTimeout,
 
+    #[error(
+        "DEADLINE passed {}s before the request started; check that the client's and server's clocks agree",
+        passed.as_secs()
+    )]
+    DeadlinePassed { passed: std::time::Duration },
+
     #[error("{0}")]
     Other(String),
 }
 
+impl FailureKind {
+    /// Whether running the same request again may well succeed.
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            FailureKind::Timeout
+                | FailureKind::FirstEventTimeout
+                | FailureKind::NoCompletion
+                | FailureKind::QuotaExceeded
+                | FailureKind::StreamError
+        )
+    }
+}
+
+impl GeminiError {
+    /// Turn an error from spawning `executable` in `cwd` into an actionable error.
+    pub fn from_spawn_error(error: std::io::Error, executable: &str, cwd: &Path) -> Self {
+        match error.kind() {
+            ErrorKind::PermissionDenied => GeminiError::SpawnPermissionDenied {
+                executable: executable.to_string(),
+                cwd: cwd.to_string_lossy().to_string(),
+            },
+            ErrorKind::StorageFull => GeminiError::StorageFull(cwd.to_string_lossy().to_string()),
+            ErrorKind::NotFound if !cwd.exists() => {
+                GeminiError::WorkspaceNotFound(cwd.to_string_lossy().to_string())
+            }
+            _ => GeminiError::ProcessSpawnError(error),
+        }
+    }
+
+    /// Category reported as `failure_kind` for this error.
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            GeminiError::InvalidInput(_)
+            | GeminiError::WorkspaceNotFound(_)
+            | GeminiError::LogFile { .. }
+            | GeminiError::OutputDir { .. }
+            | GeminiError::ScratchCopy { .. }
+            | GeminiError::NonUtf8Path(_) => FailureKind::InvalidInput,
+            GeminiError::GeminiNotFound
+            | GeminiError::ProcessSpawnError(_)
+            | GeminiError::ReadOnlyUnavailable(_)
+            | GeminiError::UnsupportedOutputFormat(_) => FailureKind::SpawnFailed,
+            GeminiError::SpawnPermissionDenied { .. } => FailureKind::PermissionDenied,
+            GeminiError::StorageFull(_) => FailureKind::StorageFull,
+            GeminiError::SessionCwdMismatch { .. } => FailureKind::SessionCwdMismatch,
+            GeminiError::SessionQuotaExceeded { .. } => FailureKind::SessionQuotaExceeded,
+            GeminiError::BudgetExceeded { .. } => FailureKind::BudgetExceeded,
+            GeminiError::PolicyDenied(_) => FailureKind::PolicyDenied,
+            GeminiError::NoSessionId => FailureKind::NoSessionId,
+            GeminiError::NoAgentMessages(_) => FailureKind::NoAgentMessages,
+            GeminiError::ProcessTimeout | GeminiError::DeadlinePassed { .. } => {
+                FailureKind::Timeout
+            }
+            GeminiError::JsonParseError(_) | GeminiError::Other(_) => FailureKind::Internal,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, GeminiError>;
@@ -1,17 +1,43 @@
 //! Gemini CLI execution module.
 
-use crate::error::{GeminiError, Result};
+use crate::capabilities::STREAM_JSON;
+use crate::deadline::Deadline;
+use crate::error::{FailureKind, GeminiError, Result};
+use crate::keys::API_KEY_ENV;
+use crate::limits;
+use crate::process::GeminiProcess;
+use crate::readonly;
+use crate::resource_usage::PeakRss;
+use crate::session::SessionHandle;
+use crate::sink::Sinks;
+use crate::write_guard;
+use rmcp::schemars::{self, JsonSchema};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::VecDeque;
-use std::path::Path;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, Split};
+use tokio::process::{ChildStderr, Command};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+
+/// Environment variable carrying the request id into the gemini process.
+pub const REQUEST_ID_ENV: &str = "GEMINI_MCP_REQUEST_ID";
 
 const GRACEFUL_SHUTDOWN_DELAY_MS: u64 = 300;
+/// Shortest idle gap that ends an adaptive drain.
+const DRAIN_MIN_IDLE_MS: u64 = 50;
 const PROCESS_TIMEOUT_SECS: u64 = 300;
 const WAIT_TIMEOUT_SECS: u64 = 5;
+/// Characters of content kept in an [`EventSummary`] preview.
+pub const EVENT_PREVIEW_CHARS: usize = 80;
 
 /// A single JSON event from the Gemini CLI output stream.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,22 +47,518 @@ pub struct GeminiEvent {
     pub role: Option<String>,
     pub content: Option<String>,
     pub session_id: Option<String>,
+    pub checkpoint_id: Option<String>,
     #[serde(flatten)]
     pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
+/// An event cut down to its shape: type, role and the start of its content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSummary {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// The first [`EVENT_PREVIEW_CHARS`] characters of the content, with `…`
+    /// appended when there was more.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+impl EventSummary {
+    /// Summarize `event`.
+    pub fn of(event: &GeminiEvent) -> Self {
+        let preview = event.content.as_deref().map(|content| {
+            match content.char_indices().nth(EVENT_PREVIEW_CHARS) {
+                Some((end, _)) => format!("{}…", &content[..end]),
+                None => content.to_string(),
+            }
+        });
+        Self {
+            event_type: event.event_type.clone(),
+            role: event.role.clone(),
+            preview,
+        }
+    }
+}
+
+/// A single step of a plan announced by gemini.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// A plan gemini announced before executing it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+/// A source gemini cited or grounded its answer on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Whether `auto_sandbox` turned the sandbox on, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SandboxDecision {
+    pub enabled: bool,
+    /// The keyword the prompt matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+}
+
+/// A tool call gemini made during the turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Status of the matching `tool_result`, e.g. `success` or `error`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// A tool call in a [`StructuredOutput`], with the file it works on when its
+/// parameters name one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    #[serde(flatten)]
+    pub call: ToolCall,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// A turn split up for rendering: what the model thought, what it answered
+/// and which tools it used.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructuredOutput {
+    /// Text of thought events and of messages flagged as thoughts.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub thinking: String,
+    /// Text of the assistant's other messages.
+    pub answer: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
+}
+
+impl StructuredOutput {
+    /// Append the sections of a later turn, separating text with blank lines.
+    fn extend(&mut self, other: &StructuredOutput) {
+        for (text, more) in [
+            (&mut self.thinking, &other.thinking),
+            (&mut self.answer, &other.answer),
+        ] {
+            if !text.is_empty() && !more.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(more);
+        }
+        self.actions.extend(other.actions.iter().cloned());
+    }
+}
+
+/// Parameters naming the file or directory a tool works on.
+const PATH_PARAMETERS: [&str; 4] = ["file_path", "absolute_path", "path", "dir_path"];
+
+/// gemini's tools that write the file named in their parameters.
+const WRITE_TOOLS: [&str; 3] = ["write_file", "replace", "edit"];
+
+/// The file or directory a `tool_use` event works on.
+fn tool_path(event: &GeminiEvent) -> Option<String> {
+    let parameters = event.extra.get("parameters")?;
+    PATH_PARAMETERS
+        .iter()
+        .find_map(|key| parameters.get(*key)?.as_str().map(str::to_string))
+}
+
+/// The file a `tool_use` event writes, for the writing tools.
+fn written_path(event: &GeminiEvent) -> Option<String> {
+    if event.event_type.as_deref() != Some("tool_use") {
+        return None;
+    }
+    let tool = event.extra.get("tool_name")?.as_str()?;
+    WRITE_TOOLS
+        .contains(&tool)
+        .then(|| tool_path(event))
+        .flatten()
+}
+
+/// Whether an event carries the model's thinking rather than its answer:
+/// a `thought` / `thinking` event or role, or a message flagged `thought`.
+fn is_thought(event: &GeminiEvent) -> bool {
+    let thought = |s: Option<&str>| matches!(s, Some("thought" | "thinking"));
+    thought(event.event_type.as_deref())
+        || thought(event.role.as_deref())
+        || event.extra.get("thought").and_then(|v| v.as_bool()) == Some(true)
+}
+
+/// Add an event to `structured`; `tool_calls` already includes it.
+fn structure_event(
+    structured: &mut StructuredOutput,
+    event: &GeminiEvent,
+    tool_calls: &[ToolCall],
+    join: &str,
+) {
+    let append = |text: &mut String, content: &str| {
+        if !text.is_empty() {
+            text.push_str(join);
+        }
+        text.push_str(content);
+    };
+    if let Some(content) = event.content.as_deref() {
+        if is_thought(event) {
+            append(&mut structured.thinking, content);
+        } else if event.event_type.as_deref() == Some("message")
+            && event.role.as_deref() == Some("assistant")
+            && !content.contains(DEPRECATED_PROMPT_WARNING)
+        {
+            append(&mut structured.answer, content);
+        }
+    }
+    if event.event_type.as_deref() == Some("tool_use") {
+        if let Some(call) = tool_calls.last() {
+            structured.actions.push(Action {
+                call: call.clone(),
+                path: tool_path(event),
+            });
+        }
+    }
+}
+
+/// Default message when a turn produces no assistant text; `{tool_calls}` is
+/// replaced by a summary of the turn's tool calls.
+pub const DEFAULT_NO_MESSAGES_TEMPLATE: &str =
+    "gemini returned no assistant text. Tool calls: {tool_calls}. \
+You can continue using the `SESSION_ID` to proceed with the conversation.";
+
+/// Summarize tool calls as e.g. `write_file (success), glob`.
+pub fn summarize_tool_calls(calls: &[ToolCall]) -> String {
+    if calls.is_empty() {
+        return "none".to_string();
+    }
+    calls
+        .iter()
+        .map(|call| match &call.status {
+            Some(status) => format!("{} ({})", call.name, status),
+            None => call.name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Token counts reported by gemini for a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// Tokens the model spent thinking, when gemini reports them apart from
+    /// the output tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_tokens: Option<u64>,
+}
+
+impl TokenUsage {
+    /// Add the counts of another report to this one.
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+        self.thinking_tokens = match (self.thinking_tokens, other.thinking_tokens) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Category of a problem met while reading gemini's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorEntryKind {
+    /// A line was not a valid JSON event.
+    JsonDecode,
+    /// Reading the output failed.
+    Io,
+}
+
+/// A problem met while reading gemini's output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorEntry {
+    pub kind: ErrorEntryKind,
+    pub message: String,
+    /// 1-based output line the problem was found on, when it concerns one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl std::fmt::Display for ErrorEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.kind, self.line) {
+            (ErrorEntryKind::JsonDecode, Some(line)) => {
+                write!(f, "[json decode error @ line {}] {}", line, self.message)
+            }
+            (ErrorEntryKind::JsonDecode, None) => write!(f, "[json decode error] {}", self.message),
+            (ErrorEntryKind::Io, _) => write!(f, "[io error] {}", self.message),
+        }
+    }
+}
+
 /// Result of a Gemini CLI execution.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeminiResult {
     pub success: bool,
     #[serde(rename = "SESSION_ID", skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// The `SESSION_ID` a call resumed, when gemini reported another one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_session_id: Option<String>,
+    /// The session id gemini reported, when it differs from the resumed one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_session_id: Option<String>,
+    /// The session with what the server knows about it, to pass back to
+    /// [`SessionHandle::resume`] or `gemini_resume`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionHandle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_messages: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_model: Option<String>,
+    /// Name of the configured gemini executable that ran, when the request
+    /// selected one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+    /// The `SEED` gemini ran with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<Plan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Exit code of the `post_process` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_process_status: Option<i32>,
+    /// What `usage` cost at the server's model costs, when it cost anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Vec<Citation>>,
+    /// gemini's activity reports ("Analyzing...", "Searching..."), in order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_updates: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub all_messages: Option<Vec<serde_json::Value>>,
+    /// How the run ended: `eof`, `turn_completed`, `stopped_early`,
+    /// `reauth_required`, `stream_error`, `no_completion`,
+    /// `stream_unparseable`, `timeout`, `first_event_timeout`, `cancelled` or
+    /// `read_error`, followed by `/killed_after_wait` when gemini then had to
+    /// be killed after the grace period.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_path: Option<String>,
+    /// Files gemini's tool calls wrote outside the workspace and the include
+    /// directories, under `outside_writes`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outside_writes: Vec<PathBuf>,
+    /// Peak resident memory of the gemini process in bytes, when requested and
+    /// supported (Linux).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_rss_bytes: Option<u64>,
+    /// The turn split into thinking, answer and actions, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured: Option<StructuredOutput>,
+    /// What `auto_sandbox` decided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox_decision: Option<SandboxDecision>,
+    /// One [`EventSummary`] per event, for `OUTPUT_DETAIL: summary`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_summary: Option<Vec<EventSummary>>,
+    /// The JSON value parsed from the reply, under `expect_json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_json: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// URI of the MCP resource the result was published at while it
+    /// accumulated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_uri: Option<String>,
+    /// The prompt gemini received for the request, after the server's
+    /// transformations; redacted and truncated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_prompt: Option<String>,
+    /// Set when the response was cut to fit the payload limit: the `request_id`
+    /// to pass to `gemini_messages_page` for the rest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+    /// Hash of the normalized inputs, usable as a cache key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_fingerprint: Option<String>,
+    /// Served from the server's response cache.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turns: Option<u32>,
+    /// Files created or modified under `OUTPUT_DIR` during the run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<PathBuf>,
+    /// Files in the workspace written during the run despite not having an
+    /// allowed extension.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_writes: Vec<PathBuf>,
+    /// The `forbidden_writes` that were undone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reverted_writes: Vec<PathBuf>,
+    /// Files changed in the scratch copy, relative to it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scratch_changes: Vec<PathBuf>,
+    /// Where the scratch copy was kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scratch_dir: Option<PathBuf>,
+    /// Number of parts an oversized prompt was sent in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>,
+    /// Size of the compressed `CONTEXT` relative to the original, when
+    /// `compress_context` compressed it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_ratio: Option<f64>,
+    /// The run was cut short by `max_assistant_messages`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stopped_early: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<FailureKind>,
+    /// Problems met while reading the output (the last 10), on failure.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorEntry>,
+    /// Shape of the tool result carrying this one, with `--debug-envelope`.
+    #[serde(rename = "_debug", skip_serializing_if = "Option::is_none")]
+    pub debug: Option<DebugEnvelope>,
+}
+
+/// Shape of the tool result the server sends, measured without `_debug`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugEnvelope {
+    /// The content blocks, in order.
+    pub content: Vec<ContentBlockInfo>,
+    /// Size in bytes of `structuredContent`, when it is sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_content_bytes: Option<usize>,
+    pub is_error: bool,
+}
+
+/// Type and size of a content block of the tool result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentBlockInfo {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Size in bytes of the block's text.
+    pub bytes: usize,
+}
+
+impl GeminiResult {
+    /// A failed result carrying the error's message and category.
+    pub fn from_error(error: &GeminiError) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            failure_kind: Some(error.failure_kind()),
+            ..Default::default()
+        }
+    }
+
+    /// Combine the results of several runs, e.g. the parts of a batch.
+    ///
+    /// The merged result succeeds only if every run did. Text is joined with
+    /// blank lines; events, tool calls, citations, warnings and errors are
+    /// concatenated; usage, `turns` and `chunks` are summed. Session,
+    /// checkpoint, model, finish reason, exit path and plan come from the last
+    /// run that reported them, and the failure kind from the first failed run.
+    /// Per-request fields (`request_id`, fingerprint, continuation token) are
+    /// left unset.
+    pub fn merge(results: &[GeminiResult]) -> GeminiResult {
+        let last = |field: fn(&GeminiResult) -> &Option<String>| {
+            results.iter().rev().find_map(|r| field(r).clone())
+        };
+
+        let mut merged = GeminiResult {
+            success: results.iter().all(|r| r.success),
+            session_id: last(|r| &r.session_id),
+            session: results.iter().rev().find_map(|r| r.session.clone()),
+            original_session_id: results.iter().find_map(|r| r.original_session_id.clone()),
+            current_session_id: last(|r| &r.current_session_id),
+            checkpoint_id: last(|r| &r.checkpoint_id),
+            effective_model: last(|r| &r.effective_model),
+            finish_reason: last(|r| &r.finish_reason),
+            exit_path: last(|r| &r.exit_path),
+            plan: results.iter().rev().find_map(|r| r.plan.clone()),
+            turns: results.iter().filter_map(|r| r.turns).reduce(|a, b| a + b),
+            peak_rss_bytes: results.iter().filter_map(|r| r.peak_rss_bytes).max(),
+            chunks: results.iter().filter_map(|r| r.chunks).reduce(|a, b| a + b),
+            cached: !results.is_empty() && results.iter().all(|r| r.cached),
+            stopped_early: results.iter().any(|r| r.stopped_early),
+            failure_kind: results.iter().find_map(|r| r.failure_kind),
+            ..Default::default()
+        };
+
+        let texts: Vec<&str> = results
+            .iter()
+            .filter_map(|r| r.agent_messages.as_deref())
+            .collect();
+        merged.agent_messages = (!texts.is_empty()).then(|| texts.join("\n\n"));
+
+        let errors: Vec<&str> = results.iter().filter_map(|r| r.error.as_deref()).collect();
+        merged.error = (!errors.is_empty()).then(|| errors.join("\n\n"));
+
+        for result in results {
+            if let Some(u) = &result.usage {
+                merged.usage.get_or_insert_with(TokenUsage::default).add(u);
+            }
+            if let Some(events) = &result.all_messages {
+                merged
+                    .all_messages
+                    .get_or_insert_with(Vec::new)
+                    .extend(events.iter().cloned());
+            }
+            if let Some(summary) = &result.event_summary {
+                merged
+                    .event_summary
+                    .get_or_insert_with(Vec::new)
+                    .extend(summary.iter().cloned());
+            }
+            if let Some(structured) = &result.structured {
+                merged
+                    .structured
+                    .get_or_insert_with(StructuredOutput::default)
+                    .extend(structured);
+            }
+            for citation in result.citations.iter().flatten() {
+                let citations = merged.citations.get_or_insert_with(Vec::new);
+                if !citations.contains(citation) {
+                    citations.push(citation.clone());
+                }
+            }
+            merged.tool_calls.extend(result.tool_calls.iter().cloned());
+            merged.warnings.extend(result.warnings.iter().cloned());
+            merged.errors.extend(result.errors.iter().cloned());
+        }
+
+        merged
+    }
 }
 
 /// Escape special characters for Windows command line.
@@ -53,16 +575,48 @@ fn windows_escape(prompt: &str) -> String {
         .replace('\'', "\\'")
 }
 
-#[cfg(not(windows))]
-fn windows_escape(prompt: &str) -> String {
-    prompt.to_string()
+/// The prompt as passed to gemini: escaped on Windows unless `raw`.
+pub(crate) fn prompt_arg(prompt: &str, raw: bool) -> String {
+    if raw {
+        return prompt.to_string();
+    }
+    #[cfg(windows)]
+    let prompt = windows_escape(prompt);
+    #[cfg(not(windows))]
+    let prompt = prompt.to_string();
+    prompt
 }
 
-/// Find the gemini executable path.
-fn find_gemini_executable() -> Result<String> {
-    which::which("gemini")
+/// A path `gemini` was found at, and the PATH it was looked up in.
+type CachedExecutable = (Option<OsString>, String);
+
+/// Where `gemini` was last found, so runs skip the lookup.
+static GEMINI_EXECUTABLE: std::sync::Mutex<Option<CachedExecutable>> = std::sync::Mutex::new(None);
+
+/// Find the gemini executable path, looking it up in PATH only the first time,
+/// once PATH has changed or once the path found before no longer exists. A
+/// gemini installed ahead of it in an unchanged PATH is only found after
+/// [`forget_gemini_executable`].
+pub(crate) fn find_gemini_executable() -> Result<String> {
+    let search_path = std::env::var_os("PATH");
+    let mut cached = GEMINI_EXECUTABLE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, path)) = cached
+        .as_ref()
+        .filter(|(searched, path)| *searched == search_path && Path::new(path).is_file())
+    {
+        return Ok(path.clone());
+    }
+    let path = which::which("gemini")
         .map(|p| p.to_string_lossy().to_string())
-        .map_err(|_| GeminiError::GeminiNotFound)
+        .map_err(|_| GeminiError::GeminiNotFound)?;
+    *cached = Some((search_path, path.clone()));
+    Ok(path)
+}
+
+/// Look gemini up in PATH again on the next run, e.g. after it failed to
+/// start because it was reinstalled elsewhere.
+pub fn forget_gemini_executable() {
+    *GEMINI_EXECUTABLE.lock().unwrap_or_else(|e| e.into_inner()) = None;
 }
 
 /// Check if the event indicates turn completion.
@@ -70,17 +624,643 @@ fn is_turn_completed(event: &GeminiEvent) -> bool {
     event.event_type.as_deref() == Some("turn.completed")
 }
 
+/// Find the session id of an event, tolerating key drift between gemini versions.
+///
+/// Checks the typed `session_id` field, then an upper-case `SESSION_ID` key,
+/// then the optional JSON pointer (e.g. `/session/id`).
+fn extract_session_id(
+    event: &GeminiEvent,
+    raw: &serde_json::Value,
+    pointer: Option<&str>,
+) -> Option<String> {
+    event
+        .session_id
+        .clone()
+        .or_else(|| raw.get("SESSION_ID")?.as_str().map(str::to_string))
+        .or_else(|| raw.pointer(pointer?)?.as_str().map(str::to_string))
+}
+
+/// Keys a completion event may carry its finish/stop reason under.
+const FINISH_REASON_KEYS: [&str; 4] = ["finish_reason", "finishReason", "stop_reason", "reason"];
+
+/// Extract why the turn ended from a completion event.
+fn finish_reason(event: &GeminiEvent) -> Option<String> {
+    FINISH_REASON_KEYS
+        .iter()
+        .find_map(|key| event.extra.get(*key)?.as_str())
+        .map(str::to_string)
+}
+
+/// Build a citation from an object with a `url` (or `uri` / `link`), `title`
+/// and `snippet` (or `text`), possibly nested under `web` as in grounding
+/// chunks.
+fn parse_citation(value: &serde_json::Value) -> Option<Citation> {
+    let fields = value.get("web").unwrap_or(value).as_object()?;
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| fields.get(*key)?.as_str())
+            .map(str::to_string)
+    };
+    let citation = Citation {
+        url: field(&["url", "uri", "link"]),
+        title: field(&["title"]),
+        snippet: field(&["snippet", "text"])
+            .or_else(|| value.get("snippet")?.as_str().map(str::to_string)),
+    };
+    (citation.url.is_some() || citation.title.is_some()).then_some(citation)
+}
+
+/// Citations carried by an event: a `citation` / `source` event itself, or
+/// a `citations` / `sources` array on any event.
+fn parse_citations(event: &GeminiEvent) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    if matches!(event.event_type.as_deref(), Some("citation" | "source")) {
+        let mut fields = serde_json::Value::Object(event.extra.clone());
+        if let Some(content) = &event.content {
+            fields["snippet"] = content.clone().into();
+        }
+        citations.extend(parse_citation(&fields));
+    }
+    for key in ["citations", "sources"] {
+        if let Some(list) = event.extra.get(key).and_then(|v| v.as_array()) {
+            citations.extend(list.iter().filter_map(parse_citation));
+        }
+    }
+    citations
+}
+
+/// The message of an `error` event or a failed `result`, or `None` for any
+/// other event.
+fn stream_error(event: &GeminiEvent, raw: &serde_json::Value) -> Option<String> {
+    let failed = match event.event_type.as_deref() {
+        Some("error") => true,
+        Some("result") => raw["status"] == "error",
+        _ => false,
+    };
+    if !failed {
+        return None;
+    }
+
+    let content = event.content.as_deref().map(serde_json::Value::from);
+    Some(
+        [&raw["message"], &raw["error"], &raw["error"]["message"]]
+            .into_iter()
+            .chain(content.as_ref())
+            .filter_map(|v| v.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// The text of a `status` or `progress` event, which reports what gemini is
+/// doing rather than carrying assistant content.
+fn status_update(event: &GeminiEvent) -> Option<String> {
+    if !matches!(event.event_type.as_deref(), Some("status" | "progress")) {
+        return None;
+    }
+    event
+        .content
+        .clone()
+        .or_else(|| {
+            ["message", "status", "text"]
+                .iter()
+                .find_map(|key| event.extra.get(*key)?.as_str())
+                .map(str::to_string)
+        })
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Whether an `error` event, or a failed `result`, reports an exhausted API quota.
+fn is_quota_error(event: &GeminiEvent, raw: &serde_json::Value) -> bool {
+    let Some(message) = stream_error(event, raw) else {
+        return false;
+    };
+    let message = message.to_lowercase();
+    ["quota", "resource_exhausted", "rate limit"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Phrases with which gemini asks to log in again, matched case-insensitively.
+const REAUTH_PATTERNS: &[&str] = &[
+    "re-authenticate",
+    "reauthenticate",
+    "authentication required",
+    "login required",
+    "please log in",
+    "please login",
+    "credentials have expired",
+];
+
+/// Whether `text` asks to log in again.
+fn is_reauth_text(text: &str) -> bool {
+    let text = text.to_lowercase();
+    REAUTH_PATTERNS.iter().any(|pattern| text.contains(pattern))
+}
+
+/// The text of an `error` or `status` event asking to log in again.
+fn reauth_signal(event: &GeminiEvent, raw: &serde_json::Value) -> Option<String> {
+    stream_error(event, raw)
+        .or_else(|| status_update(event))
+        .filter(|text| is_reauth_text(text))
+}
+
+/// Record a `tool_use` event as a new call, or a `tool_result` as the status
+/// of the call it answers.
+fn track_tool_call(calls: &mut Vec<ToolCall>, event: &GeminiEvent) {
+    let field = |key: &str| {
+        event
+            .extra
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    match event.event_type.as_deref() {
+        Some("tool_use") => calls.push(ToolCall {
+            name: field("tool_name").unwrap_or_else(|| "unknown".to_string()),
+            id: field("tool_id"),
+            status: None,
+        }),
+        Some("tool_result") => {
+            let id = field("tool_id");
+            if let Some(call) = calls.iter_mut().rev().find(|c| id.is_some() && c.id == id) {
+                call.status = field("status");
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Build a plan from a `plan` (or `plan.*`) event.
+///
+/// Steps may be plain strings or objects carrying a `description` (or `title` /
+/// `content`) and an optional `status`.
+fn parse_plan(event: &GeminiEvent) -> Option<Plan> {
+    let event_type = event.event_type.as_deref()?;
+    if event_type != "plan" && !event_type.starts_with("plan.") {
+        return None;
+    }
+
+    let steps = event.extra.get("steps")?.as_array()?;
+    let steps = steps
+        .iter()
+        .filter_map(|step| match step {
+            serde_json::Value::String(description) => Some(PlanStep {
+                description: description.clone(),
+                status: None,
+            }),
+            serde_json::Value::Object(fields) => {
+                let description = ["description", "title", "content"]
+                    .iter()
+                    .find_map(|key| fields.get(*key)?.as_str())?;
+                Some(PlanStep {
+                    description: description.to_string(),
+                    status: fields
+                        .get("status")
+                        .and_then(|s| s.as_str())
+                        .map(str::to_string),
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    Some(Plan { steps })
+}
+
+/// Keys an event may carry token counts under, with their field aliases.
+const USAGE_KEYS: [&str; 3] = ["usage", "stats", "usageMetadata"];
+const INPUT_TOKEN_KEYS: [&str; 4] = [
+    "input_tokens",
+    "inputTokens",
+    "prompt_tokens",
+    "promptTokenCount",
+];
+const OUTPUT_TOKEN_KEYS: [&str; 4] = [
+    "output_tokens",
+    "outputTokens",
+    "completion_tokens",
+    "candidatesTokenCount",
+];
+const TOTAL_TOKEN_KEYS: [&str; 3] = ["total_tokens", "totalTokens", "totalTokenCount"];
+const THINKING_TOKEN_KEYS: [&str; 5] = [
+    "thinking_tokens",
+    "thinkingTokens",
+    "thoughts_tokens",
+    "thoughtsTokenCount",
+    "reasoning_tokens",
+];
+
+/// Extract token counts from an event's `usage` / `stats` object.
+fn parse_usage(raw: &serde_json::Value) -> Option<TokenUsage> {
+    let usage = USAGE_KEYS
+        .iter()
+        .find_map(|key| raw.get(*key)?.as_object())?;
+    let count = |keys: &[&str]| keys.iter().find_map(|key| usage.get(*key)?.as_u64());
+
+    let input_tokens = count(&INPUT_TOKEN_KEYS);
+    let output_tokens = count(&OUTPUT_TOKEN_KEYS);
+    let total_tokens = count(&TOTAL_TOKEN_KEYS);
+    let thinking_tokens = count(&THINKING_TOKEN_KEYS);
+    if input_tokens.is_none()
+        && output_tokens.is_none()
+        && total_tokens.is_none()
+        && thinking_tokens.is_none()
+    {
+        return None;
+    }
+
+    let input_tokens = input_tokens.unwrap_or(0);
+    let output_tokens = output_tokens.unwrap_or(0);
+    Some(TokenUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: total_tokens
+            .unwrap_or(input_tokens + output_tokens + thinking_tokens.unwrap_or(0)),
+        thinking_tokens,
+    })
+}
+
 /// Deprecated prompt warning to filter out.
 const DEPRECATED_PROMPT_WARNING: &str = "The --prompt (-p) flag has been deprecated";
 
+/// How the gemini process is shut down after a timeout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum KillMode {
+    /// Give the process a few seconds to exit on its own before killing it.
+    #[default]
+    Graceful,
+    /// Kill the process right away.
+    Immediate,
+}
+
+/// How the prompt is handed to gemini.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptPassing {
+    /// Whatever the installed gemini prefers, once probed; `--prompt` until
+    /// then.
+    #[default]
+    Auto,
+    /// As the value of `--prompt`.
+    Flag,
+    /// As the last, positional argument.
+    Positional,
+    /// On gemini's stdin.
+    Stdin,
+}
+
+impl FromStr for PromptPassing {
+    type Err = String;
+
+    /// Parse `auto`, `flag`, `positional` or `stdin`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "flag" => Ok(Self::Flag),
+            "positional" => Ok(Self::Positional),
+            "stdin" => Ok(Self::Stdin),
+            _ => Err(format!(
+                "expected auto, flag, positional or stdin, got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do with a workspace or include path that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonUtf8Paths {
+    /// Hand the path to gemini unchanged, as a raw OS string.
+    #[default]
+    PassThrough,
+    /// Refuse to run gemini.
+    Reject,
+}
+
+/// What to do when gemini's tool calls write a file outside the workspace and
+/// the include directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutsideWrites {
+    /// Do not check.
+    #[default]
+    Ignore,
+    /// Report them in `outside_writes`, with a warning.
+    Warn,
+    /// Also fail the call with `write_outside_workspace`.
+    Fail,
+}
+
+/// Which of the server's environment variables gemini inherits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnvPolicy {
+    /// All of them.
+    #[default]
+    Inherit,
+    /// Only those matching the server's allowlist.
+    Allowlist,
+}
+
+impl FromStr for EnvPolicy {
+    type Err = String;
+
+    /// Parse `inherit` or `allowlist`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "inherit" => Ok(Self::Inherit),
+            "allowlist" => Ok(Self::Allowlist),
+            _ => Err(format!("expected inherit or allowlist, got {:?}", s)),
+        }
+    }
+}
+
+/// Whether the environment variable `name` matches a pattern of `allowlist`,
+/// either exactly or, for a pattern ending in `*`, by prefix.
+pub fn env_allowed(name: &str, allowlist: &[String]) -> bool {
+    let matches = |a: &str, b: &str| {
+        if cfg!(windows) {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+    allowlist
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name
+                .get(..prefix.len())
+                .is_some_and(|start| matches(start, prefix)),
+            None => matches(name, pattern),
+        })
+}
+
+impl FromStr for OutsideWrites {
+    type Err = String;
+
+    /// Parse `ignore`, `warn` or `fail`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!("expected ignore, warn or fail, got {:?}", s)),
+        }
+    }
+}
+
+/// Encoding gemini's output is decoded from, UTF-8 unless configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputEncoding(&'static encoding_rs::Encoding);
+
+impl OutputEncoding {
+    pub fn is_utf8(&self) -> bool {
+        self.0 == encoding_rs::UTF_8
+    }
+
+    /// Decode a line, replacing malformed sequences with U+FFFD.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        self.0.decode_without_bom_handling(bytes).0
+    }
+}
+
+impl Default for OutputEncoding {
+    fn default() -> Self {
+        Self(encoding_rs::UTF_8)
+    }
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    /// Parse a WHATWG encoding label, e.g. `utf-8`, `windows-1252` or `gbk`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match encoding_rs::Encoding::for_label(s.as_bytes()) {
+            // Lines are split on b'\n', which UTF-16 does not keep intact
+            Some(encoding) if encoding.is_ascii_compatible() => Ok(Self(encoding)),
+            Some(encoding) => Err(format!(
+                "{} is not ASCII-compatible and cannot be read line by line",
+                encoding.name()
+            )),
+            None => Err(format!("unknown encoding {:?}", s)),
+        }
+    }
+}
+
+/// Set for gemini when its output is read as UTF-8, so that runtimes
+/// honoring it write UTF-8 whatever the console's code page.
+pub const UTF8_OUTPUT_ENV: &[(&str, &str)] = &[("PYTHONIOENCODING", "utf-8")];
+
+impl FromStr for NonUtf8Paths {
+    type Err = String;
+
+    /// Parse `pass-through` or `reject`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pass-through" => Ok(Self::PassThrough),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!("expected pass-through or reject, got {:?}", s)),
+        }
+    }
+}
+
+/// How much of gemini's event stream a result carries besides the text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputDetail {
+    /// The assistant text only.
+    #[default]
+    Text,
+    /// An [`EventSummary`] per event.
+    Summary,
+    /// Every raw event, as with `return_all_messages`.
+    Full,
+}
+
+/// How long output is still read after the turn completes, to pick up
+/// trailing events such as gemini's final stats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DrainMode {
+    /// Stop once gemini exits or goes quiet for twice its average gap between
+    /// events, and after 300ms at most.
+    #[default]
+    Adaptive,
+    /// Always wait the full 300ms.
+    Fixed,
+}
+
+/// Numbers the events of one request across the gemini runs it takes, so
+/// that `_seq` keeps counting through retries, prompt parts, fallbacks and
+/// continuations. Clones share the count.
+#[derive(Debug, Clone, Default)]
+pub struct EventSeq(Arc<AtomicU64>);
+
+impl EventSeq {
+    /// The number of the next event, counting from 1.
+    fn next(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// Per-invocation options for [`execute_gemini`].
+#[derive(Debug, Clone, Default)]
+pub struct GeminiOptions {
+    /// gemini subcommand placed before every flag (default: the prompt mode).
+    pub subcommand: Option<String>,
+    /// gemini executable to run (default: `gemini` from PATH).
+    pub executable: Option<PathBuf>,
+    /// Run gemini with `--sandbox`.
+    pub sandbox: bool,
+    /// Run gemini with `--checkpointing`.
+    pub checkpointing: bool,
+    /// Present the workspace read-only to gemini (kernel-enforced on Linux).
+    pub read_only: bool,
+    /// Session to resume.
+    pub session_id: Option<String>,
+    /// Model to use instead of gemini's default.
+    pub model: Option<String>,
+    /// Passed as `--seed`, for reproducible output.
+    pub seed: Option<u64>,
+    /// Passed as `--temperature`.
+    pub temperature: Option<f64>,
+    /// Collect every raw event into `all_messages`.
+    pub return_all_messages: bool,
+    /// Collect an [`EventSummary`] of every event into `event_summary`.
+    pub event_summary: bool,
+    /// Report gemini's peak resident memory as `peak_rss_bytes` (Linux only).
+    pub collect_resource_usage: bool,
+    /// Split the turn into a [`StructuredOutput`].
+    pub structured: bool,
+    /// How to shut the process down after a timeout.
+    pub kill_mode: KillMode,
+    /// How long to keep reading once the turn is complete.
+    pub drain_mode: DrainMode,
+    /// Whether a non-UTF-8 `cwd` or `output_dir` is passed on or rejected.
+    pub non_utf8_paths: NonUtf8Paths,
+    /// Encoding gemini's stdout and stderr are decoded from.
+    pub output_encoding: OutputEncoding,
+    /// How the prompt is handed to gemini; `Auto` means `--prompt`.
+    pub prompt_passing: PromptPassing,
+    /// Patterns of the environment variables gemini inherits (see
+    /// [`env_allowed`]); `None` passes the whole environment.
+    pub env_allowlist: Option<Vec<String>>,
+    /// Whether writes outside `cwd` and `output_dir` are checked, and how
+    /// they are reported.
+    pub outside_writes: OutsideWrites,
+    /// Tools gemini may call, passed as `--allowed-tools`.
+    pub allowed_tools: Vec<String>,
+    /// Tools gemini must not call, passed as `--exclude-tools`.
+    pub denied_tools: Vec<String>,
+    /// Directory for generated files, added to gemini's workspace with
+    /// `--include-directories` (gemini has no output-directory flag).
+    pub output_dir: Option<PathBuf>,
+    /// JSON pointer to the session id for gemini versions that nest it.
+    pub session_id_pointer: Option<String>,
+    /// Roles whose message content is collected into `agent_messages`
+    /// (default: `assistant` only).
+    pub content_roles: Vec<String>,
+    /// Prefix each run of collected text with `[role] ` when more than one
+    /// role is collected.
+    pub prefix_roles: bool,
+    /// Pass the prompt to gemini untouched, without escaping it on Windows.
+    pub raw_prompt: bool,
+    /// Inserted between collected chunks (default: none). gemini streams
+    /// assistant text as deltas that carry their own whitespace, so joining
+    /// them verbatim reproduces the message.
+    pub message_join: String,
+    /// Stop gemini once it has sent this many assistant messages.
+    pub max_assistant_messages: Option<usize>,
+    /// Stop gemini once its text contains any of these, cutting the text
+    /// before the sequence (gemini has no stop-sequence flag).
+    pub stop_sequences: Vec<String>,
+    /// Address-space limit for gemini in MiB (Linux only).
+    pub memory_limit_mb: Option<u64>,
+    /// API key exported to gemini as `GEMINI_API_KEY`.
+    pub api_key: Option<String>,
+    /// Correlation id exported to gemini as `GEMINI_MCP_REQUEST_ID`.
+    pub request_id: Option<String>,
+    /// Cancels the run; gemini is killed right away.
+    pub cancel: Option<CancellationToken>,
+    /// Give up once this many events arrived without `turn.completed`, a sign
+    /// of a stream that will never complete (default: only the timeout).
+    pub max_events_without_completion: Option<usize>,
+    /// Give up once more than this many lines failed to decode, a sign of an
+    /// event format the server does not understand (default: no limit).
+    pub max_decode_errors: Option<usize>,
+    /// Give up (and kill gemini) if no event arrives within this budget.
+    pub first_event_timeout: Option<Duration>,
+    /// Time allowed for gemini to finish the turn (default: 300s).
+    pub timeout: Option<Duration>,
+    /// End the turn by this point, if that comes before `timeout`.
+    pub deadline: Option<Deadline>,
+    /// Report a turn without assistant text as a success (with a warning)
+    /// rather than a `no_agent_messages` failure.
+    pub allow_empty_messages: bool,
+    /// End the run at the first `error` event (or failed `result`) instead of
+    /// reading on to the end of the turn.
+    pub fail_fast_on_stream_error: bool,
+    /// Message for a turn without assistant text, see
+    /// [`DEFAULT_NO_MESSAGES_TEMPLATE`].
+    pub no_messages_template: Option<String>,
+    /// Non-JSON lines matching any of these (e.g. a summary printed after the
+    /// stream) are skipped instead of recorded as decode errors.
+    pub benign_output: Vec<regex::Regex>,
+    /// Receive every non-empty raw output line, in the same pass that parses it.
+    pub sinks: Sinks,
+    /// Receives every parsed event as it arrives.
+    pub event_sink: Option<mpsc::UnboundedSender<GeminiEvent>>,
+    /// Receives each piece of assistant text as it is appended to
+    /// `agent_messages`; the pieces concatenate to the final value.
+    pub delta_sink: Option<mpsc::UnboundedSender<String>>,
+    /// Numbers the events for `_seq`; share it between the runs of one
+    /// request to number them as one stream.
+    pub event_seq: EventSeq,
+    /// Receives the text of each status update as it arrives.
+    pub status_sink: Option<mpsc::UnboundedSender<String>>,
+    /// Receives each line gemini writes to stderr as it arrives; stderr is
+    /// discarded when unset.
+    pub stderr_sink: Option<mpsc::UnboundedSender<String>>,
+}
+
+/// A gemini invocation running in the background, see [`stream_gemini`].
+pub struct GeminiStream {
+    /// Every parsed event, in order.
+    pub events: mpsc::UnboundedReceiver<GeminiEvent>,
+    /// Incremental assistant text, in order.
+    pub deltas: mpsc::UnboundedReceiver<String>,
+    /// Resolves to the final result once the run is over.
+    pub result: JoinHandle<Result<GeminiResult>>,
+}
+
+/// Start gemini in the background and stream its events and text deltas.
+///
+/// Any `event_sink` / `delta_sink` already set in `options` is replaced.
+pub fn stream_gemini(prompt: &str, cwd: &Path, options: GeminiOptions) -> GeminiStream {
+    let (event_tx, events) = mpsc::unbounded_channel();
+    let (delta_tx, deltas) = mpsc::unbounded_channel();
+    let options = GeminiOptions {
+        event_sink: Some(event_tx),
+        delta_sink: Some(delta_tx),
+        ..options
+    };
+    let prompt = prompt.to_string();
+    let cwd = cwd.to_path_buf();
+
+    let result = tokio::spawn(async move { execute_gemini(&prompt, &cwd, &options).await });
+
+    GeminiStream {
+        events,
+        deltas,
+        result,
+    }
+}
+
 /// Execute the Gemini CLI and stream its output.
 pub async fn execute_gemini(
     prompt: &str,
     cwd: &Path,
-    sandbox: bool,
-    session_id: Option<&str>,
-    model: Option<&str>,
-    return_all_messages: bool,
+    options: &GeminiOptions,
 ) -> Result<GeminiResult> {
     // Validate workspace directory
     if !cwd.exists() {
@@ -89,118 +1269,220 @@ pub async fn execute_gemini(
         ));
     }
 
-    // Find gemini executable
-    let gemini_path = find_gemini_executable()?;
+    if options.non_utf8_paths == NonUtf8Paths::Reject {
+        let paths = [Some(cwd), options.output_dir.as_deref()];
+        if let Some(path) = paths.into_iter().flatten().find(|p| p.to_str().is_none()) {
+            return Err(GeminiError::NonUtf8Path(path.to_path_buf()));
+        }
+    }
 
-    // Escape prompt on Windows
-    #[cfg(windows)]
-    let prompt = windows_escape(prompt);
-    #[cfg(not(windows))]
-    let prompt = prompt.to_string();
+    // Find gemini executable
+    let gemini_path = match &options.executable {
+        Some(path) => path.to_string_lossy().to_string(),
+        None => find_gemini_executable()?,
+    };
 
     // Build command arguments
-    let mut args = vec![
-        "--prompt".to_string(),
-        prompt,
-        "-o".to_string(),
-        "stream-json".to_string(),
-    ];
+    let mut args: Vec<OsString> = options.subcommand.iter().map(OsString::from).collect();
+    if matches!(
+        options.prompt_passing,
+        PromptPassing::Auto | PromptPassing::Flag
+    ) {
+        args.push("--prompt".into());
+        args.push(prompt_arg(prompt, options.raw_prompt).into());
+    }
+    args.extend(["-o".into(), STREAM_JSON.into()]);
+
+    if options.read_only {
+        args.extend(readonly::gemini_args().map(OsString::from));
+    } else if options.sandbox {
+        args.push("--sandbox".into());
+    }
+
+    if options.checkpointing {
+        args.push("--checkpointing".into());
+    }
+
+    for tool in &options.allowed_tools {
+        args.push("--allowed-tools".into());
+        args.push(tool.into());
+    }
+
+    for tool in &options.denied_tools {
+        args.push("--exclude-tools".into());
+        args.push(tool.into());
+    }
 
-    if sandbox {
-        args.push("--sandbox".to_string());
+    if let Some(dir) = &options.output_dir {
+        args.push("--include-directories".into());
+        args.push(dir.into());
     }
 
-    if let Some(m) = model {
+    if let Some(m) = &options.model {
         if !m.is_empty() {
-            args.push("--model".to_string());
-            args.push(m.to_string());
+            args.push("--model".into());
+            args.push(m.into());
         }
     }
 
-    if let Some(sid) = session_id {
+    if let Some(seed) = options.seed {
+        args.push("--seed".into());
+        args.push(seed.to_string().into());
+    }
+    if let Some(temperature) = options.temperature {
+        args.push("--temperature".into());
+        args.push(temperature.to_string().into());
+    }
+
+    if let Some(sid) = &options.session_id {
         if !sid.is_empty() {
-            args.push("--resume".to_string());
-            args.push(sid.to_string());
+            args.push("--resume".into());
+            args.push(sid.into());
         }
     }
 
-    // Spawn the process - use Stdio::null() for stderr to avoid deadlock
-    // when stderr buffer fills up
-    let mut child = Command::new(&gemini_path)
+    if options.prompt_passing == PromptPassing::Positional {
+        // Keep a prompt starting with a dash from being read as an option
+        if prompt.starts_with('-') {
+            args.push("--".into());
+        }
+        args.push(prompt_arg(prompt, options.raw_prompt).into());
+    }
+
+    // Spawn the process. stderr is read by a task alongside stdout; an unread
+    // pipe would deadlock gemini once its buffer fills up
+    let stdin_prompt = options.prompt_passing == PromptPassing::Stdin;
+    let mut command = Command::new(&gemini_path);
+    command
         .args(&args)
         .current_dir(cwd)
-        .stdin(Stdio::null())
+        .stdin(if stdin_prompt {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
         .stdout(Stdio::piped())
-        .stderr(Stdio::null()) // Avoid deadlock by not piping stderr
-        .spawn()?;
+        .stderr(Stdio::piped());
+
+    // Cleared first, so the variables set below always get through
+    if let Some(allowlist) = &options.env_allowlist {
+        command.env_clear().envs(
+            std::env::vars_os()
+                .filter(|(name, _)| name.to_str().is_some_and(|n| env_allowed(n, allowlist))),
+        );
+    }
+
+    if options.output_encoding.is_utf8() {
+        command.envs(UTF8_OUTPUT_ENV.iter().copied());
+    }
 
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let mut reader = BufReader::new(stdout).lines();
+    if let Some(request_id) = &options.request_id {
+        command.env(REQUEST_ID_ENV, request_id);
+    }
 
-    // Only collect all_messages when needed to save memory
-    let mut all_messages: Option<Vec<serde_json::Value>> = if return_all_messages {
-        Some(Vec::new())
+    if let Some(api_key) = &options.api_key {
+        command.env(API_KEY_ENV, api_key);
+    }
+
+    if let Some(limit_mb) = options.memory_limit_mb {
+        limits::limit_memory(&mut command, limit_mb);
+    }
+
+    let spawned = if options.read_only {
+        readonly::restrict(&mut command, cwd)
+            .and_then(|_| command.spawn())
+            .map_err(GeminiError::ReadOnlyUnavailable)
     } else {
-        None
+        command
+            .spawn()
+            .map_err(|e| GeminiError::from_spawn_error(e, &gemini_path, cwd))
     };
-    let mut agent_messages = String::new();
-    let mut session_id_result: Option<String> = None;
-    let mut error_messages: VecDeque<String> = VecDeque::new();
+    if spawned.is_err() && options.executable.is_none() {
+        forget_gemini_executable();
+    }
+    let mut child = spawned?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Written alongside the read so a long prompt cannot block on the pipe
+        let prompt = prompt.to_string();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(prompt.as_bytes()).await;
+        });
+    }
+    let mut process = GeminiProcess::new(child);
+    let peak_rss = options
+        .collect_resource_usage
+        .then(|| process.id())
+        .flatten()
+        .map(PeakRss::watch);
+
+    let stdout = process.take_stdout().expect("Failed to capture stdout");
+    // Split on raw bytes rather than `lines()` so a non-UTF-8 chunk (e.g. from a
+    // misconfigured Windows console code page) is decoded lossily instead of
+    // aborting the whole read with an `InvalidData` error.
+    let mut reader = BufReader::new(stdout).split(b'\n');
+    let (reauth_tx, mut reauth_rx) = mpsc::unbounded_channel();
+    let stderr_task = process.take_stderr().map(|stderr| {
+        tokio::spawn(watch_stderr(
+            stderr,
+            options.output_encoding,
+            options.stderr_sink.clone(),
+            reauth_tx,
+        ))
+    });
+
+    let mut state = StreamState::new(options);
 
     // Read output with timeout
-    let read_result = timeout(Duration::from_secs(PROCESS_TIMEOUT_SECS), async {
+    let mut process_timeout = options
+        .timeout
+        .unwrap_or(Duration::from_secs(PROCESS_TIMEOUT_SECS));
+    if let Some(deadline) = options.deadline {
+        process_timeout = process_timeout.min(deadline.remaining());
+    }
+    let first_event_deadline = options
+        .first_event_timeout
+        .map(|budget| tokio::time::Instant::now() + budget);
+    let mut completed = false;
+    let mut gaps = EventGaps::default();
+    let read_result = timeout(process_timeout, async {
         loop {
-            match reader.next_line().await {
-                Ok(Some(line)) => {
-                    let line = line.trim().to_string();
-                    if line.is_empty() {
-                        continue;
-                    }
-
-                    // Try to parse as JSON
-                    match serde_json::from_str::<GeminiEvent>(&line) {
-                        Ok(event) => {
-                            // Store raw value if needed
-                            if let Some(ref mut messages) = all_messages {
-                                if let Ok(value) = serde_json::from_str::<serde_json::Value>(&line)
-                                {
-                                    messages.push(value);
-                                }
-                            }
-
-                            // Extract session_id
-                            if event.session_id.is_some() {
-                                session_id_result = event.session_id.clone();
-                            }
-
-                            // Extract assistant messages
-                            if event.event_type.as_deref() == Some("message")
-                                && event.role.as_deref() == Some("assistant")
-                            {
-                                if let Some(content) = &event.content {
-                                    if !content.contains(DEPRECATED_PROMPT_WARNING) {
-                                        agent_messages.push_str(content);
-                                    }
-                                }
-                            }
-
-                            // Check for turn completion
-                            if is_turn_completed(&event) {
-                                tokio::time::sleep(Duration::from_millis(
-                                    GRACEFUL_SHUTDOWN_DELAY_MS,
-                                ))
-                                .await;
-                                break;
-                            }
+            let awaiting_first_event = state.events_seen == 0;
+            let segment = tokio::select! {
+                segment = reader.next_segment() => segment,
+                _ = cancelled(options) => {
+                    state.cancelled = true;
+                    state.exit_path = "cancelled";
+                    break;
+                }
+                _ = deadline(first_event_deadline), if awaiting_first_event => {
+                    state.first_event_timed_out = true;
+                    state.exit_path = "first_event_timeout";
+                    break;
+                }
+                Some(message) = reauth_rx.recv() => {
+                    state.reauth_required = Some(message);
+                    state.exit_path = "reauth_required";
+                    break;
+                }
+            };
+            match segment {
+                Ok(Some(bytes)) => {
+                    gaps.observe();
+                    match state.push_line(&state.options.output_encoding.decode(&bytes)) {
+                        LineOutcome::Continue => {}
+                        LineOutcome::Completed => {
+                            completed = true;
+                            drain(&mut reader, &mut state, &mut gaps).await;
+                            break;
                         }
-                        Err(e) => {
-                            error_messages
-                                .push_back(format!("[json decode error] {}: {}", e, line));
-                            // Keep only last 10 error messages
-                            if error_messages.len() > 10 {
-                                error_messages.pop_front();
-                            }
+                        LineOutcome::StreamError => {
+                            drain(&mut reader, &mut state, &mut gaps).await;
+                            break;
                         }
+                        LineOutcome::StopEarly
+                        | LineOutcome::ReauthRequired
+                        | LineOutcome::NoCompletion
+                        | LineOutcome::Unparseable => break,
                     }
                 }
                 Ok(None) => {
@@ -209,58 +1491,787 @@ pub async fn execute_gemini(
                 }
                 Err(e) => {
                     // IO error - log it and break
-                    error_messages.push_back(format!("[io error] {}", e));
+                    state.push_error(ErrorEntryKind::Io, e.to_string(), None);
+                    state.exit_path = "read_error";
                     break;
                 }
             }
         }
     })
     .await;
+    if read_result.is_err() {
+        state.exit_path = "timeout";
+    }
+    let peak_rss_bytes = peak_rss.and_then(PeakRss::finish);
 
-    // Graceful process termination: wait first, then kill if necessary
-    let wait_result = timeout(Duration::from_secs(WAIT_TIMEOUT_SECS), child.wait()).await;
-    if wait_result.is_err() {
-        // Process didn't exit in time, force kill
-        let _ = child.kill().await;
-        let _ = child.wait().await;
-    }
-
-    // Build result
-    let mut result = GeminiResult {
-        success: true,
-        session_id: session_id_result.clone(),
-        agent_messages: None,
-        all_messages: None,
-        error: None,
-    };
+    if state.stopped_early
+        || state.cancelled
+        || state.first_event_timed_out
+        || state.reauth_required.is_some()
+        || ((read_result.is_err() || state.no_completion || state.unparseable)
+            && options.kill_mode == KillMode::Immediate)
+    {
+        // Stopped or cancelled on purpose, waiting for a login that cannot
+        // happen, or timed out and the caller wants the process reclaimed
+        // right away
+        process.kill().await;
+    } else {
+        match process
+            .shutdown(Duration::from_secs(WAIT_TIMEOUT_SECS))
+            .await
+        {
+            // gemini dying mid-turn under a memory limit almost certainly ran
+            // out of memory
+            Some(status) => {
+                if let Some(limit_mb) = options
+                    .memory_limit_mb
+                    .filter(|_| limits::MEMORY_LIMIT_SUPPORTED)
+                {
+                    if !completed
+                        && !state.no_completion
+                        && !state.unparseable
+                        && read_result.is_ok()
+                        && !status.success()
+                    {
+                        state.memory_limit_exit = Some((status, limit_mb));
+                    }
+                }
+            }
+            None => state.killed_after_wait = true,
+        }
+    }
 
-    // Check for errors
-    let error_suffix: String = error_messages.into_iter().collect::<Vec<_>>().join("\n");
+    if let Some(mut task) = stderr_task {
+        // Deliver the last lines before the result, unless something gemini
+        // started still holds stderr open
+        let grace = Duration::from_millis(GRACEFUL_SHUTDOWN_DELAY_MS);
+        if options.stderr_sink.is_none() || timeout(grace, &mut task).await.is_err() {
+            task.abort();
+        }
+    }
 
-    if read_result.is_err() {
-        result.success = false;
-        result.error = Some(format!("Process timeout. {}", error_suffix));
-    } else if session_id_result.is_none() {
-        result.success = false;
-        result.error = Some(format!(
-            "Failed to get `SESSION_ID` from the gemini session.\n\n{}",
-            error_suffix
-        ));
-    } else if agent_messages.is_empty() {
+    let written = std::mem::take(&mut state.written_paths);
+    let mut result = state.finish(read_result.is_err());
+    result.peak_rss_bytes = peak_rss_bytes;
+    if options.outside_writes != OutsideWrites::Ignore {
+        check_outside_writes(&mut result, &written, cwd, options);
+    }
+    Ok(result)
+}
+
+/// Report the `written` paths that resolve outside `cwd` and `output_dir`,
+/// failing the call under [`OutsideWrites::Fail`].
+fn check_outside_writes(
+    result: &mut GeminiResult,
+    written: &[String],
+    cwd: &Path,
+    options: &GeminiOptions,
+) {
+    let roots: Vec<PathBuf> = std::iter::once(cwd.to_path_buf())
+        .chain(options.output_dir.clone())
+        .collect();
+    for path in written {
+        if let Some(outside) = write_guard::outside_roots(path, cwd, &roots) {
+            if !result.outside_writes.contains(&outside) {
+                result.outside_writes.push(outside);
+            }
+        }
+    }
+    if result.outside_writes.is_empty() {
+        return;
+    }
+
+    let message = format!(
+        "gemini wrote outside the workspace: {}",
+        result
+            .outside_writes
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if options.outside_writes == OutsideWrites::Fail {
         result.success = false;
-        result.error = Some(format!(
-            "Failed to retrieve `agent_messages` data from the Gemini session. \
-            This might be due to Gemini performing a tool call. \
-            You can continue using the `SESSION_ID` to proceed with the conversation.\n\n{}",
-            error_suffix
-        ));
+        result.failure_kind = Some(FailureKind::WriteOutsideWorkspace);
+        result.error = Some(match result.error.take() {
+            Some(error) => format!("{}\n\n{}", message, error),
+            None => message,
+        });
     } else {
-        result.agent_messages = Some(agent_messages);
+        result.warnings.push(message);
     }
+}
 
-    if return_all_messages {
-        result.all_messages = all_messages;
+/// Send each non-empty line of gemini's stderr to `sink`, in order, and
+/// those asking to log in again to `reauth`.
+async fn watch_stderr(
+    stderr: ChildStderr,
+    encoding: OutputEncoding,
+    sink: Option<mpsc::UnboundedSender<String>>,
+    reauth: mpsc::UnboundedSender<String>,
+) {
+    let mut lines = BufReader::new(stderr).split(b'\n');
+    while let Ok(Some(bytes)) = lines.next_segment().await {
+        let line = encoding.decode(&bytes);
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        if is_reauth_text(line) {
+            let _ = reauth.send(line.to_string());
+        }
+        if let Some(sink) = &sink {
+            let _ = sink.send(line.to_string());
+        }
     }
+}
 
-    Ok(result)
+/// Timing of the events read so far.
+#[derive(Debug, Default)]
+struct EventGaps {
+    last: Option<tokio::time::Instant>,
+    total: Duration,
+    count: u32,
+}
+
+impl EventGaps {
+    /// Record an event arriving now.
+    fn observe(&mut self) {
+        let now = tokio::time::Instant::now();
+        if let Some(last) = self.last {
+            self.total += now - last;
+            self.count += 1;
+        }
+        self.last = Some(now);
+    }
+
+    /// How long the stream must stay quiet to count as idle: twice the
+    /// average gap between events, within the drain bounds.
+    fn idle_window(&self) -> Duration {
+        let max = Duration::from_millis(GRACEFUL_SHUTDOWN_DELAY_MS);
+        if self.count == 0 {
+            return max;
+        }
+        (self.total * 2 / self.count).clamp(Duration::from_millis(DRAIN_MIN_IDLE_MS), max)
+    }
+}
+
+/// Keep reading trailing events after the turn completes, for at most the
+/// graceful shutdown delay; see [`DrainMode`].
+async fn drain<R: AsyncBufRead + Unpin>(
+    reader: &mut Split<R>,
+    state: &mut StreamState<'_>,
+    gaps: &mut EventGaps,
+) {
+    let mode = state.options.drain_mode;
+    let until = tokio::time::Instant::now() + Duration::from_millis(GRACEFUL_SHUTDOWN_DELAY_MS);
+    loop {
+        let wait = match mode {
+            DrainMode::Adaptive => until.min(tokio::time::Instant::now() + gaps.idle_window()),
+            DrainMode::Fixed => until,
+        };
+        match tokio::time::timeout_at(wait, reader.next_segment()).await {
+            Ok(Ok(Some(bytes))) => {
+                gaps.observe();
+                if state.push_line(&state.options.output_encoding.decode(&bytes))
+                    == LineOutcome::StopEarly
+                {
+                    return;
+                }
+            }
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => {
+                state.push_error(ErrorEntryKind::Io, e.to_string(), None);
+                break;
+            }
+            // Idle, or out of time
+            Err(_) => return,
+        }
+    }
+    // gemini has exited
+    if mode == DrainMode::Fixed {
+        tokio::time::sleep_until(until).await;
+    }
+}
+
+/// Whether an unparseable line is known to be harmless.
+fn is_benign(options: &GeminiOptions, line: &str) -> bool {
+    options.benign_output.iter().any(|p| p.is_match(line))
+}
+
+/// Resolves once the run is cancelled, never if it has no token.
+async fn cancelled(options: &GeminiOptions) {
+    match &options.cancel {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves at the deadline, never without one.
+async fn deadline(at: Option<tokio::time::Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Replay recorded `stream-json` output through the event parser without
+/// running gemini, e.g. to test against fixtures captured from real runs.
+pub fn replay_stream(output: &str, options: &GeminiOptions) -> GeminiResult {
+    let mut state = StreamState::new(options);
+    for line in output.split('\n') {
+        if state.push_line(line) != LineOutcome::Continue {
+            break;
+        }
+    }
+    state.finish(false)
+}
+
+/// Like [`replay_stream`], but reading from `reader` so that read errors can be
+/// replayed too.
+pub async fn replay_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    options: &GeminiOptions,
+) -> GeminiResult {
+    let mut reader = BufReader::new(reader).split(b'\n');
+    let mut state = StreamState::new(options);
+    loop {
+        match reader.next_segment().await {
+            Ok(Some(bytes)) => {
+                if state.push_line(&state.options.output_encoding.decode(&bytes))
+                    != LineOutcome::Continue
+                {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                state.push_error(ErrorEntryKind::Io, e.to_string(), None);
+                break;
+            }
+        }
+    }
+    state.finish(false)
+}
+
+/// What to do after a line of gemini output has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineOutcome {
+    /// Keep reading.
+    Continue,
+    /// The turn is complete.
+    Completed,
+    /// `max_assistant_messages` was reached.
+    StopEarly,
+    /// gemini asked to log in again.
+    ReauthRequired,
+    /// gemini reported an error and `fail_fast_on_stream_error` is set.
+    StreamError,
+    /// `max_events_without_completion` was exceeded.
+    NoCompletion,
+    /// `max_decode_errors` was exceeded.
+    Unparseable,
+}
+
+impl LineOutcome {
+    /// The `exit_path` of a run ending with this outcome.
+    fn exit_path(self) -> Option<&'static str> {
+        match self {
+            LineOutcome::Continue => None,
+            LineOutcome::Completed => Some("turn_completed"),
+            LineOutcome::StopEarly => Some("stopped_early"),
+            LineOutcome::ReauthRequired => Some("reauth_required"),
+            LineOutcome::StreamError => Some("stream_error"),
+            LineOutcome::NoCompletion => Some("no_completion"),
+            LineOutcome::Unparseable => Some("stream_unparseable"),
+        }
+    }
+}
+
+/// Accumulates gemini's event stream into a [`GeminiResult`].
+struct StreamState<'a> {
+    options: &'a GeminiOptions,
+    /// Only collected when needed to save memory.
+    all_messages: Option<Vec<serde_json::Value>>,
+    event_summary: Option<Vec<EventSummary>>,
+    structured: Option<StructuredOutput>,
+    agent_messages: String,
+    /// Bytes of `agent_messages` already sent to the `delta_sink`.
+    deltas_sent: usize,
+    session_id: Option<String>,
+    checkpoint_id: Option<String>,
+    plan: Option<Plan>,
+    tool_calls: Vec<ToolCall>,
+    citations: Vec<Citation>,
+    status_updates: Vec<String>,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+    /// Collected messages of role `assistant`, other roles aside.
+    assistant_message_count: usize,
+    /// Role of the last collected chunk, used for prefixing.
+    last_role: Option<String>,
+    stopped_early: bool,
+    cancelled: bool,
+    /// Number of events parsed so far.
+    events_seen: usize,
+    first_event_timed_out: bool,
+    /// `turn.completed` arrived; later events are trailing ones.
+    turn_completed: bool,
+    /// More than `max_events_without_completion` events arrived.
+    no_completion: bool,
+    /// Number of lines that failed to decode.
+    decode_errors: usize,
+    /// Files named by writing tool calls, as gemini gave them.
+    written_paths: Vec<String>,
+    /// More than `max_decode_errors` lines failed to decode.
+    unparseable: bool,
+    /// Why reading stopped, see [`GeminiResult::exit_path`].
+    exit_path: &'static str,
+    /// gemini did not exit within the grace period and was killed.
+    killed_after_wait: bool,
+    /// gemini reported that its API key ran out of quota.
+    quota_exceeded: bool,
+    /// What gemini said when asking to log in again.
+    reauth_required: Option<String>,
+    /// Message of the error event that ended the run under
+    /// `fail_fast_on_stream_error`.
+    stream_error: Option<String>,
+    /// Abnormal exit under a memory limit, with the limit in MiB.
+    memory_limit_exit: Option<(std::process::ExitStatus, u64)>,
+    /// Number of lines read so far, counting blank ones.
+    line_number: usize,
+    errors: VecDeque<ErrorEntry>,
+}
+
+impl<'a> StreamState<'a> {
+    fn new(options: &'a GeminiOptions) -> Self {
+        Self {
+            options,
+            all_messages: options.return_all_messages.then(Vec::new),
+            event_summary: options.event_summary.then(Vec::new),
+            structured: options.structured.then(StructuredOutput::default),
+            agent_messages: String::new(),
+            deltas_sent: 0,
+            session_id: None,
+            checkpoint_id: None,
+            plan: None,
+            tool_calls: Vec::new(),
+            citations: Vec::new(),
+            status_updates: Vec::new(),
+            finish_reason: None,
+            usage: None,
+            assistant_message_count: 0,
+            last_role: None,
+            stopped_early: false,
+            cancelled: false,
+            events_seen: 0,
+            first_event_timed_out: false,
+            turn_completed: false,
+            no_completion: false,
+            decode_errors: 0,
+            written_paths: Vec::new(),
+            unparseable: false,
+            exit_path: "eof",
+            killed_after_wait: false,
+            quota_exceeded: false,
+            reauth_required: None,
+            stream_error: None,
+            memory_limit_exit: None,
+            line_number: 0,
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Record a problem, keeping only the last 10.
+    fn push_error(&mut self, kind: ErrorEntryKind, message: String, line: Option<usize>) {
+        self.errors.push_back(ErrorEntry {
+            kind,
+            message,
+            line,
+        });
+        if self.errors.len() > 10 {
+            self.errors.pop_front();
+        }
+    }
+
+    /// Whether message content of this role goes into `agent_messages`.
+    fn collects_role(&self, role: &str) -> bool {
+        if self.options.content_roles.is_empty() {
+            role == "assistant"
+        } else {
+            self.options.content_roles.iter().any(|r| r == role)
+        }
+    }
+
+    /// The text to append for a chunk: `message_join` between chunks, or a
+    /// `[role] ` prefix on its own line whenever the role changes if prefixing
+    /// applies.
+    fn chunk_text(&mut self, role: &str, content: &str) -> String {
+        let prefixing = self.options.prefix_roles && self.options.content_roles.len() >= 2;
+        if prefixing && self.last_role.as_deref() != Some(role) {
+            let separator = if self.agent_messages.is_empty() {
+                ""
+            } else {
+                "\n"
+            };
+            self.last_role = Some(role.to_string());
+            return format!("{}[{}] {}", separator, role, content);
+        }
+
+        if self.agent_messages.is_empty() {
+            content.to_string()
+        } else {
+            format!("{}{}", self.options.message_join, content)
+        }
+    }
+
+    /// Where the first stop sequence starts in `agent_messages`, searching from
+    /// just before `appended_at` so sequences split across chunks are found.
+    fn find_stop_sequence(&self, appended_at: usize) -> Option<usize> {
+        let longest = self.options.stop_sequences.iter().map(String::len).max()?;
+        let mut from = appended_at.saturating_sub(longest.saturating_sub(1));
+        while !self.agent_messages.is_char_boundary(from) {
+            from -= 1;
+        }
+        let window = &self.agent_messages[from..];
+        self.options
+            .stop_sequences
+            .iter()
+            .filter_map(|sequence| window.find(sequence.as_str()))
+            .min()
+            .map(|at| from + at)
+    }
+
+    /// Send the text appended since the last delta to the `delta_sink`.
+    ///
+    /// Unless `last`, the tail that could start a stop sequence is held
+    /// back, so that the deltas concatenate to the final text even when a
+    /// stop sequence spans several of them.
+    fn send_delta(&mut self, last: bool) {
+        let Some(sink) = &self.options.delta_sink else {
+            return;
+        };
+        let mut end = self.agent_messages.len();
+        if !last {
+            let longest = self.options.stop_sequences.iter().map(String::len).max();
+            end = end.saturating_sub(longest.unwrap_or(0).saturating_sub(1));
+            while !self.agent_messages.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+        if end > self.deltas_sent {
+            let _ = sink.send(self.agent_messages[self.deltas_sent..end].to_string());
+            self.deltas_sent = end;
+        }
+    }
+
+    /// Handle a single line of output.
+    fn push_line(&mut self, line: &str) -> LineOutcome {
+        let outcome = self.handle_line(line);
+        if let Some(path) = outcome.exit_path() {
+            self.exit_path = path;
+        }
+        outcome
+    }
+
+    fn handle_line(&mut self, line: &str) -> LineOutcome {
+        self.line_number += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            return LineOutcome::Continue;
+        }
+        // Try to parse as JSON
+        let parsed = serde_json::from_str::<serde_json::Value>(line)
+            .and_then(|raw| GeminiEvent::deserialize(&raw).map(|event| (raw, event)));
+        let seq = parsed.is_ok().then(|| self.options.event_seq.next());
+        self.options.sinks.send(line, seq);
+        let (mut raw, event) = match parsed {
+            Ok(parsed) => parsed,
+            Err(_) if is_benign(self.options, line) => {
+                return LineOutcome::Continue;
+            }
+            Err(e) => {
+                self.push_error(
+                    ErrorEntryKind::JsonDecode,
+                    format!("{}: {}", e, line),
+                    Some(self.line_number),
+                );
+                self.decode_errors += 1;
+                if self
+                    .options
+                    .max_decode_errors
+                    .is_some_and(|max| self.decode_errors > max)
+                {
+                    self.unparseable = true;
+                    return LineOutcome::Unparseable;
+                }
+                return LineOutcome::Continue;
+            }
+        };
+        let options = self.options;
+        self.events_seen += 1;
+        // Numbers the event for clients merging streamed and returned events
+        if let (Some(object), Some(seq)) = (raw.as_object_mut(), seq) {
+            object.insert("_seq".to_string(), seq.into());
+        }
+
+        if let Some(sink) = &options.event_sink {
+            let _ = sink.send(event.clone());
+        }
+
+        // Extract session_id
+        if let Some(sid) = extract_session_id(&event, &raw, options.session_id_pointer.as_deref()) {
+            self.session_id = Some(sid);
+        }
+
+        // Extract checkpoint_id (last one wins)
+        if event.checkpoint_id.is_some() {
+            self.checkpoint_id = event.checkpoint_id.clone();
+        }
+
+        // Extract the plan (later plan events replace earlier ones)
+        if let Some(p) = parse_plan(&event) {
+            self.plan = Some(p);
+        }
+
+        track_tool_call(&mut self.tool_calls, &event);
+        if options.outside_writes != OutsideWrites::Ignore {
+            self.written_paths.extend(written_path(&event));
+        }
+        if let Some(structured) = &mut self.structured {
+            structure_event(structured, &event, &self.tool_calls, &options.message_join);
+        }
+        for citation in parse_citations(&event) {
+            if !self.citations.contains(&citation) {
+                self.citations.push(citation);
+            }
+        }
+        // Spinners repeat their text, so only changes are kept
+        if let Some(status) = status_update(&event) {
+            if self.status_updates.last() != Some(&status) {
+                if let Some(sink) = &options.status_sink {
+                    let _ = sink.send(status.clone());
+                }
+                self.status_updates.push(status);
+            }
+        }
+        self.quota_exceeded |= is_quota_error(&event, &raw);
+        if let Some(message) = reauth_signal(&event, &raw) {
+            self.reauth_required = Some(message);
+        }
+        if options.fail_fast_on_stream_error {
+            self.stream_error = stream_error(&event, &raw);
+        }
+
+        // Sum token counts over every event reporting them
+        if let Some(u) = parse_usage(&raw) {
+            self.usage.get_or_insert_with(TokenUsage::default).add(&u);
+        }
+
+        // Store raw value and summary if needed, before a stop sequence can end the
+        // stream on this event
+        if let Some(summary) = &mut self.event_summary {
+            summary.push(EventSummary::of(&event));
+        }
+        if let Some(messages) = &mut self.all_messages {
+            messages.push(raw);
+        }
+
+        // Extract assistant messages (or those of the other requested roles)
+        if event.event_type.as_deref() == Some("message") {
+            if let (Some(role), Some(content)) = (&event.role, &event.content) {
+                if self.collects_role(role) && !content.contains(DEPRECATED_PROMPT_WARNING) {
+                    let delta = self.chunk_text(role, content);
+                    let start = self.agent_messages.len();
+                    self.agent_messages.push_str(&delta);
+                    if role == "assistant" {
+                        self.assistant_message_count += 1;
+                    }
+                    let stop = self.find_stop_sequence(start);
+                    if let Some(at) = stop {
+                        self.agent_messages.truncate(at);
+                    }
+                    self.send_delta(stop.is_some());
+                    if stop.is_some() {
+                        self.stopped_early = true;
+                        self.finish_reason = Some("stop_sequence".to_string());
+                        return LineOutcome::StopEarly;
+                    }
+                }
+            }
+        }
+
+        // gemini would wait for a login no one can give it
+        if self.reauth_required.is_some() {
+            return LineOutcome::ReauthRequired;
+        }
+
+        // Abort on gemini's first error if the caller is strict about them
+        if self.stream_error.is_some() {
+            return LineOutcome::StreamError;
+        }
+
+        // Check for turn completion
+        if is_turn_completed(&event) {
+            self.finish_reason = finish_reason(&event);
+            self.turn_completed = true;
+            return LineOutcome::Completed;
+        }
+
+        // Stop once the caller has all the messages it asked for
+        if options
+            .max_assistant_messages
+            .is_some_and(|max| self.assistant_message_count >= max)
+        {
+            self.stopped_early = true;
+            return LineOutcome::StopEarly;
+        }
+
+        // A stream this long without completing is most likely stuck in a loop
+        if !self.turn_completed
+            && options
+                .max_events_without_completion
+                .is_some_and(|max| self.events_seen > max)
+        {
+            self.no_completion = true;
+            return LineOutcome::NoCompletion;
+        }
+
+        LineOutcome::Continue
+    }
+
+    /// Build the result once reading has stopped.
+    fn finish(mut self, timed_out: bool) -> GeminiResult {
+        self.send_delta(true);
+        let mut result = GeminiResult {
+            success: true,
+            session_id: self.session_id.clone(),
+            checkpoint_id: self.checkpoint_id,
+            plan: self.plan,
+            usage: self.usage,
+            structured: self.structured.map(|mut structured| {
+                // Tool results arrive after the calls they answer
+                for (action, call) in structured.actions.iter_mut().zip(&self.tool_calls) {
+                    action.call.status = call.status.clone();
+                }
+                structured
+            }),
+            tool_calls: self.tool_calls,
+            citations: (!self.citations.is_empty()).then_some(self.citations),
+            status_updates: (!self.status_updates.is_empty()).then_some(self.status_updates),
+            event_summary: self.event_summary,
+            exit_path: Some(if self.killed_after_wait {
+                format!("{}/killed_after_wait", self.exit_path)
+            } else {
+                self.exit_path.to_string()
+            }),
+            stopped_early: self.stopped_early,
+            finish_reason: self.finish_reason,
+            ..Default::default()
+        };
+
+        // Check for errors
+        let errors: Vec<ErrorEntry> = self.errors.into();
+        let error_suffix = errors
+            .iter()
+            .map(ErrorEntry::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if timed_out {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::Timeout);
+            result.error = Some(format!("Process timeout. {}", error_suffix));
+        } else if self.cancelled {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::Cancelled);
+            result.error = Some(format!("Cancelled. {}", error_suffix));
+        } else if let Some(message) = self.reauth_required {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::ReauthRequired);
+            result.error = Some(format!(
+                "gemini needs to log in again ({}); run `gemini` interactively to re-authenticate, or give the server an API key. {}",
+                message, error_suffix
+            ));
+        } else if self.first_event_timed_out {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::FirstEventTimeout);
+            result.error = Some(format!(
+                "gemini sent no event within {}s. {}",
+                self.options
+                    .first_event_timeout
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                error_suffix
+            ));
+        } else if self.no_completion {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::NoCompletion);
+            result.error = Some(format!(
+                "gemini sent {} events without completing the turn. {}",
+                self.events_seen, error_suffix
+            ));
+        } else if self.unparseable {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::StreamUnparseable);
+            result.error = Some(format!(
+                "{} lines of gemini's output failed to decode, more than the {} allowed; its event format may be incompatible with this server. {}",
+                self.decode_errors,
+                self.options.max_decode_errors.unwrap_or_default(),
+                error_suffix
+            ));
+        } else if let Some((status, limit_mb)) = self.memory_limit_exit {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::MemoryLimit);
+            result.error = Some(format!(
+                "gemini exited ({}) before finishing the turn, most likely because it exceeded its {} MiB memory limit. {}",
+                status, limit_mb, error_suffix
+            ));
+        } else if self.quota_exceeded && self.agent_messages.is_empty() {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::QuotaExceeded);
+            result.error = Some(format!(
+                "gemini's API key ran out of quota. {}",
+                error_suffix
+            ));
+        } else if let Some(message) = self.stream_error {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::StreamError);
+            result.error = Some(format!("gemini reported an error: {}", message));
+        } else if self.session_id.is_none() {
+            result.success = false;
+            result.failure_kind = Some(FailureKind::NoSessionId);
+            result.error = Some(format!(
+                "Failed to get `SESSION_ID` from the gemini session.\n\n{}",
+                error_suffix
+            ));
+        } else if self.agent_messages.is_empty() {
+            let message = self
+                .options
+                .no_messages_template
+                .as_deref()
+                .unwrap_or(DEFAULT_NO_MESSAGES_TEMPLATE)
+                .replace("{tool_calls}", &summarize_tool_calls(&result.tool_calls));
+            if self.options.allow_empty_messages {
+                result.agent_messages = Some(String::new());
+                result.warnings.push(message);
+            } else {
+                result.success = false;
+                result.failure_kind = Some(FailureKind::NoAgentMessages);
+                result.error = Some(format!("{}\n\n{}", message, error_suffix));
+            }
+        } else {
+            result.agent_messages = Some(self.agent_messages);
+        }
+
+        if !result.success {
+            result.errors = errors;
+        }
+
+        if self.options.return_all_messages {
+            result.all_messages = self.all_messages;
+        }
+
+        result
+    }
 }
@@ -0,0 +1,310 @@
+//! The server's health, reported per subsystem by `gemini_health`.
+
+use crate::capabilities::{Capabilities, STREAM_JSON};
+use crate::spend::SpendReport;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+/// Number of most recent calls the error rate is computed over.
+pub const RECENT_CALLS: usize = 100;
+
+/// Calls needed in the window before the error rate can degrade the health.
+const MIN_CALLS_FOR_ERROR_RATE: u64 = 5;
+
+/// Error rate at or above which the server is degraded.
+const DEGRADED_ERROR_RATE: f64 = 0.5;
+
+/// Health of one subsystem, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    /// Calls may fail or be slowed, but the server still serves them.
+    Degraded,
+    /// Calls cannot succeed until the problem is fixed.
+    Down,
+}
+
+/// Output of the gemini_health tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// The worst status of the subsystems.
+    pub status: HealthStatus,
+    pub gemini: GeminiHealth,
+    /// The configured gemini executables, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub binaries: BTreeMap<String, GeminiHealth>,
+    pub auth: AuthHealth,
+    pub concurrency: ConcurrencyHealth,
+    pub errors: ErrorHealth,
+    pub spend: SpendHealth,
+    pub config: ConfigSummary,
+}
+
+impl HealthReport {
+    /// Build a report whose `status` is the worst of the subsystems'. A
+    /// configured binary only fails the calls selecting it, so it degrades
+    /// the server at worst.
+    pub fn new(
+        gemini: GeminiHealth,
+        binaries: BTreeMap<String, GeminiHealth>,
+        auth: AuthHealth,
+        concurrency: ConcurrencyHealth,
+        errors: ErrorHealth,
+        spend: SpendHealth,
+        config: ConfigSummary,
+    ) -> Self {
+        let status = [
+            gemini.status,
+            auth.status,
+            concurrency.status,
+            errors.status,
+            spend.status,
+        ]
+        .into_iter()
+        .chain(
+            binaries
+                .values()
+                .map(|binary| binary.status.min(HealthStatus::Degraded)),
+        )
+        .max()
+        .unwrap_or(HealthStatus::Ok);
+        Self {
+            status,
+            gemini,
+            binaries,
+            auth,
+            concurrency,
+            errors,
+            spend,
+            config,
+        }
+    }
+}
+
+/// The gemini executable and whether the server can read its output.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeminiHealth {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Whether gemini supports the `stream-json` output format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compatible: Option<bool>,
+    /// Why gemini could not be found or probed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl GeminiHealth {
+    /// Health of the gemini at `path`, given the result of probing it.
+    pub fn new(
+        path: Result<String, String>,
+        capabilities: Option<Result<&Capabilities, String>>,
+    ) -> Self {
+        let path = match path {
+            Ok(path) => path,
+            Err(error) => {
+                return Self {
+                    status: HealthStatus::Down,
+                    path: None,
+                    version: None,
+                    compatible: None,
+                    error: Some(error),
+                }
+            }
+        };
+        match capabilities {
+            Some(Ok(capabilities)) => {
+                let compatible = capabilities.supports_output_format(STREAM_JSON);
+                Self {
+                    status: if compatible {
+                        HealthStatus::Ok
+                    } else {
+                        HealthStatus::Down
+                    },
+                    path: Some(path),
+                    version: capabilities.version.clone(),
+                    compatible: Some(compatible),
+                    error: (!compatible).then(|| {
+                        format!("gemini does not support --output-format {}", STREAM_JSON)
+                    }),
+                }
+            }
+            Some(Err(error)) => Self {
+                status: HealthStatus::Degraded,
+                path: Some(path),
+                version: None,
+                compatible: None,
+                error: Some(error),
+            },
+            None => Self {
+                status: HealthStatus::Ok,
+                path: Some(path),
+                version: None,
+                compatible: None,
+                error: None,
+            },
+        }
+    }
+}
+
+/// Where gemini gets its credentials from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// The server's `--api-key` pool.
+    KeyPool,
+    /// An API key in the environment gemini inherits.
+    Env,
+    /// gemini's cached OAuth credentials.
+    Oauth,
+    /// None found; gemini may still be configured some other way.
+    None,
+}
+
+/// The credentials gemini runs with.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthHealth {
+    pub status: HealthStatus,
+    pub method: AuthMethod,
+    /// Keys in the pool, for `key_pool`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys: Option<usize>,
+    /// Keys in the pool not cooling down after a rate limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_keys: Option<usize>,
+}
+
+impl AuthHealth {
+    /// Authentication through a pool of `keys` keys, `available` of them usable.
+    pub fn key_pool(keys: usize, available: usize) -> Self {
+        Self {
+            status: if available == 0 {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Ok
+            },
+            method: AuthMethod::KeyPool,
+            keys: Some(keys),
+            available_keys: Some(available),
+        }
+    }
+
+    /// Authentication through `method`, degraded when none was found.
+    pub fn method(method: AuthMethod) -> Self {
+        Self {
+            status: if method == AuthMethod::None {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Ok
+            },
+            method,
+            keys: None,
+            available_keys: None,
+        }
+    }
+}
+
+/// Runs in flight. Calls start as they arrive, so there is no queue depth.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcurrencyHealth {
+    pub status: HealthStatus,
+    pub active_runs: usize,
+}
+
+/// Failures among the most recent calls.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorHealth {
+    pub status: HealthStatus,
+    /// Calls in the window, at most [`RECENT_CALLS`].
+    pub calls: u64,
+    pub failures: u64,
+    pub error_rate: f64,
+}
+
+/// Spend against the budget.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendHealth {
+    pub status: HealthStatus,
+    #[serde(flatten)]
+    pub report: SpendReport,
+}
+
+impl SpendHealth {
+    /// Degraded once `report` shows the budget used up, as calls then fail.
+    pub fn new(report: SpendReport) -> Self {
+        let exceeded = report.remaining.is_some_and(|remaining| remaining <= 0.0);
+        Self {
+            status: if exceeded {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Ok
+            },
+            report,
+        }
+    }
+}
+
+/// The settings that most affect how calls behave.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSummary {
+    pub tool_name: String,
+    pub binaries: usize,
+    pub prompt_passing: &'static str,
+    pub env_policy: &'static str,
+    pub max_timeout_secs: u64,
+    pub first_event_timeout_secs: u64,
+    pub response_cache: bool,
+    pub structured_output: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_prompt_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_payload_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_limit_mb: Option<u64>,
+}
+
+/// Whether each of the last [`RECENT_CALLS`] calls succeeded.
+#[derive(Debug, Default)]
+pub struct RecentOutcomes {
+    outcomes: Mutex<VecDeque<bool>>,
+}
+
+impl RecentOutcomes {
+    /// Count a call, dropping the oldest once the window is full.
+    pub fn record(&self, success: bool) {
+        let mut outcomes = self.outcomes.lock().unwrap_or_else(|e| e.into_inner());
+        if outcomes.len() == RECENT_CALLS {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(success);
+    }
+
+    /// The error rate over the window, degraded once at least half of enough
+    /// calls failed.
+    pub fn report(&self) -> ErrorHealth {
+        let outcomes = self.outcomes.lock().unwrap_or_else(|e| e.into_inner());
+        let calls = outcomes.len() as u64;
+        let failures = outcomes.iter().filter(|success| !**success).count() as u64;
+        let error_rate = if calls == 0 {
+            0.0
+        } else {
+            failures as f64 / calls as f64
+        };
+        let status = if calls >= MIN_CALLS_FOR_ERROR_RATE && error_rate >= DEGRADED_ERROR_RATE {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Ok
+        };
+        ErrorHealth {
+            status,
+            calls,
+            failures,
+            error_rate,
+        }
+    }
+}
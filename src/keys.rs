@@ -0,0 +1,158 @@
+//! Rotation of gemini API keys across invocations.
+//!
+//! Each run gets a key from the pool through `GEMINI_API_KEY`. A key that
+//! hits its quota cools down for a while and is skipped until then, unless
+//! every key is cooling down.
+
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Environment variable carrying the API key into the gemini process.
+pub const API_KEY_ENV: &str = "GEMINI_API_KEY";
+
+/// Default time a key is skipped after hitting its quota.
+pub const DEFAULT_KEY_COOLDOWN_SECS: u64 = 60;
+
+/// How the next key is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyRotation {
+    /// Cycle through the keys in order.
+    #[default]
+    RoundRobin,
+    /// Use the key that has gone unused the longest.
+    LeastRecentlyUsed,
+}
+
+impl FromStr for KeyRotation {
+    type Err = String;
+
+    /// Parse `round-robin` or `lru`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(Self::RoundRobin),
+            "lru" => Ok(Self::LeastRecentlyUsed),
+            _ => Err(format!("expected round-robin or lru, got {:?}", s)),
+        }
+    }
+}
+
+/// Read API keys from a file, one per line; blank lines and `#` comments are
+/// skipped.
+pub fn read_keys_file(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[derive(Debug)]
+struct KeyState {
+    key: String,
+    last_used: Option<Instant>,
+    cooldown_until: Option<Instant>,
+}
+
+impl KeyState {
+    fn cooling(&self, now: Instant) -> bool {
+        self.cooldown_until.is_some_and(|until| until > now)
+    }
+}
+
+#[derive(Debug)]
+struct PoolState {
+    keys: Vec<KeyState>,
+    next: usize,
+}
+
+/// A pool of API keys shared by all invocations.
+#[derive(Debug)]
+pub struct KeyPool {
+    rotation: KeyRotation,
+    cooldown: Duration,
+    state: Mutex<PoolState>,
+}
+
+impl KeyPool {
+    /// A pool of `keys`, or `None` if there are none.
+    pub fn new(keys: Vec<String>, rotation: KeyRotation, cooldown: Duration) -> Option<Self> {
+        if keys.is_empty() {
+            return None;
+        }
+        let keys = keys
+            .into_iter()
+            .map(|key| KeyState {
+                key,
+                last_used: None,
+                cooldown_until: None,
+            })
+            .collect();
+        Some(Self {
+            rotation,
+            cooldown,
+            state: Mutex::new(PoolState { keys, next: 0 }),
+        })
+    }
+
+    /// Number of keys in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().keys.len()
+    }
+
+    /// Whether the pool has no keys; never true for a constructed pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of keys not cooling down.
+    pub fn available(&self) -> usize {
+        let now = Instant::now();
+        self.lock().keys.iter().filter(|k| !k.cooling(now)).count()
+    }
+
+    /// Pick the key for the next run. When every key is cooling down, the one
+    /// that recovers first is used.
+    pub fn pick(&self) -> String {
+        let now = Instant::now();
+        let mut state = self.lock();
+        let len = state.keys.len();
+
+        let index = match self.rotation {
+            KeyRotation::RoundRobin => (0..len)
+                .map(|offset| (state.next + offset) % len)
+                .find(|&i| !state.keys[i].cooling(now)),
+            KeyRotation::LeastRecentlyUsed => state
+                .keys
+                .iter()
+                .enumerate()
+                .filter(|(_, k)| !k.cooling(now))
+                .min_by_key(|(_, k)| k.last_used)
+                .map(|(i, _)| i),
+        }
+        .unwrap_or_else(|| {
+            (0..len)
+                .min_by_key(|&i| state.keys[i].cooldown_until)
+                .unwrap_or(0)
+        });
+
+        state.next = (index + 1) % len;
+        let key = &mut state.keys[index];
+        key.last_used = Some(now);
+        key.key.clone()
+    }
+
+    /// Skip `key` until its cooldown has passed.
+    pub fn cool_down(&self, key: &str) {
+        let until = Instant::now() + self.cooldown;
+        if let Some(state) = self.lock().keys.iter_mut().find(|k| k.key == key) {
+            state.cooldown_until = Some(until);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -3,10 +3,65 @@
 //! This crate provides an MCP server that enables Claude Code to invoke
 //! the Gemini CLI for AI-assisted programming tasks.
 
+mod artifacts;
+pub mod audit;
+pub mod batch;
+pub mod cache;
+pub mod capabilities;
+pub mod chunking;
+pub mod config;
+pub mod context;
+pub mod deadline;
 pub mod error;
 pub mod gemini;
+pub mod health;
+pub mod keys;
+mod limits;
+pub mod live;
+pub mod policy;
+pub mod post_process;
+pub mod process;
+mod readonly;
+pub mod registry;
+mod resource_usage;
+pub mod retry;
+pub mod scratch;
 pub mod server;
+pub mod session;
+pub mod sink;
+pub mod spend;
+pub mod tags;
+pub mod telemetry;
+pub mod transcript;
+pub mod validation;
+mod write_guard;
 
-pub use error::{GeminiError, Result};
-pub use gemini::{execute_gemini, GeminiEvent, GeminiResult};
-pub use server::{run_server, GeminiServer, GeminiToolInput};
+pub use batch::{Batch, BatchResult, BatchStore};
+pub use cache::ResponseCache;
+pub use capabilities::Capabilities;
+pub use config::{FieldDescription, ModelThreshold, ServerConfig, TimeoutPolicy};
+pub use deadline::Deadline;
+pub use error::{FailureKind, GeminiError, Result};
+pub use gemini::{
+    env_allowed, execute_gemini, forget_gemini_executable, replay_reader, replay_stream,
+    stream_gemini, Action, Citation, ContentBlockInfo, DebugEnvelope, DrainMode, EnvPolicy,
+    ErrorEntry, ErrorEntryKind, EventSeq, EventSummary, GeminiEvent, GeminiOptions, GeminiResult,
+    GeminiStream, KillMode, NonUtf8Paths, OutputDetail, OutputEncoding, OutsideWrites, Plan,
+    PlanStep, PromptPassing, SandboxDecision, StructuredOutput, TokenUsage, ToolCall,
+    REQUEST_ID_ENV,
+};
+pub use health::{HealthReport, HealthStatus};
+pub use keys::{KeyPool, KeyRotation, API_KEY_ENV};
+pub use live::{LiveResult, LiveResults};
+pub use policy::{ContentPolicy, PolicyDecision};
+pub use process::GeminiProcess;
+pub use registry::{RunHandle, RunRegistry};
+pub use retry::RetryPolicy;
+pub use server::{
+    run_server, BatchInput, BatchRetryInput, GeminiServer, GeminiToolInput, MessagesPageInput,
+    ResumeInput,
+};
+pub use session::{SessionHandle, SessionRecord, SessionStore};
+pub use sink::{Callback, FileSink, LineBuffer, LineSink, SequencedSender, Sinks};
+pub use transcript::{MessagesPage, TranscriptStore};
+pub use validation::{validate_input, Severity, ValidationIssue, ValidationReport};
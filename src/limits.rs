@@ -0,0 +1,38 @@
+//! Resource limits applied to the gemini process.
+//!
+//! On Linux the memory limit is an `RLIMIT_AS` set between fork and exec, so
+//! it covers gemini and everything it spawns. It bounds virtual address space,
+//! not resident memory: Node.js reserves a lot of address space up front, so
+//! limits should be generous (a few GiB). Elsewhere no limit is applied.
+
+use tokio::process::Command;
+
+/// Whether memory limits are enforced on this platform.
+pub(crate) const MEMORY_LIMIT_SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Limit the address space of the spawned child to `limit_mb` MiB.
+#[cfg(target_os = "linux")]
+pub(crate) fn limit_memory(cmd: &mut Command, limit_mb: u64) {
+    let bytes = limit_mb.saturating_mul(1024 * 1024) as libc::rlim_t;
+    let limit = libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    };
+
+    // SAFETY: setrlimit is async-signal-safe and `limit` is plain data.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+/// Limit the address space of the spawned child to `limit_mb` MiB.
+///
+/// Not enforced on this platform; see the module documentation.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn limit_memory(_cmd: &mut Command, _limit_mb: u64) {}
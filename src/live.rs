@@ -0,0 +1,163 @@
+//! Accumulating results of running `gemini` calls, published as MCP
+//! resources the client can subscribe to and re-read while gemini runs.
+
+use crate::gemini::{GeminiEvent, GeminiResult};
+use crate::sink::LineSink;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Prefix of live result URIs, followed by the call's request id.
+pub const LIVE_RESULT_URI_PREFIX: &str = "gemini://results/";
+
+/// Number of live results kept before the oldest one is evicted.
+pub const DEFAULT_LIVE_RESULT_CAPACITY: usize = 32;
+
+/// URI of the live result of call `request_id`.
+pub fn live_result_uri(request_id: &str) -> String {
+    format!("{LIVE_RESULT_URI_PREFIX}{request_id}")
+}
+
+/// The state of a call so far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiveResult {
+    pub request_id: String,
+    /// Whether the call is over and `result` set.
+    pub done: bool,
+    /// Number of events received so far.
+    pub events: usize,
+    /// Assistant text received so far.
+    pub agent_messages: String,
+    /// The final result, once the call is over.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<GeminiResult>,
+}
+
+impl LiveResult {
+    /// Add a raw output line.
+    fn push_line(&mut self, line: &str) {
+        let Ok(event) = serde_json::from_str::<GeminiEvent>(line) else {
+            return;
+        };
+        self.events += 1;
+        if event.event_type.as_deref() == Some("message")
+            && event.role.as_deref() == Some("assistant")
+        {
+            self.agent_messages
+                .push_str(event.content.as_deref().unwrap_or_default());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LiveState {
+    results: VecDeque<LiveResult>,
+    subscriptions: HashSet<String>,
+}
+
+/// Bounded, in-memory store of live results keyed by request id, with the
+/// URIs the client subscribed to.
+#[derive(Debug)]
+pub struct LiveResults {
+    capacity: usize,
+    state: Mutex<LiveState>,
+}
+
+impl LiveResults {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(LiveState::default()),
+        }
+    }
+
+    /// Start publishing call `request_id`, evicting the oldest result when the
+    /// store is full.
+    pub fn start(&self, request_id: &str) {
+        let mut state = self.lock();
+        state.results.retain(|r| r.request_id != request_id);
+        state.results.push_back(LiveResult {
+            request_id: request_id.to_string(),
+            ..Default::default()
+        });
+        while state.results.len() > self.capacity {
+            state.results.pop_front();
+        }
+    }
+
+    /// Record the final result of call `request_id`.
+    pub fn finish(&self, request_id: &str, result: &GeminiResult) {
+        self.update(request_id, |live| {
+            live.done = true;
+            live.result = Some(result.clone());
+        });
+    }
+
+    /// The live result at `uri`.
+    pub fn get(&self, uri: &str) -> Option<LiveResult> {
+        let request_id = uri.strip_prefix(LIVE_RESULT_URI_PREFIX)?;
+        let state = self.lock();
+        state
+            .results
+            .iter()
+            .find(|r| r.request_id == request_id)
+            .cloned()
+    }
+
+    /// Request ids of the stored results, oldest first.
+    pub fn request_ids(&self) -> Vec<String> {
+        let state = self.lock();
+        state.results.iter().map(|r| r.request_id.clone()).collect()
+    }
+
+    pub fn subscribe(&self, uri: &str) {
+        self.lock().subscriptions.insert(uri.to_string());
+    }
+
+    pub fn unsubscribe(&self, uri: &str) {
+        self.lock().subscriptions.remove(uri);
+    }
+
+    /// Whether the client subscribed to `uri`.
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.lock().subscriptions.contains(uri)
+    }
+
+    fn update(&self, request_id: &str, f: impl FnOnce(&mut LiveResult)) {
+        let mut state = self.lock();
+        if let Some(live) = state
+            .results
+            .iter_mut()
+            .find(|r| r.request_id == request_id)
+        {
+            f(live);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LiveState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for LiveResults {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIVE_RESULT_CAPACITY)
+    }
+}
+
+/// Adds every output line of call `request_id` to its live result and sends
+/// the result's URI to `updates`.
+pub struct LiveSink {
+    pub results: Arc<LiveResults>,
+    pub request_id: String,
+    pub updates: mpsc::UnboundedSender<String>,
+}
+
+impl LineSink for LiveSink {
+    fn line(&self, line: &str) {
+        self.results
+            .update(&self.request_id, |live| live.push_line(line));
+        let _ = self.updates.send(live_result_uri(&self.request_id));
+    }
+}
@@ -1,6 +1,8 @@
 //! Gemini MCP Server CLI entry point.
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{ArgAction, Parser};
+use gemini_mcp::retry::{DEFAULT_RETRY_BASE_MS, DEFAULT_RETRY_MAX_MS};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 /// Gemini MCP Server - Wraps Gemini CLI as a standard MCP protocol interface.
@@ -10,6 +12,294 @@ struct Args {
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// Refuse to start if the gemini executable is not found in PATH
+    #[arg(long)]
+    require_gemini: bool,
+
+    /// Reject resuming a session from a different working directory
+    #[arg(long)]
+    strict_session_cwd: bool,
+
+    /// Number of retries for calls that fail before gemini starts a session
+    #[arg(long, default_value_t = 0)]
+    retry_attempts: u32,
+
+    /// Base backoff in milliseconds, doubled on every retry
+    #[arg(long, default_value_t = DEFAULT_RETRY_BASE_MS)]
+    retry_base_ms: u64,
+
+    /// Maximum backoff in milliseconds
+    #[arg(long, default_value_t = DEFAULT_RETRY_MAX_MS)]
+    retry_max_ms: u64,
+
+    /// Randomize each backoff between zero and its exponential value
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    retry_jitter: bool,
+
+    /// Timeout in seconds for a gemini run, before scaling with prompt size
+    #[arg(long, default_value_t = gemini_mcp::config::DEFAULT_TIMEOUT_BASE_SECS)]
+    timeout_base_secs: u64,
+
+    /// Extra timeout in seconds per KiB of prompt
+    #[arg(long, default_value_t = 0.0)]
+    timeout_per_kb_secs: f64,
+
+    /// Upper bound in seconds for the scaled timeout
+    #[arg(long, default_value_t = gemini_mcp::config::DEFAULT_TIMEOUT_MAX_SECS)]
+    timeout_max_secs: u64,
+
+    /// Seconds to wait for gemini's first event before switching to a
+    /// request's `fallback_model`
+    #[arg(long, default_value_t = gemini_mcp::config::DEFAULT_FIRST_EVENT_TIMEOUT_SECS)]
+    first_event_timeout_secs: u64,
+
+    /// Model picked for `auto_model` calls with prompts of at least MIN_CHARS
+    /// characters, as MIN_CHARS=MODEL (repeatable)
+    #[arg(long = "auto-model", value_name = "MIN_CHARS=MODEL")]
+    auto_models: Vec<gemini_mcp::config::ModelThreshold>,
+
+    /// JSON pointer to the session id in gemini events (e.g. /session/id), used
+    /// when neither `session_id` nor `SESSION_ID` is present
+    #[arg(long, value_name = "POINTER")]
+    session_id_pointer: Option<String>,
+
+    /// Maximum number of resumes per session within the resume window
+    #[arg(long, value_name = "N")]
+    session_resume_limit: Option<u32>,
+
+    /// Length of the per-session resume window in seconds
+    #[arg(long, default_value_t = gemini_mcp::config::DEFAULT_SESSION_RESUME_WINDOW_SECS)]
+    session_resume_window_secs: u64,
+
+    /// Return results as MCP structured content to clients that support it,
+    /// in addition to the JSON text
+    #[arg(long)]
+    structured_output: bool,
+
+    /// Add a `_debug` field to results describing the tool result envelope
+    /// sent (content block types and sizes), for debugging MCP clients
+    #[arg(long)]
+    debug_envelope: bool,
+
+    /// Address-space limit for the gemini process in MiB (Linux only)
+    #[arg(long, value_name = "MIB")]
+    memory_limit_mb: Option<u64>,
+
+    /// Log every prompt under the `gemini_mcp::audit` tracing target
+    #[arg(long)]
+    audit_prompts: bool,
+
+    /// Regex whose matches are replaced with [REDACTED] in audit log entries
+    /// (repeatable)
+    #[arg(long = "redact-pattern", value_name = "REGEX")]
+    redact_patterns: Vec<regex::Regex>,
+
+    /// File of gemini API keys, one per line, handed to gemini in turn through
+    /// GEMINI_API_KEY (default: gemini's own configuration)
+    #[arg(long, value_name = "PATH")]
+    api_keys_file: Option<std::path::PathBuf>,
+
+    /// How the next API key is chosen: round-robin or lru
+    #[arg(long, default_value = "round-robin")]
+    key_rotation: gemini_mcp::KeyRotation,
+
+    /// Seconds a key that hit its quota is skipped
+    #[arg(long, default_value_t = gemini_mcp::keys::DEFAULT_KEY_COOLDOWN_SECS)]
+    key_cooldown_secs: u64,
+
+    /// Serve identical requests from a cache of successful results for this
+    /// many seconds; identical means the same `request_fingerprint`
+    /// (default: no cache)
+    #[arg(long, value_name = "SECS")]
+    response_cache_ttl_secs: Option<u64>,
+
+    /// Largest `gemini` response in bytes; events and text beyond it are
+    /// stored for `gemini_messages_page` (default: no limit)
+    #[arg(long, value_name = "BYTES")]
+    max_payload_bytes: Option<usize>,
+
+    /// Most entries accepted in one `gemini_batch`
+    #[arg(long, value_name = "N", default_value_t = gemini_mcp::config::DEFAULT_MAX_BATCH_ENTRIES)]
+    max_batch_entries: usize,
+
+    /// Most `gemini_batch` entries running at the same time
+    #[arg(long, value_name = "N", default_value_t = gemini_mcp::config::DEFAULT_BATCH_CONCURRENCY)]
+    batch_concurrency: usize,
+
+    /// Largest prompt sent in one turn, in bytes; larger prompts are rejected
+    /// unless the request sets `chunk_prompt` or `compress_context` (default:
+    /// no limit)
+    #[arg(long, value_name = "BYTES")]
+    max_prompt_bytes: Option<usize>,
+
+    /// Instruction of the `compress_context` pass; {target_bytes} and {prompt}
+    /// are replaced, and CONTEXT follows it (default: built in)
+    #[arg(long, value_name = "TEXT")]
+    compression_prompt: Option<String>,
+
+    /// Size `compress_context` asks gemini to compress CONTEXT to, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = gemini_mcp::context::DEFAULT_COMPRESSION_TARGET_BYTES)]
+    compression_target_bytes: usize,
+
+    /// Message returned when a turn produces no assistant text; `{tool_calls}`
+    /// is replaced by a summary of gemini's tool calls
+    #[arg(long, value_name = "TEXT")]
+    no_messages_template: Option<String>,
+
+    /// gemini subcommand requests may select with `SUBCOMMAND` (repeatable;
+    /// default: none)
+    #[arg(long = "allow-subcommand", value_name = "NAME")]
+    subcommands: Vec<String>,
+
+    /// Regex for non-JSON gemini output lines to ignore instead of reporting
+    /// them as decode errors, e.g. a trailing summary line (repeatable)
+    #[arg(long = "benign-output-pattern", value_name = "REGEX")]
+    benign_output_patterns: Vec<regex::Regex>,
+
+    /// End a run with `no_completion` once gemini has sent more than N events
+    /// without completing the turn, rather than waiting for the timeout
+    /// (default: no limit)
+    #[arg(long, value_name = "N")]
+    max_events_without_completion: Option<usize>,
+
+    /// Fail a run with `stream_unparseable` once more than N of gemini's output
+    /// lines failed to decode, a sign of an incompatible event format
+    /// (default: no limit)
+    #[arg(long, value_name = "N")]
+    max_decode_errors: Option<usize>,
+
+    /// Reject requests that set `raw_prompt` to bypass the server's prompt
+    /// transformations
+    #[arg(long)]
+    forbid_raw_prompt: bool,
+
+    /// Register the `gemini` tool under this name, and the other tools with it
+    /// as their prefix, e.g. to run several instances in one client
+    #[arg(long, value_name = "NAME")]
+    tool_name: Option<String>,
+
+    /// Description of the `gemini` tool shown to clients
+    #[arg(long, value_name = "TEXT")]
+    tool_description: Option<String>,
+
+    /// Description of a tool input field shown to clients, e.g. to localize
+    /// the schema, as FIELD=TEXT (repeatable)
+    #[arg(long = "field-description", value_name = "FIELD=TEXT")]
+    field_descriptions: Vec<gemini_mcp::FieldDescription>,
+
+    /// Send gemini's stderr lines to the client as MCP logging notifications
+    /// while it runs, to watch its diagnostics live
+    #[arg(long)]
+    forward_stderr: bool,
+
+    /// Word that makes `auto_sandbox` enable the sandbox when the prompt
+    /// contains it, or a word it starts when it ends in `*`; replaces the
+    /// defaults (repeatable)
+    #[arg(
+        long = "sandbox-keyword",
+        value_name = "WORD",
+        default_values_t = gemini_mcp::config::DEFAULT_SANDBOX_KEYWORDS.iter().map(|s| s.to_string())
+    )]
+    sandbox_keywords: Vec<String>,
+
+    /// Extension of files gemini may create or modify in the workspace, e.g.
+    /// rs; writing any other file fails the call with `forbidden_write`
+    /// (repeatable, default: any file)
+    #[arg(long = "allowed-write-extension", value_name = "EXT")]
+    allowed_write_extensions: Vec<String>,
+
+    /// Undo forbidden writes in git repositories: delete new files and check
+    /// out tracked files that had no local changes before the run
+    #[arg(long)]
+    revert_forbidden_writes: bool,
+
+    /// What to do with a workspace or include path that is not valid UTF-8:
+    /// pass-through hands it to gemini as is, reject refuses to run
+    #[arg(long, value_name = "POLICY", default_value = "pass-through")]
+    non_utf8_paths: gemini_mcp::NonUtf8Paths,
+
+    /// Encoding of gemini's output, as a WHATWG label such as windows-1252 or
+    /// gbk; malformed bytes are replaced. Under utf-8, gemini is also started
+    /// with PYTHONIOENCODING=utf-8
+    #[arg(long, value_name = "LABEL", default_value = "utf-8")]
+    output_encoding: gemini_mcp::OutputEncoding,
+
+    /// What to do when gemini's tool calls write a file outside the workspace
+    /// and output_dir: ignore, warn (report it in outside_writes) or fail
+    /// (with write_outside_workspace)
+    #[arg(long, value_name = "POLICY", default_value = "ignore")]
+    outside_writes: gemini_mcp::OutsideWrites,
+
+    /// Largest workspace copied for `scratch_copy`, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = gemini_mcp::scratch::DEFAULT_SCRATCH_COPY_MAX_BYTES)]
+    scratch_copy_max_bytes: u64,
+
+    /// File or directory name left out of scratch copies (repeatable; replaces
+    /// the defaults when given)
+    #[arg(
+        long = "scratch-copy-ignore",
+        value_name = "NAME",
+        default_values_t = gemini_mcp::scratch::DEFAULT_SCRATCH_COPY_IGNORE.iter().map(|s| s.to_string())
+    )]
+    scratch_copy_ignore: Vec<String>,
+
+    /// Named gemini executable requests can select with `binary`, e.g.
+    /// canary=/opt/gemini-canary/bin/gemini (repeatable, or comma-separated
+    /// in GEMINI_MCP_BINARIES)
+    #[arg(
+        long = "binary",
+        value_name = "NAME=PATH",
+        env = "GEMINI_MCP_BINARIES",
+        value_delimiter = ','
+    )]
+    binaries: Vec<gemini_mcp::config::GeminiBinary>,
+
+    /// Cost per 1k tokens of a model, e.g. gemini-2.5-pro=0.01; `*` prices
+    /// the models without their own cost (repeatable, default: free)
+    #[arg(long = "model-cost", value_name = "MODEL=COST")]
+    model_costs: Vec<gemini_mcp::spend::ModelCost>,
+
+    /// Largest spend per spend window; further calls fail with
+    /// budget_exceeded until older runs leave the window (default: no cap)
+    #[arg(long, value_name = "COST")]
+    spend_budget: Option<f64>,
+
+    /// Length of the spend window in seconds
+    #[arg(long, default_value_t = gemini_mcp::spend::DEFAULT_SPEND_WINDOW_SECS)]
+    spend_window_secs: u64,
+
+    /// Named shell command requests can pipe gemini's reply through with
+    /// `post_process`, e.g. 'fmt=prettier --parser markdown'; it reads the
+    /// reply on stdin and its stdout replaces it (repeatable, default: none)
+    #[arg(long = "post-processor", value_name = "NAME=COMMAND")]
+    post_processors: Vec<gemini_mcp::config::PostProcessor>,
+
+    /// How the prompt is handed to gemini: flag (--prompt), positional, stdin
+    /// or auto (positional once the installed gemini deprecates --prompt)
+    #[arg(long, value_name = "MODE", default_value = "auto")]
+    prompt_passing: gemini_mcp::PromptPassing,
+
+    /// Which environment variables gemini inherits: inherit (all of the
+    /// server's) or allowlist (only those matching --env-allow)
+    #[arg(long, value_name = "POLICY", default_value = "inherit")]
+    env_policy: gemini_mcp::EnvPolicy,
+
+    /// Environment variable gemini inherits under --env-policy allowlist; a
+    /// trailing * matches any suffix, e.g. GEMINI_* (repeatable; replaces the
+    /// defaults when given)
+    #[arg(
+        long = "env-allow",
+        value_name = "NAME",
+        default_values_t = gemini_mcp::config::DEFAULT_ENV_ALLOWLIST.iter().map(|s| s.to_string())
+    )]
+    env_allowlist: Vec<String>,
+
+    /// Export a span per gemini invocation over OTLP/HTTP, configured through
+    /// the standard OTEL_EXPORTER_OTLP_* environment variables
+    #[cfg(feature = "otel")]
+    #[arg(long)]
+    otel: bool,
 }
 
 #[tokio::main]
@@ -23,11 +313,100 @@ async fn main() -> anyhow::Result<()> {
         EnvFilter::new("info")
     };
 
+    #[cfg(feature = "otel")]
+    let tracer_provider = if args.otel {
+        Some(gemini_mcp::telemetry::init_tracer_provider()?)
+    } else {
+        None
+    };
+    #[cfg(feature = "otel")]
+    let otel_layer = tracer_provider.as_ref().map(gemini_mcp::telemetry::layer);
+    #[cfg(not(feature = "otel"))]
+    let otel_layer: Option<tracing_subscriber::layer::Identity> = None;
+
     tracing_subscriber::registry()
         .with(filter)
         .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(otel_layer)
         .init();
 
+    let api_keys = match &args.api_keys_file {
+        Some(path) => gemini_mcp::keys::read_keys_file(path)
+            .with_context(|| format!("Failed to read API keys from {}", path.display()))?,
+        None => Vec::new(),
+    };
+
+    let config = gemini_mcp::ServerConfig {
+        require_gemini: args.require_gemini,
+        strict_session_cwd: args.strict_session_cwd,
+        retry: gemini_mcp::RetryPolicy {
+            attempts: args.retry_attempts,
+            base_ms: args.retry_base_ms,
+            max_ms: args.retry_max_ms,
+            jitter: args.retry_jitter,
+        },
+        timeout: gemini_mcp::TimeoutPolicy {
+            base_secs: args.timeout_base_secs,
+            per_kb_secs: args.timeout_per_kb_secs,
+            max_secs: args.timeout_max_secs,
+        },
+        first_event_timeout_secs: args.first_event_timeout_secs,
+        auto_models: args.auto_models,
+        session_id_pointer: args.session_id_pointer,
+        session_resume_limit: args.session_resume_limit,
+        session_resume_window_secs: args.session_resume_window_secs,
+        structured_output: args.structured_output,
+        debug_envelope: args.debug_envelope,
+        memory_limit_mb: args.memory_limit_mb,
+        audit_prompts: args.audit_prompts,
+        redact_patterns: args.redact_patterns,
+        api_keys,
+        key_rotation: args.key_rotation,
+        key_cooldown_secs: args.key_cooldown_secs,
+        response_cache_ttl_secs: args.response_cache_ttl_secs,
+        max_payload_bytes: args.max_payload_bytes,
+        max_batch_entries: args.max_batch_entries,
+        batch_concurrency: args.batch_concurrency,
+        max_prompt_bytes: args.max_prompt_bytes,
+        compression_prompt: args.compression_prompt,
+        compression_target_bytes: args.compression_target_bytes,
+        no_messages_template: args.no_messages_template,
+        subcommands: args.subcommands,
+        benign_output_patterns: args.benign_output_patterns,
+        max_events_without_completion: args.max_events_without_completion,
+        max_decode_errors: args.max_decode_errors,
+        forbid_raw_prompt: args.forbid_raw_prompt,
+        tool_name: args.tool_name,
+        tool_description: args.tool_description,
+        field_descriptions: args.field_descriptions,
+        forward_stderr: args.forward_stderr,
+        sandbox_keywords: args.sandbox_keywords,
+        allowed_write_extensions: args.allowed_write_extensions,
+        revert_forbidden_writes: args.revert_forbidden_writes,
+        non_utf8_paths: args.non_utf8_paths,
+        output_encoding: args.output_encoding,
+        outside_writes: args.outside_writes,
+        scratch_copy_max_bytes: args.scratch_copy_max_bytes,
+        scratch_copy_ignore: args.scratch_copy_ignore,
+        binaries: args.binaries,
+        model_costs: args.model_costs,
+        spend_budget: args.spend_budget,
+        spend_window_secs: args.spend_window_secs,
+        post_processors: args.post_processors,
+        prompt_passing: args.prompt_passing,
+        env_policy: args.env_policy,
+        env_allowlist: args.env_allowlist,
+    };
+
     // Run the MCP server
-    gemini_mcp::run_server().await
+    let served = gemini_mcp::run_server(config).await;
+
+    #[cfg(feature = "otel")]
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("Failed to flush OpenTelemetry spans: {}", e);
+        }
+    }
+
+    served
 }
@@ -0,0 +1,29 @@
+//! Pre-send moderation of prompts by embedders.
+//!
+//! A [`ContentPolicy`] set with [`GeminiServer::with_content_policy`] sees
+//! every prompt as gemini would receive it, with `CONTEXT` embedded, before
+//! gemini is started.
+//!
+//! [`GeminiServer::with_content_policy`]: crate::GeminiServer::with_content_policy
+
+/// What a [`ContentPolicy`] decided about a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    /// Send the prompt as is.
+    Allow,
+    /// Refuse the request with `failure_kind: "policy_denied"`.
+    Deny { reason: String },
+    /// Send this prompt instead, e.g. with sensitive parts removed.
+    Transform(String),
+}
+
+/// Decides whether a prompt may be sent to gemini.
+pub trait ContentPolicy: Send + Sync {
+    fn check(&self, prompt: &str) -> PolicyDecision;
+}
+
+impl<F: Fn(&str) -> PolicyDecision + Send + Sync> ContentPolicy for F {
+    fn check(&self, prompt: &str) -> PolicyDecision {
+        self(prompt)
+    }
+}
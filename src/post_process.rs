@@ -0,0 +1,85 @@
+//! Piping gemini's reply through a server-configured command.
+//!
+//! Only commands the server names with `--post-processor` can run, so a
+//! request can pick one but never supply its own. The command runs through
+//! the platform shell in the request's workspace, gets `agent_messages` on
+//! stdin, and its stdout replaces them when it succeeds.
+
+use crate::error::FailureKind;
+use crate::gemini::GeminiResult;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Longest a post-processing command may run.
+pub const POST_PROCESS_TIMEOUT_SECS: u64 = 60;
+
+/// Run `command` on the reply in `result`, replacing it with the command's
+/// output, or failing the result when the command fails.
+pub(crate) async fn apply(name: &str, command: &str, cwd: &Path, result: &mut GeminiResult) {
+    let input = result.agent_messages.clone().unwrap_or_default();
+    let outcome = tokio::time::timeout(
+        Duration::from_secs(POST_PROCESS_TIMEOUT_SECS),
+        run(command, cwd, input),
+    )
+    .await;
+
+    let error = match outcome {
+        Ok(Ok(output)) => {
+            result.post_process_status = output.status.code();
+            if output.status.success() {
+                result.agent_messages = Some(String::from_utf8_lossy(&output.stdout).into_owned());
+                return;
+            }
+            format!(
+                "post-processor {} failed ({}): {}",
+                name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        Ok(Err(e)) => format!("post-processor {} could not run: {}", name, e),
+        Err(_) => format!(
+            "post-processor {} did not finish within {}s",
+            name, POST_PROCESS_TIMEOUT_SECS
+        ),
+    };
+    result.success = false;
+    result.failure_kind = Some(FailureKind::PostProcessFailed);
+    result.error = Some(error);
+}
+
+/// Run `command` through the shell with `input` on its stdin.
+async fn run(command: &str, cwd: &Path, input: String) -> std::io::Result<std::process::Output> {
+    let mut child = shell(command)
+        .current_dir(cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Write while the output is read, so a large reply cannot deadlock the pipes
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(input.as_bytes()).await;
+    });
+    let output = child.wait_with_output().await;
+    let _ = writer.await;
+    output
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
@@ -0,0 +1,75 @@
+//! Lifecycle of a running gemini process.
+
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::{Child, ChildStderr, ChildStdout};
+
+/// A spawned gemini process that is never left running: it is killed when
+/// dropped, even on early returns and panics.
+#[derive(Debug)]
+pub struct GeminiProcess {
+    /// `None` once the process has been reaped.
+    child: Option<Child>,
+}
+
+impl GeminiProcess {
+    pub fn new(child: Child) -> Self {
+        Self { child: Some(child) }
+    }
+
+    /// OS process id, while the process has not been reaped.
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref()?.id()
+    }
+
+    /// Take the piped standard output.
+    pub fn take_stdout(&mut self) -> Option<ChildStdout> {
+        self.child.as_mut()?.stdout.take()
+    }
+
+    /// Take the piped standard error, when it was piped.
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.child.as_mut()?.stderr.take()
+    }
+
+    /// Kill the process right away and reap it.
+    pub async fn kill(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill().await;
+        }
+    }
+
+    /// Give the process `grace` to exit on its own, then kill it. Returns the
+    /// exit status if it exited by itself.
+    pub async fn shutdown(&mut self, grace: Duration) -> Option<ExitStatus> {
+        let child = self.child.as_mut()?;
+        match tokio::time::timeout(grace, child.wait()).await {
+            Ok(status) => {
+                self.child = None;
+                status.ok()
+            }
+            Err(_) => {
+                self.kill().await;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for GeminiProcess {
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        let _ = child.start_kill();
+        // Reap it in the background so it does not linger as a zombie
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = child.wait().await;
+            });
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Presenting the workspace to gemini as read-only.
+//!
+//! Guarantees per platform:
+//! - **Linux**: the child runs in a private user + mount namespace in which the
+//!   workspace is bind-mounted read-only, so writes fail at the kernel level
+//!   (`EROFS`) no matter which tool gemini uses. If the kernel refuses to create
+//!   the namespace (e.g. unprivileged user namespaces are disabled) the spawn
+//!   fails instead of silently running writable.
+//! - **Other platforms**: no OS-level enforcement. Read-only mode only forces
+//!   `--sandbox` and `--approval-mode default`, under which headless gemini does
+//!   not offer its file-editing and shell tools. This is best-effort.
+
+use tokio::process::Command;
+
+/// Arguments added to the gemini command line in read-only mode.
+pub(crate) fn gemini_args() -> [&'static str; 3] {
+    ["--sandbox", "--approval-mode", "default"]
+}
+
+/// Arrange for `dir` to be read-only inside the spawned child.
+#[cfg(target_os = "linux")]
+pub(crate) fn restrict(cmd: &mut Command, dir: &std::path::Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = CString::new(dir.canonicalize()?.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // Everything the child needs is prepared here: only async-signal-safe
+    // calls are made between fork and exec.
+    // SAFETY: getuid/getgid cannot fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let uid_map = format!("{uid} {uid} 1");
+    let gid_map = format!("{gid} {gid} 1");
+    let setgroups_path = c"/proc/self/setgroups";
+    let uid_map_path = c"/proc/self/uid_map";
+    let gid_map_path = c"/proc/self/gid_map";
+    let root = c"/";
+
+    // Flags such as nosuid/nodev on the original mount are locked inside a
+    // user namespace and must be carried over to the read-only remount.
+    // SAFETY: `dir` is a valid NUL-terminated path and `stat` is a plain output buffer.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(dir.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let locked_flags = [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .iter()
+    .filter(|(st, _)| stat.f_flag & *st != 0)
+    .fold(0, |acc, (_, ms)| acc | ms);
+
+    fn check(ret: libc::c_int) -> io::Result<()> {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    fn write_file(path: &std::ffi::CStr, data: &[u8]) -> io::Result<()> {
+        // SAFETY: `path` is NUL-terminated and `data` outlives the calls.
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let written = libc::write(fd, data.as_ptr().cast(), data.len());
+            libc::close(fd);
+            if written < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    // SAFETY: the closure only performs async-signal-safe syscalls on data
+    // prepared above.
+    unsafe {
+        cmd.pre_exec(move || {
+            check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS))?;
+            write_file(setgroups_path, b"deny")?;
+            write_file(uid_map_path, uid_map.as_bytes())?;
+            write_file(gid_map_path, gid_map.as_bytes())?;
+            check(libc::mount(
+                std::ptr::null(),
+                root.as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            check(libc::mount(
+                dir.as_ptr(),
+                dir.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                std::ptr::null(),
+            ))?;
+            check(libc::mount(
+                std::ptr::null(),
+                dir.as_ptr(),
+                std::ptr::null(),
+                libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | locked_flags,
+                std::ptr::null(),
+            ))?;
+            // The working directory was entered before this hook ran and still
+            // refers to the writable mount underneath; re-enter it.
+            check(libc::chdir(dir.as_ptr()))
+        });
+    }
+
+    Ok(())
+}
+
+/// Arrange for `dir` to be read-only inside the spawned child.
+///
+/// Not enforced on this platform; see the module documentation.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn restrict(_cmd: &mut Command, _dir: &std::path::Path) -> std::io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Registry of in-flight gemini runs, so they can be cancelled on shutdown or
+//! by request id.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+/// Tracks a cancellation token for every running gemini invocation, with the
+/// id of the request it serves.
+#[derive(Debug, Default)]
+pub struct RunRegistry {
+    next_id: AtomicU64,
+    runs: Mutex<HashMap<u64, (String, CancellationToken)>>,
+    idle: Notify,
+}
+
+/// A registered run; removed from the registry when dropped.
+#[derive(Debug)]
+pub struct RunHandle {
+    id: u64,
+    token: CancellationToken,
+    registry: Arc<RunRegistry>,
+}
+
+impl RunHandle {
+    /// Token cancelled when the run should stop.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for RunHandle {
+    fn drop(&mut self) {
+        let mut runs = self.registry.lock();
+        runs.remove(&self.id);
+        if runs.is_empty() {
+            self.registry.idle.notify_waiters();
+        }
+    }
+}
+
+impl RunRegistry {
+    /// Register a new run for the request `request_id`.
+    pub fn register(self: &Arc<Self>, request_id: &str) -> RunHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        self.lock()
+            .insert(id, (request_id.to_string(), token.clone()));
+        RunHandle {
+            id,
+            token,
+            registry: Arc::clone(self),
+        }
+    }
+
+    /// Number of runs in flight.
+    pub fn active(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Cancel every run in flight.
+    pub fn cancel_all(&self) {
+        for (_, token) in self.lock().values() {
+            token.cancel();
+        }
+    }
+
+    /// Cancel the runs in flight for `request_id`. Returns whether there
+    /// were any.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let mut found = false;
+        for (_, token) in self.lock().values().filter(|(id, _)| id == request_id) {
+            token.cancel();
+            found = true;
+        }
+        found
+    }
+
+    /// Wait until no run is in flight. Returns `false` if some were still
+    /// running after `timeout`.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let idle = self.idle.notified();
+                if self.active() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, (String, CancellationToken)>> {
+        self.runs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,71 @@
+//! Peak memory of the gemini process.
+//!
+//! On Linux the kernel tracks each process's resident set high-water mark as
+//! `VmHWM` in `/proc/<pid>/status`; it is sampled while gemini runs, since it
+//! disappears once the process has exited. It covers the gemini process only,
+//! not the processes it spawns. Elsewhere nothing is reported.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+/// Interval between samples of the high-water mark.
+const SAMPLE_INTERVAL_MS: u64 = 50;
+
+/// Samples a process's peak resident set size in the background.
+pub(crate) struct PeakRss {
+    pid: u32,
+    peak: Arc<AtomicU64>,
+    task: JoinHandle<()>,
+}
+
+impl PeakRss {
+    /// Start sampling process `pid`.
+    pub(crate) fn watch(pid: u32) -> Self {
+        let peak = Arc::new(AtomicU64::new(0));
+        let task = tokio::spawn({
+            let peak = Arc::clone(&peak);
+            async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(SAMPLE_INTERVAL_MS));
+                loop {
+                    interval.tick().await;
+                    match high_water_mark(pid) {
+                        Some(bytes) => {
+                            peak.fetch_max(bytes, Ordering::Relaxed);
+                        }
+                        None => break,
+                    }
+                }
+            }
+        });
+        Self { pid, peak, task }
+    }
+
+    /// Take a last sample and stop, returning the peak seen in bytes. Call it
+    /// before the process is reaped.
+    pub(crate) fn finish(self) -> Option<u64> {
+        self.task.abort();
+        if let Some(bytes) = high_water_mark(self.pid) {
+            self.peak.fetch_max(bytes, Ordering::Relaxed);
+        }
+        Some(self.peak.load(Ordering::Relaxed)).filter(|&bytes| bytes > 0)
+    }
+}
+
+/// `VmHWM` of process `pid` in bytes, while it is alive.
+#[cfg(target_os = "linux")]
+fn high_water_mark(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let line = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Not available on this platform; see the module documentation.
+#[cfg(not(target_os = "linux"))]
+fn high_water_mark(_pid: u32) -> Option<u64> {
+    None
+}
@@ -0,0 +1,51 @@
+//! Retry policy with exponential backoff for transient gemini failures.
+
+use std::time::Duration;
+
+pub const DEFAULT_RETRY_BASE_MS: u64 = 500;
+pub const DEFAULT_RETRY_MAX_MS: u64 = 10_000;
+
+/// How failed invocations are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt. `0` disables retrying.
+    pub attempts: u32,
+    /// Backoff before the first retry, doubled for every following one.
+    pub base_ms: u64,
+    /// Upper bound for a single backoff.
+    pub max_ms: u64,
+    /// Use full jitter, i.e. sleep a uniformly random duration up to the
+    /// exponential backoff, so concurrent retries do not line up.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Backoff ceiling for the given retry (0-based), capped at `max_ms`.
+    pub fn backoff_ceiling(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_ms
+            .saturating_mul(1u64.checked_shl(retry).unwrap_or(u64::MAX));
+        Duration::from_millis(exp.min(self.max_ms))
+    }
+
+    /// Delay to sleep before the given retry (0-based).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self.backoff_ceiling(retry);
+        if self.jitter {
+            Duration::from_millis(fastrand::u64(0..=ceiling.as_millis() as u64))
+        } else {
+            ceiling
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            base_ms: DEFAULT_RETRY_BASE_MS,
+            max_ms: DEFAULT_RETRY_MAX_MS,
+            jitter: true,
+        }
+    }
+}
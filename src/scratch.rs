@@ -0,0 +1,131 @@
+//! Running gemini on a disposable copy of the workspace.
+//!
+//! The copy lives in the system temp dir under the workspace's own name, so
+//! gemini sees a familiar project. Files and directories named in the ignore
+//! list are left out, and copying stops once the size limit is exceeded.
+
+use crate::artifacts::Snapshot;
+use crate::error::GeminiError;
+use std::path::{Path, PathBuf};
+
+/// Default largest workspace copied for `scratch_copy`.
+pub const DEFAULT_SCRATCH_COPY_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Names left out of scratch copies by default.
+pub const DEFAULT_SCRATCH_COPY_IGNORE: &[&str] = &[".git", "node_modules", "target"];
+
+/// A copy of a workspace, removed on drop unless kept.
+#[derive(Debug)]
+pub(crate) struct ScratchCopy {
+    /// Temp directory holding the copy.
+    root: PathBuf,
+    /// The copy itself, inside `root`.
+    dir: PathBuf,
+    before: Snapshot,
+    keep: bool,
+}
+
+impl ScratchCopy {
+    /// Copy `cwd` into a fresh temp directory, skipping entries named in
+    /// `ignore` and failing once more than `max_bytes` would be copied.
+    pub(crate) fn create(
+        cwd: &Path,
+        ignore: &[String],
+        max_bytes: u64,
+    ) -> Result<Self, GeminiError> {
+        if !cwd.is_dir() {
+            return Err(GeminiError::WorkspaceNotFound(
+                cwd.to_string_lossy().to_string(),
+            ));
+        }
+        let root =
+            std::env::temp_dir().join(format!("gemini-mcp-scratch-{}", uuid::Uuid::new_v4()));
+        let name = cwd
+            .file_name()
+            .map_or_else(|| "workspace".into(), |n| n.to_os_string());
+        let mut copy = Self {
+            dir: root.join(name),
+            root,
+            before: Snapshot::default(),
+            keep: false,
+        };
+        let mut copied = 0;
+        copy_dir(cwd, &copy.dir, ignore, max_bytes, &mut copied).map_err(|source| {
+            GeminiError::ScratchCopy {
+                path: cwd.to_string_lossy().to_string(),
+                source,
+            }
+        })?;
+        copy.before = Snapshot::take(&copy.dir);
+        Ok(copy)
+    }
+
+    /// The copied workspace.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Files created, modified or deleted in the copy since it was made,
+    /// relative to it.
+    pub(crate) fn changes(&self) -> Vec<PathBuf> {
+        let mut changes: Vec<PathBuf> = self
+            .before
+            .changed(&self.dir)
+            .into_iter()
+            .chain(self.before.missing())
+            .filter_map(|path| path.strip_prefix(&self.dir).ok().map(Path::to_path_buf))
+            .collect();
+        changes.sort();
+        changes
+    }
+
+    /// Leave the copy on disk when dropped, returning where it is.
+    pub(crate) fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.dir.clone()
+    }
+}
+
+impl Drop for ScratchCopy {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+/// Copy the tree at `from` to `to`, counting file sizes in `copied`.
+fn copy_dir(
+    from: &Path,
+    to: &Path,
+    ignore: &[String],
+    max_bytes: u64,
+    copied: &mut u64,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if ignore.iter().any(|ignored| name == ignored.as_str()) {
+            continue;
+        }
+        let (source, target) = (entry.path(), to.join(&name));
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&source, &target, ignore, max_bytes, copied)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(&source)?, &target)?;
+        } else {
+            *copied += entry.metadata()?.len();
+            if *copied > max_bytes {
+                return Err(std::io::Error::other(format!(
+                    "the workspace is larger than the {} byte limit",
+                    max_bytes
+                )));
+            }
+            std::fs::copy(&source, &target)?;
+        }
+    }
+    Ok(())
+}
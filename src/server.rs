@@ -1,23 +1,99 @@
 //! MCP Server implementation for Gemini.
 
-use crate::gemini::{execute_gemini, GeminiResult};
+use crate::artifacts::Snapshot;
+use crate::audit;
+use crate::batch::{Batch, BatchResult, BatchStore};
+use crate::cache::{self, ResponseCache};
+use crate::capabilities::{self, Capabilities};
+use crate::chunking;
+use crate::config::{FieldDescription, ServerConfig};
+use crate::context;
+use crate::deadline::Deadline;
+use crate::error::{FailureKind, GeminiError};
+use crate::gemini::{
+    env_allowed, execute_gemini, find_gemini_executable, prompt_arg, ContentBlockInfo,
+    DebugEnvelope, DrainMode, EnvPolicy, EventSeq, GeminiOptions, GeminiResult, KillMode,
+    OutputDetail, PromptPassing, SandboxDecision, TokenUsage,
+};
+use crate::health::{
+    AuthHealth, AuthMethod, ConcurrencyHealth, ConfigSummary, GeminiHealth, HealthReport,
+    HealthStatus, RecentOutcomes, SpendHealth,
+};
+use crate::keys::{KeyPool, API_KEY_ENV};
+use crate::live::{live_result_uri, LiveResults, LiveSink};
+use crate::policy::{ContentPolicy, PolicyDecision};
+use crate::post_process;
+use crate::registry::RunRegistry;
+use crate::scratch::ScratchCopy;
+use crate::session::{same_workspace, SessionHandle, SessionStore};
+use crate::sink::{FileSink, SequencedSender, Sinks};
+use crate::spend::{SpendReport, SpendTracker};
+use crate::tags::{TagMetrics, TagReport};
+use crate::telemetry;
+use crate::transcript::{TranscriptStore, DEFAULT_PAGE_LIMIT};
+use crate::validation::validate_input;
+use crate::write_guard::WriteGuard;
+use rmcp::handler::server::common::{schema_for_type, FromContextPart};
 use rmcp::handler::server::router::tool::ToolRouter;
+use rmcp::handler::server::tool::ToolCallContext;
 use rmcp::handler::server::wrapper::Parameters;
 use rmcp::model::*;
 use rmcp::schemars::{self, JsonSchema};
-use rmcp::{tool, tool_handler, tool_router, ErrorData as McpError, ServiceExt};
+use rmcp::service::RequestContext;
+use rmcp::{tool, tool_handler, tool_router, ErrorData as McpError, Peer, RoleServer, ServiceExt};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::Instrument;
 
 /// Input parameters for the gemini tool.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[schemars(description = "Parameters for invoking the Gemini CLI")]
 pub struct GeminiToolInput {
     /// Instruction for the task to send to gemini.
-    #[schemars(description = "The prompt/instruction to send to Gemini")]
+    ///
+    /// There is no way to name an MCP resource instead: resources are served
+    /// by servers, and MCP has no request for a server to read one from its
+    /// client, so the client has to read it and pass the content here.
+    #[schemars(
+        description = "The prompt/instruction to send to Gemini. To use an MCP resource as the prompt, read it and pass its content here; the server cannot read resources from the client"
+    )]
     #[serde(rename = "PROMPT")]
     pub prompt: String,
 
+    /// Structured data for the task.
+    #[schemars(
+        description = "JSON value given to gemini with the prompt, appended to PROMPT as a fenced block labeled \"Context (JSON):\" (default: none)"
+    )]
+    #[serde(rename = "CONTEXT", default)]
+    pub context: Option<serde_json::Value>,
+
+    /// gemini subcommand to run instead of the default prompt mode.
+    #[schemars(
+        description = "gemini subcommand placed before the prompt flags, e.g. \"chat\"; must be allowed by the server (default: gemini's prompt mode)"
+    )]
+    #[serde(rename = "SUBCOMMAND", default)]
+    pub subcommand: String,
+
+    /// Name of the configured gemini executable to run.
+    #[schemars(
+        description = "Name of one of the server's configured gemini executables to run, e.g. \"canary\"; its own probed capabilities decide `SEED`, `DENIED_TOOLS` and prompt passing (default: gemini from PATH)"
+    )]
+    #[serde(default)]
+    pub binary: Option<String>,
+
+    /// Seed for reproducible output.
+    #[schemars(
+        description = "Seed passed to gemini as --seed, with --temperature 0 when gemini has that option, so the same PROMPT and SEED are answered alike; rejected if the installed gemini has no --seed option (default: none)"
+    )]
+    #[serde(rename = "SEED", default)]
+    pub seed: Option<u64>,
+
     /// Set the workspace root for gemini before executing the task.
     #[schemars(description = "Working directory for Gemini to execute in")]
     pub cd: PathBuf,
@@ -27,6 +103,41 @@ pub struct GeminiToolInput {
     #[serde(default)]
     pub sandbox: bool,
 
+    /// Enable the sandbox when the prompt looks like it modifies files.
+    #[schemars(
+        description = "Enable `sandbox` when the prompt looks like it modifies files, i.e. has a word starting with one of the server's sandbox keywords (delete, refactor, write, ... by default), ignoring case. This is a best-effort heuristic, not a guarantee; the decision is returned as `sandbox_decision` (default: false)"
+    )]
+    #[serde(default)]
+    pub auto_sandbox: bool,
+
+    /// Enable gemini's checkpointing of file edits. Defaults to `false`.
+    #[schemars(
+        description = "Checkpoint file edits so the project state can be restored later (default: false)"
+    )]
+    #[serde(default)]
+    pub checkpointing: bool,
+
+    /// Present `cd` to gemini read-only. Defaults to `false`.
+    #[schemars(
+        description = "Guarantee gemini cannot modify files in `cd`: enforced by the kernel on Linux, best-effort (sandbox + non-editing approval mode) elsewhere (default: false)"
+    )]
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Tools gemini may call.
+    #[schemars(
+        description = "Names of the only tools gemini may call, e.g. [\"read_file\", \"glob\"] (default: gemini's own configuration)"
+    )]
+    #[serde(rename = "ALLOWED_TOOLS", default)]
+    pub allowed_tools: Vec<String>,
+
+    /// Tools gemini must not call.
+    #[schemars(
+        description = "Names of tools gemini must not call, e.g. [\"run_shell_command\", \"write_file\"]; rejected unless the installed gemini lists `--exclude-tools` in its help"
+    )]
+    #[serde(rename = "DENIED_TOOLS", default)]
+    pub denied_tools: Vec<String>,
+
     /// Resume the specified session of the gemini.
     #[schemars(description = "Session ID to resume a previous conversation")]
     #[serde(rename = "SESSION_ID", default)]
@@ -37,16 +148,598 @@ pub struct GeminiToolInput {
     #[serde(default)]
     pub return_all_messages: bool,
 
+    /// How much of gemini's event stream to return besides the text.
+    #[schemars(
+        description = "`text` returns the reply only, `summary` adds `event_summary` with each event's type, role and the first 80 characters of its content, `full` adds every event as `all_messages` like `return_all_messages` (default: text)"
+    )]
+    #[serde(rename = "OUTPUT_DETAIL", default)]
+    pub output_detail: OutputDetail,
+
+    /// Split the turn into thinking, answer and actions.
+    #[schemars(
+        description = "Also return `structured`: the turn split into `thinking` (thought events and messages), `answer` (the assistant's other messages) and `actions` (tool calls with their status and the file they work on), ready to render (default: false)"
+    )]
+    #[serde(default)]
+    pub structured_turn: bool,
+
+    /// Fail when the reply is suspiciously short.
+    #[schemars(
+        description = "Fail with `failure_kind: \"output_too_short\"` when the trimmed `agent_messages` has fewer characters than this (default: no minimum)"
+    )]
+    #[serde(default)]
+    pub min_output_chars: Option<usize>,
+
+    /// Pipe the reply through one of the server's commands.
+    #[schemars(
+        description = "Name of one of the server's configured post-processors to pipe `agent_messages` through; its output replaces them, and a failing command fails the call with `failure_kind: \"post_process_failed\"` (default: none)"
+    )]
+    #[serde(default)]
+    pub post_process: Option<String>,
+
+    /// Parse the reply as a JSON value.
+    #[schemars(
+        description = "Parse the last fenced code block of `agent_messages` (or the whole trimmed reply when there is none) as JSON and return it as `result_json`, failing with `failure_kind: \"invalid_json\"` when it does not parse (default: false)"
+    )]
+    #[serde(default)]
+    pub expect_json: bool,
+
+    /// Succeed with a warning when gemini only made tool calls.
+    #[schemars(
+        description = "Treat a turn without assistant text (e.g. only tool calls) as a success, reporting the tool calls in `warnings` instead of failing with `no_agent_messages` (default: false)"
+    )]
+    #[serde(default)]
+    pub allow_empty_messages: bool,
+
+    /// Abort at gemini's first error event.
+    #[schemars(
+        description = "Abort the run at gemini's first error event and fail with `failure_kind: \"stream_error\"` and the event's message, instead of reading on to the end of the turn (default: false)"
+    )]
+    #[serde(default)]
+    pub fail_fast_on_stream_error: bool,
+
+    /// Keep all messages server-side for paging instead of inlining them.
+    #[schemars(
+        description = "Store all messages server-side and return a `request_id` for paging through them with `gemini_messages_page` instead of inlining them (default: false)"
+    )]
+    #[serde(default)]
+    pub paginate_messages: bool,
+
+    /// Append gemini's raw output to this file.
+    #[schemars(
+        description = "Append gemini's raw output lines to this file, a relative path inside `cd`; not allowed with `read_only` (default: none)"
+    )]
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+
+    /// Directory gemini should write generated files to.
+    #[schemars(
+        description = "Directory for files gemini generates, relative to `cd` unless absolute; created if missing and added to gemini's workspace. Files created or changed in it are listed in `artifacts` (default: none)"
+    )]
+    #[serde(rename = "OUTPUT_DIR", default)]
+    pub output_dir: Option<PathBuf>,
+
     /// The model to use for the gemini session.
     #[schemars(description = "Model to use (only specify if user explicitly requests)")]
     #[serde(default)]
     pub model: String,
+
+    /// Model to switch to when the first one is slow to respond.
+    #[schemars(
+        description = "Model to retry with when gemini sends no event within the first-event budget (default: none)"
+    )]
+    #[serde(default)]
+    pub fallback_model: String,
+
+    /// Budget for the first event before switching to `fallback_model`.
+    #[schemars(
+        description = "Seconds to wait for gemini's first event before switching to `fallback_model` (default: the server's budget, 30s)"
+    )]
+    #[serde(default)]
+    pub first_event_timeout_secs: Option<u64>,
+
+    /// Pick the model from the prompt size when no model is given.
+    #[schemars(
+        description = "When `model` is not given, pick one from the server's prompt-size thresholds (default: false)"
+    )]
+    #[serde(default)]
+    pub auto_model: bool,
+
+    /// Pretty-print the JSON result. Defaults to `false`.
+    #[schemars(description = "Pretty-print the returned JSON for human debugging (default: false)")]
+    #[serde(default)]
+    pub pretty: bool,
+
+    /// Keep resuming the session until this phrase appears in the output.
+    #[schemars(
+        description = "Automatically resume the session with a continuation prompt until this phrase appears in the output (default: disabled)"
+    )]
+    #[serde(default)]
+    pub stop_phrase: String,
+
+    /// How to shut gemini down after a timeout.
+    #[schemars(
+        description = "On timeout, `graceful` waits up to 5s for gemini to exit before killing it, `immediate` kills it right away (default: graceful)"
+    )]
+    #[serde(default)]
+    pub kill_mode: KillMode,
+
+    /// How long to keep reading after gemini completes the turn.
+    #[schemars(
+        description = "After the turn completes, `adaptive` reads trailing events until gemini exits or its stream goes idle (judged from the gaps between its events), `fixed` always waits 300ms; both stop after 300ms (default: adaptive)"
+    )]
+    #[serde(default)]
+    pub drain_mode: DrainMode,
+
+    /// Maximum number of turns when `stop_phrase` is set.
+    #[schemars(description = "Maximum number of turns when `stop_phrase` is set (default: 10)")]
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+
+    /// Report the prompt gemini actually received.
+    #[schemars(
+        description = "Return the prompt gemini received for this request after the server's transformations (framing of split parts, escaping) as `effective_prompt`, redacted like audit entries and truncated to 16 KiB (default: false)"
+    )]
+    #[serde(default)]
+    pub return_effective_prompt: bool,
+
+    /// Send PROMPT to gemini exactly as given.
+    #[schemars(
+        description = "Send PROMPT to gemini exactly as given, skipping every server-side transformation: parts of a split prompt are sent without framing, and nothing is escaped. Meant for debugging; the server may forbid it (default: false)"
+    )]
+    #[serde(default)]
+    pub raw_prompt: bool,
+
+    /// Send a prompt over the server's size limit in several turns.
+    #[schemars(
+        description = "Split a PROMPT larger than the server's size limit on paragraph/line boundaries and send the parts as consecutive turns of one session; the result is the reply to the last part (default: false)"
+    )]
+    #[serde(default)]
+    pub chunk_prompt: bool,
+
+    /// Compress an oversized `CONTEXT` in a gemini pass of its own first.
+    #[schemars(
+        description = "When PROMPT with CONTEXT is larger than the server's size limit, first have gemini compress CONTEXT in a separate turn, then send PROMPT with the compressed context; the result reports compression_ratio (default: false)"
+    )]
+    #[serde(default)]
+    pub compress_context: bool,
+
+    /// Memory limit for gemini in MiB.
+    #[schemars(
+        description = "Address-space limit for the gemini process in MiB, enforced on Linux; can only lower the server's limit (default: the server's limit)"
+    )]
+    #[serde(default)]
+    pub memory_limit_mb: Option<u64>,
+
+    /// Report gemini's peak memory use.
+    #[schemars(
+        description = "Report the peak resident memory of the gemini process as `peak_rss_bytes`, sampled while it runs; Linux only (default: false)"
+    )]
+    #[serde(default)]
+    pub collect_resource_usage: bool,
+
+    /// Run gemini on a disposable copy of `cd`.
+    #[schemars(
+        description = "Run gemini on a temporary copy of `cd` (without the server's ignored names, e.g. `.git`), leaving `cd` untouched; the files gemini changed are listed in `scratch_changes` and the copy is discarded afterwards. The session, `log_file` and `output_dir` still belong to `cd` (default: false)"
+    )]
+    #[serde(default)]
+    pub scratch_copy: bool,
+
+    /// Keep the scratch copy for inspection.
+    #[schemars(
+        description = "With `scratch_copy`, keep the copy after the run and return its path as `scratch_dir` (default: false)"
+    )]
+    #[serde(default)]
+    pub keep_scratch_copy: bool,
+
+    /// Correlation id for this call.
+    #[schemars(
+        description = "Correlation id for this call, returned as `request_id` and passed to gemini as the GEMINI_MCP_REQUEST_ID environment variable (default: generated)"
+    )]
+    #[serde(rename = "REQUEST_ID", default)]
+    pub request_id: String,
+
+    /// Labels grouping this call in metrics and logs.
+    #[schemars(
+        description = "Labels for grouping this call, e.g. {\"team\": \"search\"}; recorded on the tracing span and audit entry and counted per `key=value` by `gemini_metrics` (default: none)"
+    )]
+    #[serde(rename = "TAGS", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+
+    /// Publish the accumulating result as an MCP resource.
+    #[schemars(
+        description = "Publish the accumulating result as the MCP resource `gemini://results/<REQUEST_ID>`, updated as events arrive; subscribe to it for update notifications, and set REQUEST_ID to know the URI before the call returns (default: false)"
+    )]
+    #[serde(default)]
+    pub publish_resource: bool,
+
+    /// Timeout in seconds, overriding the server's prompt-size based timeout.
+    ///
+    /// This is a wall-clock limit on the gemini process. gemini has no flag for
+    /// the timeout of its own API calls, so that one cannot be set separately.
+    #[schemars(
+        description = "Wall-clock timeout in seconds for each gemini run; gemini's own API calls have no separately configurable timeout (default: the server's timeout, scaled with prompt size)"
+    )]
+    #[serde(rename = "TIMEOUT_SECS", default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Absolute deadline for the whole request, as Unix time in seconds.
+    ///
+    /// Compared with the wall clock once when the request starts; every run
+    /// after that is timed on the monotonic clock.
+    #[schemars(
+        description = "Deadline for the whole request as Unix time in seconds; every gemini run is cut short to end by then. It is checked against the server's clock once when the request starts, so clock changes during the run do not affect it (default: none)"
+    )]
+    #[serde(rename = "DEADLINE", default)]
+    pub deadline: Option<u64>,
+
+    /// Message roles collected into `agent_messages`.
+    #[schemars(
+        description = "Message roles whose content is collected into `agent_messages`, e.g. [\"assistant\", \"model\"] (default: [\"assistant\"])"
+    )]
+    #[serde(default)]
+    pub content_roles: Vec<String>,
+
+    /// Prefix collected text with its role when several roles are collected.
+    #[schemars(
+        description = "When several `content_roles` are collected, prefix each run of text with `[role] ` (default: false)"
+    )]
+    #[serde(default)]
+    pub prefix_roles: bool,
+
+    /// Separator between collected message chunks.
+    #[schemars(
+        description = "Separator inserted between collected message chunks: \"\", \" \" or \"\\n\". gemini's streamed chunks carry their own whitespace, so only set this for output made of whole messages (default: \"\")"
+    )]
+    #[serde(default)]
+    pub message_join: String,
+
+    /// Stop gemini after this many assistant messages.
+    #[schemars(
+        description = "Stop gemini once it has sent this many assistant messages and return what was collected with `stopped_early=True` (default: unlimited)"
+    )]
+    #[serde(default)]
+    pub max_assistant_messages: Option<usize>,
+
+    /// Markers that end generation.
+    #[schemars(
+        description = "Stop gemini as soon as its text contains any of these sequences; `agent_messages` is cut before the sequence, with `stopped_early=True` and `finish_reason: \"stop_sequence\"`. Entries must be non-empty (default: none)"
+    )]
+    #[serde(rename = "STOP_SEQUENCES", default)]
+    pub stop_sequences: Vec<String>,
+}
+
+/// How long to wait for cancelled runs to clean up on shutdown.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
+/// Default cap on turns when continuing until a stop phrase.
+pub const DEFAULT_MAX_TURNS: u32 = 10;
+
+/// Input parameters for the gemini_messages_page tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Parameters for paging through a stored gemini transcript")]
+pub struct MessagesPageInput {
+    /// Request id returned by a `gemini` call made with `paginate_messages`.
+    #[schemars(
+        description = "The `request_id` returned by a `gemini` call with `paginate_messages=True`, or its `continuation_token`"
+    )]
+    pub request_id: String,
+
+    /// Index of the first event to return.
+    #[schemars(description = "Index of the first event to return (default: 0)")]
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Maximum number of events to return.
+    #[schemars(description = "Maximum number of events to return (default: 50)")]
+    #[serde(default = "default_page_limit")]
+    pub limit: usize,
+}
+
+fn default_page_limit() -> usize {
+    DEFAULT_PAGE_LIMIT
+}
+
+/// Input parameters for the gemini_resume tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Parameters for the next turn of a gemini session")]
+pub struct ResumeInput {
+    /// Session handle returned by an earlier call.
+    #[schemars(description = "The `session` returned by an earlier `gemini` call")]
+    pub session: SessionHandle,
+
+    /// Instruction for the next turn.
+    #[schemars(description = "The prompt/instruction for the next turn")]
+    #[serde(rename = "PROMPT")]
+    pub prompt: String,
+}
+
+/// Input parameters for the gemini_batch tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Parameters for running several gemini requests at once")]
+pub struct BatchInput {
+    /// Requests to run concurrently.
+    #[schemars(
+        description = "Requests to run concurrently, each taking the parameters of the `gemini` tool"
+    )]
+    pub entries: Vec<GeminiToolInput>,
+}
+
+/// Input parameters for the gemini_batch_retry tool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Parameters for retrying the failed entries of a batch")]
+pub struct BatchRetryInput {
+    /// Batch id returned by `gemini_batch`.
+    #[schemars(description = "The `batch_id` returned by `gemini_batch`")]
+    pub batch_id: String,
+}
+
+/// The JSON value in `reply`: its last fenced code block, or the whole trimmed
+/// reply when it has none.
+fn extract_json(reply: &str) -> serde_json::Result<serde_json::Value> {
+    let mut fences = reply.match_indices("```").map(|(i, _)| i);
+    let mut block = None;
+    while let (Some(open), Some(close)) = (fences.next(), fences.next()) {
+        // Skip the info string, e.g. `json`
+        let body = &reply[open + 3..close];
+        block = Some(body.split_once('\n').map_or(body, |(_, rest)| rest));
+    }
+    serde_json::from_str(block.unwrap_or(reply).trim())
+}
+
+/// The largest char boundary in `text` not above `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Output of the gemini_metrics tool.
+#[derive(Debug, Serialize)]
+struct Metrics {
+    spend: SpendReport,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tags: BTreeMap<String, TagReport>,
+}
+
+/// What a `gemini` request has gathered by the time it stops, for
+/// [`GeminiServer::finalize`].
+#[derive(Default)]
+struct RequestState {
+    request_id: String,
+    /// Unset if the request was rejected before it was fingerprinted.
+    fingerprint: Option<String>,
+    effective_model: Option<String>,
+    /// The `SEED` passed to gemini.
+    seed: Option<u64>,
+    warnings: Vec<String>,
+    /// `OUTPUT_DIR` and its files before the run.
+    artifacts: Option<(PathBuf, Snapshot)>,
+    effective_prompt: Option<String>,
+    sandbox_decision: Option<SandboxDecision>,
+    /// Where gemini ran and that directory before the run, under
+    /// `allowed_write_extensions`.
+    write_guard: Option<(PathBuf, WriteGuard)>,
+}
+
+/// Longest `effective_prompt` returned.
+const MAX_EFFECTIVE_PROMPT_BYTES: usize = 16 * 1024;
+
+/// Name of the main tool, which prefixes the names of the others.
+const TOOL_NAME: &str = "gemini";
+
+/// Register the tools under `name` instead of [`TOOL_NAME`] and give the main
+/// tool `description`.
+fn rename_tools(
+    router: &mut ToolRouter<GeminiServer>,
+    name: Option<&str>,
+    description: Option<&str>,
+) {
+    if let Some(name) = name.filter(|name| *name != TOOL_NAME) {
+        let routes: Vec<_> = router.map.drain().map(|(_, route)| route).collect();
+        for mut route in routes {
+            let renamed = route.attr.name.replacen(TOOL_NAME, name, 1);
+            route.attr.name = renamed.into();
+            router.add_route(route);
+        }
+    }
+
+    if let Some(description) = description {
+        let name = name.unwrap_or(TOOL_NAME);
+        if let Some(route) = router.map.get_mut(name) {
+            route.attr.description = Some(description.to_string().into());
+        }
+    }
+}
+
+/// Replace the descriptions of input fields in the tools' schemas.
+fn describe_fields(router: &mut ToolRouter<GeminiServer>, descriptions: &[FieldDescription]) {
+    for FieldDescription { field, description } in descriptions {
+        let mut found = false;
+        for route in router.map.values_mut() {
+            let schema = Arc::make_mut(&mut route.attr.input_schema);
+            let property = schema
+                .get_mut("properties")
+                .and_then(|properties| properties.get_mut(field.as_str()))
+                .and_then(|property| property.as_object_mut());
+            if let Some(property) = property {
+                property.insert("description".to_string(), description.clone().into());
+                found = true;
+            }
+        }
+        if !found {
+            tracing::warn!("No tool takes field {:?} to describe", field);
+        }
+    }
+}
+
+/// Send each raw output line and status update to the client as a progress
+/// notification, event lines with their `_seq`.
+async fn forward_progress(
+    peer: Peer<RoleServer>,
+    token: ProgressToken,
+    mut lines: mpsc::UnboundedReceiver<String>,
+) {
+    let mut progress = 0.0;
+    while let Some(line) = lines.recv().await {
+        progress += 1.0;
+        let notification = ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress,
+            total: None,
+            message: Some(line),
+        };
+        if peer.notify_progress(notification).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Send each stderr line to the client as an `info` logging notification from
+/// the `stderr` logger, unless the client set a higher level.
+async fn forward_stderr(
+    peer: Peer<RoleServer>,
+    level: Arc<std::sync::Mutex<LoggingLevel>>,
+    mut lines: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(line) = lines.recv().await {
+        let wanted = matches!(
+            *level.lock().unwrap_or_else(|e| e.into_inner()),
+            LoggingLevel::Debug | LoggingLevel::Info
+        );
+        if !wanted {
+            continue;
+        }
+        let notification = LoggingMessageNotificationParam {
+            level: LoggingLevel::Info,
+            logger: Some("stderr".to_string()),
+            data: line.into(),
+        };
+        if peer.notify_logging_message(notification).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Tell the client about each update of a live result it subscribed to.
+async fn forward_resource_updates(
+    peer: Peer<RoleServer>,
+    live: Arc<LiveResults>,
+    mut updates: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(uri) = updates.recv().await {
+        if !live.is_subscribed(&uri) {
+            continue;
+        }
+        let notification = ResourceUpdatedNotificationParam { uri };
+        if peer.notify_resource_updated(notification).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Extractor rejecting a call that lacks a required field of `T` with an error
+/// naming the field, ahead of the `Parameters<T>` that would fail with serde's
+/// less helpful message.
+struct RequiredFields<T>(PhantomData<T>);
+
+impl<T: JsonSchema + 'static> FromContextPart<ToolCallContext<'_, GeminiServer>>
+    for RequiredFields<T>
+{
+    fn from_context_part(
+        context: &mut ToolCallContext<'_, GeminiServer>,
+    ) -> Result<Self, McpError> {
+        let schema = schema_for_type::<T>();
+        let missing: Vec<&str> = schema
+            .get("required")
+            .and_then(|required| required.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|field| field.as_str())
+            .filter(|field| {
+                !context
+                    .arguments
+                    .as_ref()
+                    .is_some_and(|arguments| arguments.contains_key(*field))
+            })
+            .collect();
+        match missing.as_slice() {
+            [] => Ok(Self(PhantomData)),
+            [field] => Err(McpError::invalid_params(
+                format!("missing required field: {}", field),
+                None,
+            )),
+            fields => Err(McpError::invalid_params(
+                format!("missing required fields: {}", fields.join(", ")),
+                None,
+            )),
+        }
+    }
+}
+
+/// Add the usage `result` reported, if any, to `usage`.
+fn add_usage(usage: &mut Option<TokenUsage>, result: &GeminiResult) {
+    if let Some(u) = &result.usage {
+        usage.get_or_insert_with(Default::default).add(u);
+    }
+}
+
+/// Serialize a value to JSON, compact by default or indented when `pretty` is set.
+fn to_json<T: Serialize>(value: &T, pretty: bool) -> serde_json::Result<String> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+/// `result` as JSON, or a serialization failure reported in its place.
+fn result_json(result: &GeminiResult, pretty: bool) -> String {
+    to_json(result, pretty).unwrap_or_else(|e| {
+        // Use serde_json to ensure proper escaping
+        to_json(
+            &GeminiResult::from_error(&GeminiError::Other(format!(
+                "JSON serialization error: {}",
+                e
+            ))),
+            pretty,
+        )
+        .unwrap_or_else(|_| r#"{"success":false,"error":"Unknown error"}"#.to_string())
+    })
+}
+
+/// Whether the client negotiated a protocol version with structured tool
+/// results (2025-06-18 and later).
+fn supports_structured_content(peer: &Peer<RoleServer>) -> bool {
+    peer.peer_info()
+        .is_some_and(|info| info.protocol_version >= ProtocolVersion::V_2025_06_18)
 }
 
 /// The Gemini MCP Server.
 #[derive(Clone)]
 pub struct GeminiServer {
     tool_router: ToolRouter<Self>,
+    config: Arc<ServerConfig>,
+    transcripts: Arc<TranscriptStore>,
+    sessions: Arc<SessionStore>,
+    runs: Arc<RunRegistry>,
+    batches: Arc<BatchStore>,
+    live: Arc<LiveResults>,
+    cache: Option<Arc<ResponseCache>>,
+    keys: Option<Arc<KeyPool>>,
+    spend: Arc<SpendTracker>,
+    tag_metrics: Arc<TagMetrics>,
+    /// Outcomes of the latest calls, for the error rate `gemini_health` reports.
+    outcomes: Arc<RecentOutcomes>,
+    /// Probed on the first `gemini_capabilities` call.
+    capabilities: Arc<tokio::sync::OnceCell<Capabilities>>,
+    /// Probed on first use, for each configured `binary`.
+    binary_capabilities: Arc<BTreeMap<String, tokio::sync::OnceCell<Capabilities>>>,
+    /// Checks every prompt before gemini is started.
+    content_policy: Option<Arc<dyn ContentPolicy>>,
+    /// Lowest level of logging notifications the client asked for.
+    log_level: Arc<std::sync::Mutex<LoggingLevel>>,
 }
 
 #[tool_router]
@@ -54,6 +747,25 @@ impl GeminiServer {
     pub fn new() -> Self {
         Self {
             tool_router: Self::tool_router(),
+            config: Arc::new(ServerConfig::default()),
+            transcripts: Arc::new(TranscriptStore::default()),
+            sessions: Arc::new(SessionStore::default()),
+            runs: Arc::new(RunRegistry::default()),
+            batches: Arc::new(BatchStore::default()),
+            live: Arc::new(LiveResults::default()),
+            cache: None,
+            keys: None,
+            spend: Arc::new(SpendTracker::new(
+                Vec::new(),
+                None,
+                Duration::from_secs(crate::spend::DEFAULT_SPEND_WINDOW_SECS),
+            )),
+            tag_metrics: Arc::default(),
+            outcomes: Arc::default(),
+            capabilities: Arc::default(),
+            binary_capabilities: Arc::default(),
+            content_policy: None,
+            log_level: Arc::new(std::sync::Mutex::new(LoggingLevel::Debug)),
         }
     }
 
@@ -64,101 +776,1673 @@ impl GeminiServer {
 **Return structure:**
 - `success`: boolean indicating execution status
 - `SESSION_ID`: unique identifier for resuming this conversation in future calls
+- `original_session_id`, `current_session_id`: (optional) the resumed `SESSION_ID` and the one gemini reported, when they differ; continue with `current_session_id` after a success, and resume `original_session_id` to retry a failed turn from the last complete state
+- `session`: (optional) `{id, model, cwd, created_at, turns}` handle of the session, to pass to `gemini_resume`
+- `checkpoint_id`: (optional) identifier of the last checkpoint gemini recorded when `checkpointing=True`
 - `agent_messages`: concatenated assistant response text
-- `all_messages`: (optional) complete array of JSON events when `return_all_messages=True`
+- `effective_model`: (optional) model passed to gemini, either `model` or the one picked by `auto_model`
+- `binary`: (optional) name of the configured gemini executable that ran, when `binary` was set
+- `seed`: (optional) the `SEED` gemini ran with
+- `finish_reason`: (optional) why the turn ended as reported by gemini, e.g. `stop` or `max_tokens`
+- `plan`: (optional) `{steps: [{description, status}]}` when gemini announced a plan
+- `usage`: (optional) `{input_tokens, output_tokens, total_tokens, thinking_tokens}` when gemini reported token counts, including those of runs that timed out, were cancelled or were retried; `thinking_tokens` only when reported separately
+- `post_process_status`: (optional) exit code of the `post_process` command, whose output replaced `agent_messages` when it succeeded
+- `cost`: (optional) what `usage` cost at the server's per-model costs, counted towards its spend budget
+- `status_updates`: (optional) gemini's activity reports (e.g. \"Searching...\"), also sent as progress notifications
+- `citations`: (optional) `[{url, title, snippet}]` for the sources gemini cited, kept out of `agent_messages`
+- `tool_calls`: (optional) `[{name, id, status}]` for every tool gemini called
+- `all_messages`: (optional) complete array of JSON events when `return_all_messages=True` or `OUTPUT_DETAIL=\"full\"`, each with an injected `_seq`: its number in the request, counting from 1 across every gemini run the request took (retries, prompt parts, fallbacks, `stop_phrase` turns), also injected into the event's progress notification
+- `forbidden_writes`: (optional) workspace files written without an extension allowed by the server, failing the call with `forbidden_write`
+- `reverted_writes`: (optional) the `forbidden_writes` the server undid
+- `exit_path`: how the gemini run ended, e.g. `turn_completed`, `eof`, `timeout`, `first_event_timeout`, `cancelled`, `stopped_early`, `reauth_required`, `stream_error`, `no_completion`, `stream_unparseable`; `/killed_after_wait` is appended when gemini had to be killed after not exiting in time
+- `sandbox_decision`: (optional) `{enabled, keyword}` when `auto_sandbox=True`: whether the prompt matched a sandbox keyword
+- `outside_writes`: (optional) files gemini's tool calls wrote outside `cd` and `output_dir`, when the server checks for them
+- `peak_rss_bytes`: (optional) peak resident memory of the gemini process when `collect_resource_usage=True` (Linux only)
+- `structured`: (optional) `{thinking, answer, actions: [{name, id, status, path}]}` when `structured_turn=True`
+- `result_json`: (optional) the JSON value parsed from the reply when `expect_json=True`
+- `event_summary`: (optional) `[{type, role, preview}]`, one per event, when `OUTPUT_DETAIL=\"summary\"`
+- `resource_uri`: (optional) the MCP resource the result was published at when `publish_resource=True`
+- `request_id`: correlation id of the call (`REQUEST_ID` or generated), also the key for `gemini_messages_page` when `paginate_messages=True`
+- `turns`: (optional) number of turns run when `stop_phrase` is set
+- `continuation_token`: (optional) set when the response was cut to fit the server's payload limit; pass it as `request_id` to `gemini_messages_page` for the stored events and the rest of `agent_messages` (as `{type: \"agent_messages\", content}` entries)
+- `effective_prompt`: (optional) the prompt gemini received when `return_effective_prompt=True`, redacted and truncated
+- `request_fingerprint`: SHA-256 of the normalized inputs (prompt, workspace, model, flags), stable across identical requests
+- `cached`: (optional) `true` when the result was served from the server's response cache
+- `scratch_changes`: (optional) files gemini created, modified or deleted in the copy when `scratch_copy=True`, relative to it
+- `scratch_dir`: (optional) where the copy was kept when `keep_scratch_copy=True`
+- `artifacts`: (optional) files created or modified under `OUTPUT_DIR` during the run
+- `chunks`: (optional) number of parts the prompt was sent in when `chunk_prompt=True` split it
+- `compression_ratio`: (optional) size of the compressed `CONTEXT` relative to the original when `compress_context=True` compressed it
+- `stopped_early`: (optional) `true` when gemini was stopped by `max_assistant_messages` or `STOP_SEQUENCES`
+- `warnings`: (optional) non-fatal problems, e.g. resuming a session from a different `cd`
 - `error`: error description when `success=False`
+- `failure_kind`: (optional) machine-readable failure category when `success=False`, e.g. `timeout`, `no_session_id`, `session_quota_exceeded`, `budget_exceeded`, `quota_exceeded`, `reauth_required`, `stream_error`, `memory_limit`, `output_too_short`, `invalid_json`, `post_process_failed`, `no_completion`, `stream_unparseable`, `forbidden_write`, `policy_denied`, `write_outside_workspace`
+- `errors`: (optional) problems met while reading gemini's output when `success=False`, as `{kind, message, line}` with `kind` `json_decode` or `io`
+- `_debug`: (optional) `{content: [{type, bytes}], structured_content_bytes, is_error}` when the server runs with `--debug-envelope`: the content blocks of the tool result and their sizes, and the size of its structured content when sent, all measured without `_debug`
 
 **Best practices:**
 - Always capture and reuse `SESSION_ID` for multi-turn interactions
+- Resume a session with the same `cd` it was created in
 - Enable `sandbox` mode when file modifications should be isolated
+- Enable `read_only` for analysis-only tasks that must never modify files
 - Use `return_all_messages` only when detailed execution traces are necessary (increases payload size)
-- Only pass `model` when the user has explicitly requested a specific model"
+- Prefer `paginate_messages` over `return_all_messages` for long sessions to keep responses small
+- Send a progress token to receive gemini's raw output lines as progress notifications while it runs; set `log_file` to also keep them on disk
+- Use a narrow `ALLOWED_TOOLS` (or `DENIED_TOOLS`, where gemini supports it) for untrusted prompts
+- Only pass `model` when the user has explicitly requested a specific model
+- Set `fallback_model` to a faster model when the requested one may be overloaded; `effective_model` reports which one served the request"
     )]
     async fn gemini(
         &self,
-        Parameters(input): Parameters<GeminiToolInput>,
+        meta: Meta,
+        peer: Peer<RoleServer>,
+        _: RequiredFields<GeminiToolInput>,
+        Parameters(mut input): Parameters<GeminiToolInput>,
     ) -> Result<CallToolResult, McpError> {
-        let session_id = if input.session_id.is_empty() {
-            None
-        } else {
-            Some(input.session_id.as_str())
-        };
+        let span = telemetry::invocation_span(&meta);
+        telemetry::record_tags(&span, &input.tags);
+        let started = Instant::now();
 
-        let model = if input.model.is_empty() {
-            None
-        } else {
-            Some(input.model.as_str())
-        };
+        // Stream raw output and status updates to clients that asked for progress
+        let mut sinks = Sinks::default();
+        let mut status_sink = None;
+        let progress = meta.get_progress_token().map(|token| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            sinks.push(SequencedSender(tx.clone()));
+            status_sink = Some(tx);
+            tokio::spawn(forward_progress(peer.clone(), token, rx))
+        });
+        let live = input.publish_resource.then(|| {
+            if input.request_id.is_empty() {
+                input.request_id = uuid::Uuid::new_v4().to_string();
+            }
+            self.live.start(&input.request_id);
+            let (tx, rx) = mpsc::unbounded_channel();
+            sinks.push(LiveSink {
+                results: Arc::clone(&self.live),
+                request_id: input.request_id.clone(),
+                updates: tx,
+            });
+            tokio::spawn(forward_resource_updates(
+                peer.clone(),
+                Arc::clone(&self.live),
+                rx,
+            ))
+        });
+        if live.is_some() {
+            let _ = peer.notify_resource_list_changed().await;
+        }
+        let mut stderr_sink = None;
+        let stderr = self.config.forward_stderr.then(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            stderr_sink = Some(tx);
+            tokio::spawn(forward_stderr(peer.clone(), self.log_level.clone(), rx))
+        });
 
-        let result = execute_gemini(
-            &input.prompt,
-            &input.cd,
-            input.sandbox,
-            session_id,
-            model,
-            input.return_all_messages,
-        )
-        .await;
-
-        let json_str = match result {
-            Ok(gemini_result) => serde_json::to_string(&gemini_result).unwrap_or_else(|e| {
-                // Use serde_json to ensure proper escaping
-                serde_json::to_string(&GeminiResult {
-                    success: false,
-                    session_id: None,
-                    agent_messages: None,
-                    all_messages: None,
-                    error: Some(format!("JSON serialization error: {}", e)),
-                })
-                .unwrap_or_else(|_| r#"{"success":false,"error":"Unknown error"}"#.to_string())
-            }),
-            Err(e) => {
-                let error_result = GeminiResult {
-                    success: false,
-                    session_id: None,
-                    agent_messages: None,
-                    all_messages: None,
-                    error: Some(e.to_string()),
-                };
-                serde_json::to_string(&error_result)
-                    .unwrap_or_else(|_| r#"{"success":false,"error":"Unknown error"}"#.to_string())
+        let mut result = self
+            .run_gemini(&input, sinks, status_sink, stderr_sink)
+            .instrument(span.clone())
+            .await;
+        if let Some(progress) = progress {
+            // The sender is dropped with the options, so this only drains what is queued
+            let _ = progress.await;
+        }
+        if let Some(stderr) = stderr {
+            let _ = stderr.await;
+        }
+        if let Some(live) = live {
+            let _ = live.await;
+            let uri = live_result_uri(&input.request_id);
+            self.live.finish(&input.request_id, &result);
+            if self.live.is_subscribed(&uri) {
+                let _ = peer
+                    .notify_resource_updated(ResourceUpdatedNotificationParam { uri: uri.clone() })
+                    .await;
             }
-        };
+            result.resource_uri = Some(uri);
+        }
+        telemetry::record_result(&span, &result, started.elapsed());
+        let structured = self.config.structured_output && supports_structured_content(&peer);
+        self.cap_payload(&mut result, input.pretty, structured);
+
+        let json_str = self.envelope_json(&mut result, input.pretty, structured);
+
+        if structured {
+            if let Ok(value) = serde_json::to_value(&result) {
+                let mut structured = CallToolResult::structured(value);
+                structured.content = vec![Content::text(json_str)];
+                return Ok(structured);
+            }
+        }
 
         Ok(CallToolResult::success(vec![Content::text(json_str)]))
     }
-}
 
-impl Default for GeminiServer {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+    #[tool(
+        name = "gemini_validate",
+        description = "Validates parameters for the `gemini` tool without running Gemini, reporting every problem found at once.
 
-#[tool_handler]
-impl rmcp::ServerHandler for GeminiServer {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            instructions: Some(
-                "Gemini MCP Server - Wraps Gemini CLI as a standard MCP protocol interface".into(),
-            ),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            ..Default::default()
+**Return structure:**
+- `valid`: boolean, `false` when any issue is an error
+- `issues`: array of `{field, severity, message}` where `severity` is `error` or `warning`"
+    )]
+    async fn gemini_validate(
+        &self,
+        _: RequiredFields<GeminiToolInput>,
+        Parameters(input): Parameters<GeminiToolInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut report = validate_input(&input, &self.config);
+        if let Err(e) = find_gemini_executable() {
+            report.error("gemini", e.to_string());
         }
-    }
-}
 
-/// Create and run the MCP server over stdio transport.
-pub async fn run_server() -> anyhow::Result<()> {
-    tracing::info!("Starting Gemini MCP Server...");
+        let json_str = to_json(&report, input.pretty)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
 
-    let server = GeminiServer::new();
-    let service = server.serve(rmcp::transport::stdio()).await?;
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
 
-    tracing::info!("Gemini MCP Server is running");
+    #[tool(
+        name = "gemini_capabilities",
+        description = "Reports what the installed Gemini CLI supports, probed from `gemini --help` and `gemini --version` once and then cached. Once probed, `gemini` calls fail fast if gemini lacks the `stream-json` output format.
 
-    service.waiting().await?;
+**Return structure:**
+- `version`: (optional) gemini's version
+- `output_formats`: values accepted by `--output-format`, empty if the help text lists none
+- `prompt_flag_deprecated`: whether the help text marks `--prompt` as deprecated, in which case `--prompt-passing auto` passes the prompt positionally
+- `options`: long options listed in the help text, e.g. `--seed`, which `SEED` requires"
+    )]
+    async fn gemini_capabilities(&self) -> Result<CallToolResult, McpError> {
+        let capabilities = self
+            .capabilities
+            .get_or_try_init(capabilities::probe)
+            .await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        let json_str = to_json(capabilities, false)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    #[tool(
+        name = "gemini_metrics",
+        description = "Reports the server's running metrics.
+
+**Return structure:**
+- `spend`: `{total, window, window_secs, budget, remaining}`: cost of the token usage of every run since the server started and of those in the current budget window, at the server's per-model costs; `budget` and `remaining` only when the server caps spend
+- `tags`: (optional) `{\"key=value\": {calls, failures, mean_duration_ms}}` for every `TAGS` entry calls were made with"
+    )]
+    async fn gemini_metrics(&self) -> Result<CallToolResult, McpError> {
+        let metrics = Metrics {
+            spend: self.spend.report(),
+            tags: self.tag_metrics.report(),
+        };
+
+        let json_str =
+            to_json(&metrics, false).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    #[tool(
+        name = "gemini_health",
+        description = "Reports the health of each of the server's subsystems, probing the installed Gemini CLI like `gemini_capabilities` if it has not been yet. Calls are never queued and there is no circuit breaker, so neither is reported; gemini's version is reported but not checked against a minimum.
+
+**Return structure:**
+- `status`: `ok`, `degraded` or `down`, the worst of the subsystems below
+- `gemini`: `{status, path, version, compatible, error}`: the gemini executable, down when it is missing or lacks the `stream-json` output format (all `compatible` checks), degraded when probing it failed
+- `binaries`: (optional) `{name: {status, path, version, compatible, error}}` for each configured `binary`, like `gemini`; one that is down only degrades the server
+- `auth`: `{status, method, keys, available_keys}` where `method` is `key_pool`, `env`, `oauth` or `none`; degraded when no credentials were found or every pooled key is cooling down
+- `concurrency`: `{status, active_runs}`: runs in flight; there is no queue behind them
+- `errors`: `{status, calls, failures, error_rate}` over the last 100 calls, degraded when at least half of 5 or more failed
+- `spend`: `{status, total, window, window_secs, budget, remaining}` as in `gemini_metrics`, degraded when the budget is used up
+- `config`: summary of the settings that most affect calls"
+    )]
+    async fn gemini_health(&self) -> Result<CallToolResult, McpError> {
+        let report = self.health().await;
+
+        let json_str =
+            to_json(&report, false).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    #[tool(
+        name = "gemini_messages_page",
+        description = "Returns a page of the event transcript stored by a `gemini` call made with `paginate_messages=True`, or of the overflow of a response cut at the server's payload limit (`continuation_token`).
+
+**Return structure:**
+- `request_id`: the transcript being paged
+- `offset`: index of the first returned event
+- `total`: total number of events in the transcript
+- `messages`: the events in this page
+- `next_offset`: (optional) offset of the next page, absent on the last page"
+    )]
+    async fn gemini_messages_page(
+        &self,
+        _: RequiredFields<MessagesPageInput>,
+        Parameters(input): Parameters<MessagesPageInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let page = self
+            .transcripts
+            .page(&input.request_id, input.offset, input.limit)
+            .ok_or_else(|| {
+                McpError::invalid_params(
+                    format!("Unknown or expired request_id: {}", input.request_id),
+                    None,
+                )
+            })?;
+
+        let json_str = serde_json::to_string(&page)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    #[tool(
+        name = "gemini_resume",
+        description = "Continues a session returned by `gemini` with a new prompt, in the session's working directory and with its model. Returns the same structure as `gemini`, including the updated `session`."
+    )]
+    async fn gemini_resume(
+        &self,
+        _: RequiredFields<ResumeInput>,
+        Parameters(input): Parameters<ResumeInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let input = input.session.resume(input.prompt);
+        let mut result = self.run_gemini(&input, Sinks::default(), None, None).await;
+        self.cap_payload(&mut result, input.pretty, false);
+
+        let json_str = self.envelope_json(&mut result, input.pretty, false);
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    #[tool(
+        name = "gemini_batch",
+        description = "Runs several `gemini` requests concurrently and keeps their results, so `gemini_batch_retry` can re-run only the entries that failed transiently.
+
+**Return structure:**
+- `batch_id`: identifier to pass to `gemini_batch_retry`
+- `success`: whether every entry succeeded
+- `results`: one `gemini` result per entry, in input order"
+    )]
+    async fn gemini_batch(
+        &self,
+        _: RequiredFields<BatchInput>,
+        Parameters(input): Parameters<BatchInput>,
+    ) -> Result<CallToolResult, McpError> {
+        if input.entries.len() > self.config.max_batch_entries {
+            return Err(McpError::invalid_params(
+                format!(
+                    "A batch takes at most {} entries, got {}",
+                    self.config.max_batch_entries,
+                    input.entries.len()
+                ),
+                None,
+            ));
+        }
+        let all: Vec<usize> = (0..input.entries.len()).collect();
+        let results = self.run_batch_entries(&input.entries, &all).await;
+        let batch = Batch {
+            entries: input.entries,
+            results,
+        };
+        self.store_batch(uuid::Uuid::new_v4().to_string(), batch, Vec::new())
+    }
+
+    #[tool(
+        name = "gemini_batch_retry",
+        description = "Re-runs the entries of a `gemini_batch` that failed with a transient `failure_kind` (timeout, first_event_timeout, no_completion, quota_exceeded, stream_error), keeping every other result.
+
+**Return structure:**
+- `batch_id`: the batch, whose stored results are updated
+- `success`: whether every entry has now succeeded
+- `results`: one `gemini` result per entry, in input order
+- `retried`: (optional) indices of the entries that were run again"
+    )]
+    async fn gemini_batch_retry(
+        &self,
+        _: RequiredFields<BatchRetryInput>,
+        Parameters(input): Parameters<BatchRetryInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut batch = self.batches.get(&input.batch_id).ok_or_else(|| {
+            McpError::invalid_params(
+                format!("Unknown or expired batch_id: {}", input.batch_id),
+                None,
+            )
+        })?;
+
+        let retried = batch.transient_failures();
+        let results = self.run_batch_entries(&batch.entries, &retried).await;
+        for (&index, result) in retried.iter().zip(results) {
+            batch.results[index] = result;
+        }
+        self.store_batch(input.batch_id, batch, retried)
+    }
+}
+
+impl GeminiServer {
+    /// Create a server with the given configuration.
+    pub fn with_config(config: ServerConfig) -> Self {
+        let mut tool_router = Self::tool_router();
+        rename_tools(
+            &mut tool_router,
+            config.tool_name.as_deref(),
+            config.tool_description.as_deref(),
+        );
+        describe_fields(&mut tool_router, &config.field_descriptions);
+
+        Self {
+            tool_router,
+            cache: config
+                .response_cache_ttl_secs
+                .map(|ttl| Arc::new(ResponseCache::new(Duration::from_secs(ttl)))),
+            keys: KeyPool::new(
+                config.api_keys.clone(),
+                config.key_rotation,
+                Duration::from_secs(config.key_cooldown_secs),
+            )
+            .map(Arc::new),
+            spend: Arc::new(SpendTracker::new(
+                config.model_costs.clone(),
+                config.spend_budget,
+                Duration::from_secs(config.spend_window_secs),
+            )),
+            binary_capabilities: Arc::new(
+                config
+                    .binaries
+                    .iter()
+                    .map(|b| (b.name.clone(), Default::default()))
+                    .collect(),
+            ),
+            config: Arc::new(config),
+            ..Self::new()
+        }
+    }
+
+    /// Capabilities of the configured gemini `binary`, or of the one on PATH,
+    /// probed on first use.
+    async fn capabilities_of(&self, binary: Option<&str>) -> Result<&Capabilities, GeminiError> {
+        let configured = binary.and_then(|name| {
+            Some((
+                self.binary_capabilities.get(name)?,
+                self.config.binary(name)?,
+            ))
+        });
+        match configured {
+            Some((cell, path)) => {
+                cell.get_or_try_init(|| capabilities::probe_executable(path))
+                    .await
+            }
+            None => self.capabilities.get_or_try_init(capabilities::probe).await,
+        }
+    }
+
+    /// Capabilities of the configured gemini `binary`, or of the one on PATH,
+    /// if they were probed already.
+    fn known_capabilities(&self, binary: Option<&str>) -> Option<&Capabilities> {
+        match binary.and_then(|name| self.binary_capabilities.get(name)) {
+            Some(cell) => cell.get(),
+            None => self.capabilities.get(),
+        }
+    }
+
+    /// The health of each subsystem, as reported by `gemini_health`.
+    pub async fn health(&self) -> HealthReport {
+        let path = find_gemini_executable().map_err(|e| e.to_string());
+        let capabilities = match path {
+            Ok(_) => Some(
+                self.capabilities
+                    .get_or_try_init(capabilities::probe)
+                    .await
+                    .map_err(|e| e.to_string()),
+            ),
+            Err(_) => None,
+        };
+        let mut binaries = BTreeMap::new();
+        for binary in &self.config.binaries {
+            let path = if binary.path.is_file() {
+                Ok(binary.path.to_string_lossy().to_string())
+            } else {
+                Err(format!("{} is not a file", binary.path.to_string_lossy()))
+            };
+            let capabilities = match path {
+                Ok(_) => Some(
+                    self.capabilities_of(Some(&binary.name))
+                        .await
+                        .map_err(|e| e.to_string()),
+                ),
+                Err(_) => None,
+            };
+            binaries.insert(
+                binary.name.clone(),
+                GeminiHealth::new(path, capabilities),
+            );
+        }
+        HealthReport::new(
+            GeminiHealth::new(path, capabilities),
+            binaries,
+            self.auth_health(),
+            ConcurrencyHealth {
+                status: HealthStatus::Ok,
+                active_runs: self.runs.active(),
+            },
+            self.outcomes.report(),
+            SpendHealth::new(self.spend.report()),
+            self.config_summary(),
+        )
+    }
+
+    /// Where gemini's credentials come from: the key pool, an API key it
+    /// inherits, or its cached OAuth login.
+    fn auth_health(&self) -> AuthHealth {
+        if let Some(keys) = &self.keys {
+            return AuthHealth::key_pool(keys.len(), keys.available());
+        }
+        let inherited = |name: &str| {
+            std::env::var_os(name).is_some_and(|value| !value.is_empty())
+                && (self.config.env_policy == EnvPolicy::Inherit
+                    || env_allowed(name, &self.config.env_allowlist))
+        };
+        let oauth = std::env::var_os("HOME").is_some_and(|home| {
+            Path::new(&home)
+                .join(".gemini")
+                .join("oauth_creds.json")
+                .is_file()
+        });
+        AuthHealth::method(
+            if [API_KEY_ENV, "GOOGLE_API_KEY"].into_iter().any(inherited) {
+                AuthMethod::Env
+            } else if oauth {
+                AuthMethod::Oauth
+            } else {
+                AuthMethod::None
+            },
+        )
+    }
+
+    fn config_summary(&self) -> ConfigSummary {
+        let config = &self.config;
+        ConfigSummary {
+            tool_name: config.tool_name.as_deref().unwrap_or(TOOL_NAME).to_string(),
+            binaries: config.binaries.len(),
+            prompt_passing: match config.prompt_passing {
+                PromptPassing::Auto => "auto",
+                PromptPassing::Flag => "flag",
+                PromptPassing::Positional => "positional",
+                PromptPassing::Stdin => "stdin",
+            },
+            env_policy: match config.env_policy {
+                EnvPolicy::Inherit => "inherit",
+                EnvPolicy::Allowlist => "allowlist",
+            },
+            max_timeout_secs: config.timeout.max_secs,
+            first_event_timeout_secs: config.first_event_timeout_secs,
+            response_cache: self.cache.is_some(),
+            structured_output: config.structured_output,
+            max_prompt_bytes: config.max_prompt_bytes,
+            max_payload_bytes: config.max_payload_bytes,
+            memory_limit_mb: config.memory_limit_mb,
+        }
+    }
+
+    /// Check every prompt with `policy` before starting gemini.
+    pub fn with_content_policy(mut self, policy: impl ContentPolicy + 'static) -> Self {
+        self.content_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Cancel the gemini run in flight for `request_id`, which then ends with
+    /// `failure_kind: "cancelled"`. Returns whether such a run was found.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        self.runs.cancel(request_id)
+    }
+
+    /// Serve over `transport` until the client disconnects, then cancel the
+    /// gemini runs still in flight so no child process is left behind.
+    pub async fn run<T, E, A>(self, transport: T) -> anyhow::Result<()>
+    where
+        T: rmcp::transport::IntoTransport<rmcp::RoleServer, E, A>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let runs = Arc::clone(&self.runs);
+        let service = self.serve(transport).await?;
+
+        tracing::info!("Gemini MCP Server is running");
+
+        let quit_reason = service.waiting().await?;
+        tracing::info!("Client disconnected ({:?})", quit_reason);
+
+        let active = runs.active();
+        if active > 0 {
+            tracing::warn!("Cancelling {} in-flight gemini run(s)", active);
+            runs.cancel_all();
+            if !runs
+                .wait_idle(Duration::from_secs(SHUTDOWN_TIMEOUT_SECS))
+                .await
+            {
+                tracing::warn!("Some gemini runs did not stop in time");
+            }
+        }
+
+        tracing::info!("Gemini MCP Server shutting down");
+        Ok(())
+    }
+
+    /// Run the entries at `indices` concurrently, at most `batch_concurrency`
+    /// at a time, returning their results in the same order.
+    async fn run_batch_entries(
+        &self,
+        entries: &[GeminiToolInput],
+        indices: &[usize],
+    ) -> Vec<GeminiResult> {
+        let permits = Arc::new(Semaphore::new(self.config.batch_concurrency.max(1)));
+        let handles: Vec<_> = indices
+            .iter()
+            .map(|&index| {
+                let server = self.clone();
+                let entry = entries[index].clone();
+                let permits = Arc::clone(&permits);
+                tokio::spawn(async move {
+                    let _permit = permits.acquire_owned().await;
+                    let mut result = server
+                        .run_gemini(&entry, Sinks::default(), None, None)
+                        .await;
+                    server.cap_payload(&mut result, entry.pretty, false);
+                    result
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            results.push(
+                handle.await.unwrap_or_else(|e| {
+                    GeminiResult::from_error(&GeminiError::Other(e.to_string()))
+                }),
+            );
+        }
+        results
+    }
+
+    /// Keep `batch` under `batch_id` and return it as a tool result.
+    fn store_batch(
+        &self,
+        batch_id: String,
+        batch: Batch,
+        retried: Vec<usize>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = BatchResult {
+            batch_id: batch_id.clone(),
+            success: batch.results.iter().all(|r| r.success),
+            results: batch.results.clone(),
+            retried,
+        };
+        self.batches.insert(batch_id, batch);
+
+        let json_str = serde_json::to_string(&result)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(json_str)]))
+    }
+
+    /// Run a `gemini` request, then [`finalize`](Self::finalize) it however
+    /// it ended.
+    async fn run_gemini(
+        &self,
+        input: &GeminiToolInput,
+        sinks: Sinks,
+        status_sink: Option<mpsc::UnboundedSender<String>>,
+        stderr_sink: Option<mpsc::UnboundedSender<String>>,
+    ) -> GeminiResult {
+        let started = Instant::now();
+        let result = self
+            .run_in_workspace(input, sinks, status_sink, stderr_sink)
+            .await;
+        self.tag_metrics
+            .record(&input.tags, &result, started.elapsed());
+        self.outcomes.record(result.success);
+        result
+    }
+
+    /// Run the request in `cd`, or in a scratch copy of it.
+    async fn run_in_workspace(
+        &self,
+        input: &GeminiToolInput,
+        sinks: Sinks,
+        status_sink: Option<mpsc::UnboundedSender<String>>,
+        stderr_sink: Option<mpsc::UnboundedSender<String>>,
+    ) -> GeminiResult {
+        let mut run = RequestState {
+            request_id: if input.request_id.is_empty() {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                input.request_id.clone()
+            },
+            ..Default::default()
+        };
+        if !input.scratch_copy {
+            let outcome = self
+                .execute_request(input, &input.cd, sinks, status_sink, stderr_sink, &mut run)
+                .await;
+            return self.finalize(input, run, outcome);
+        }
+
+        // Copying, diffing and removing a workspace walk the whole tree, so
+        // they run off the async workers
+        let (cd, ignore, max_bytes) = (
+            input.cd.clone(),
+            self.config.scratch_copy_ignore.clone(),
+            self.config.scratch_copy_max_bytes,
+        );
+        let created =
+            tokio::task::spawn_blocking(move || ScratchCopy::create(&cd, &ignore, max_bytes))
+                .await
+                .unwrap_or_else(|e| Err(GeminiError::Other(e.to_string())));
+        let scratch = match created {
+            Ok(scratch) => scratch,
+            Err(e) => return self.finalize(input, run, Err(e)),
+        };
+        // Only gemini runs in the copy; the session, cache, log and outputs
+        // still belong to `cd`
+        let outcome = self
+            .execute_request(
+                input,
+                scratch.dir(),
+                sinks,
+                status_sink,
+                stderr_sink,
+                &mut run,
+            )
+            .await;
+        let mut result = self.finalize(input, run, outcome);
+        let keep = input.keep_scratch_copy;
+        (result.scratch_changes, result.scratch_dir) = tokio::task::spawn_blocking(move || {
+            let changes = scratch.changes();
+            (changes, keep.then(|| scratch.keep()))
+        })
+        .await
+        .unwrap_or_default();
+        result
+    }
+
+    /// Validate the input, run gemini in `workdir` (`cd` or a scratch copy of
+    /// it) and record the session, noting in `run` what
+    /// [`finalize`](Self::finalize) needs.
+    async fn execute_request(
+        &self,
+        input: &GeminiToolInput,
+        workdir: &Path,
+        mut sinks: Sinks,
+        status_sink: Option<mpsc::UnboundedSender<String>>,
+        stderr_sink: Option<mpsc::UnboundedSender<String>>,
+        run: &mut RequestState,
+    ) -> Result<GeminiResult, GeminiError> {
+        let report = validate_input(input, &self.config);
+        if report.has_errors() {
+            return Err(GeminiError::InvalidInput(report.error_summary()));
+        }
+
+        let deadline = input
+            .deadline
+            .map(Deadline::from_unix_secs)
+            .transpose()
+            .map_err(|passed| GeminiError::DeadlinePassed { passed })?;
+
+        if let Some(capabilities) = self.known_capabilities(input.binary.as_deref()) {
+            if !capabilities.supports_output_format(capabilities::STREAM_JSON) {
+                return Err(GeminiError::UnsupportedOutputFormat(
+                    capabilities::STREAM_JSON.to_string(),
+                ));
+            }
+        }
+
+        if let Some(log_file) = &input.log_file {
+            let path = input.cd.join(log_file);
+            let sink = FileSink::append(&path).map_err(|source| GeminiError::LogFile {
+                path: path.to_string_lossy().to_string(),
+                source,
+            })?;
+            sinks.push(sink);
+        }
+
+        let output_dir = input.output_dir.as_ref().map(|dir| input.cd.join(dir));
+        if let Some(dir) = &output_dir {
+            std::fs::create_dir_all(dir).map_err(|source| GeminiError::OutputDir {
+                path: dir.to_string_lossy().to_string(),
+                source,
+            })?;
+        }
+
+        if input.auto_sandbox {
+            let keyword = self.config.sandbox_keyword_in(&input.prompt);
+            run.sandbox_decision = Some(SandboxDecision {
+                enabled: keyword.is_some(),
+                keyword: keyword.map(str::to_string),
+            });
+        }
+
+        let session_id = if input.session_id.is_empty() {
+            None
+        } else {
+            Some(input.session_id.as_str())
+        };
+
+        let model = if !input.model.is_empty() {
+            Some(input.model.clone())
+        } else if input.auto_model {
+            self.config
+                .auto_model_for(&input.prompt)
+                .map(str::to_string)
+        } else {
+            None
+        };
+
+        let request_id = run.request_id.clone();
+        let fingerprint = cache::fingerprint(input, model.as_deref());
+        run.fingerprint = Some(fingerprint.clone());
+
+        if let Some(spent) = self.spend.exceeded() {
+            return Err(GeminiError::BudgetExceeded {
+                spent,
+                budget: self.config.spend_budget.unwrap_or_default(),
+                window_secs: self.config.spend_window_secs,
+            });
+        }
+
+        run.effective_model = model.clone();
+        let handle = self.runs.register(&request_id);
+        let fallback_model =
+            (!input.fallback_model.is_empty()).then(|| input.fallback_model.clone());
+        let mut options = GeminiOptions {
+            subcommand: (!input.subcommand.is_empty()).then(|| input.subcommand.clone()),
+            executable: input
+                .binary
+                .as_deref()
+                .and_then(|name| self.config.binary(name))
+                .map(Path::to_path_buf),
+            sandbox: input.sandbox || run.sandbox_decision.as_ref().is_some_and(|d| d.enabled),
+            checkpointing: input.checkpointing,
+            read_only: input.read_only,
+            session_id: session_id.map(str::to_string),
+            model,
+            return_all_messages: input.return_all_messages
+                || input.paginate_messages
+                || input.output_detail == OutputDetail::Full,
+            event_summary: input.output_detail == OutputDetail::Summary,
+            structured: input.structured_turn,
+            collect_resource_usage: input.collect_resource_usage,
+            kill_mode: input.kill_mode,
+            drain_mode: input.drain_mode,
+            non_utf8_paths: self.config.non_utf8_paths,
+            output_encoding: self.config.output_encoding,
+            prompt_passing: self.prompt_passing(input.binary.as_deref()),
+            env_allowlist: (self.config.env_policy == EnvPolicy::Allowlist)
+                .then(|| self.config.env_allowlist.clone()),
+            outside_writes: self.config.outside_writes,
+            allowed_tools: input.allowed_tools.clone(),
+            denied_tools: input.denied_tools.clone(),
+            output_dir: output_dir.clone(),
+            session_id_pointer: self.config.session_id_pointer.clone(),
+            allow_empty_messages: input.allow_empty_messages,
+            fail_fast_on_stream_error: input.fail_fast_on_stream_error,
+            no_messages_template: self.config.no_messages_template.clone(),
+            benign_output: self.config.benign_output_patterns.clone(),
+            max_events_without_completion: self.config.max_events_without_completion,
+            max_decode_errors: self.config.max_decode_errors,
+            content_roles: input.content_roles.clone(),
+            prefix_roles: input.prefix_roles,
+            message_join: input.message_join.clone(),
+            raw_prompt: input.raw_prompt,
+            max_assistant_messages: input.max_assistant_messages,
+            stop_sequences: input.stop_sequences.clone(),
+            memory_limit_mb: match (input.memory_limit_mb, self.config.memory_limit_mb) {
+                (Some(requested), Some(server)) => Some(requested.min(server)),
+                (requested, server) => requested.or(server),
+            },
+            request_id: Some(request_id.clone()),
+            cancel: Some(handle.token()),
+            sinks,
+            status_sink,
+            stderr_sink,
+            first_event_timeout: fallback_model.as_ref().map(|_| {
+                Duration::from_secs(
+                    input
+                        .first_event_timeout_secs
+                        .unwrap_or(self.config.first_event_timeout_secs),
+                )
+            }),
+            timeout: Some(
+                input
+                    .timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or_else(|| self.config.timeout.for_prompt(&input.prompt)),
+            ),
+            deadline,
+            ..Default::default()
+        };
+
+        if let Some(seed) = input.seed {
+            let capabilities = self.capabilities_of(input.binary.as_deref()).await?;
+            if !capabilities.supports_option("--seed") {
+                return Err(GeminiError::InvalidInput(
+                    "SEED is set, but the installed gemini has no --seed option".to_string(),
+                ));
+            }
+            options.seed = Some(seed);
+            if capabilities.supports_option("--temperature") {
+                options.temperature = Some(0.0);
+            } else {
+                run.warnings.push(
+                    "The installed gemini has no --temperature option; SEED alone may not make its output reproducible"
+                        .to_string(),
+                );
+            }
+            run.seed = Some(seed);
+        }
+
+        // gemini releases without the flag only take excludeTools from settings.json
+        if !input.denied_tools.is_empty() {
+            let capabilities = self.capabilities_of(input.binary.as_deref()).await?;
+            if !capabilities.supports_option("--exclude-tools") {
+                return Err(GeminiError::InvalidInput(
+                    "DENIED_TOOLS is set, but the installed gemini has no --exclude-tools option; use ALLOWED_TOOLS or excludeTools in gemini's settings.json"
+                        .to_string(),
+                ));
+            }
+        }
 
-    tracing::info!("Gemini MCP Server shutting down");
-    Ok(())
+        if let Some(record) = session_id.and_then(|sid| self.sessions.get(sid)) {
+            if !same_workspace(&record.cwd, &input.cd) {
+                let mismatch = GeminiError::SessionCwdMismatch {
+                    session_id: input.session_id.clone(),
+                    expected: record.cwd.to_string_lossy().to_string(),
+                    actual: input.cd.to_string_lossy().to_string(),
+                };
+                if self.config.strict_session_cwd {
+                    return Err(mismatch);
+                }
+                run.warnings.push(format!(
+                    "{}; gemini's file context may be inconsistent",
+                    mismatch
+                ));
+            }
+        }
+
+        if let (Some(sid), Some(limit)) = (session_id, self.config.session_resume_limit) {
+            let window_secs = self.config.session_resume_window_secs;
+            if !self
+                .sessions
+                .try_resume(sid, limit, Duration::from_secs(window_secs))
+            {
+                return Err(GeminiError::SessionQuotaExceeded {
+                    session_id: sid.to_string(),
+                    limit,
+                    window_secs,
+                });
+            }
+        }
+
+        let mut full_prompt = match &input.context {
+            Some(value) => Cow::Owned(context::embed_context(&input.prompt, value)),
+            None => Cow::Borrowed(input.prompt.as_str()),
+        };
+        let mut compression = None;
+        if let (Some(value), Some(max)) = (&input.context, self.config.max_prompt_bytes) {
+            if input.compress_context && full_prompt.len() > max {
+                let mut pass = self
+                    .compress_context(&input.prompt, value, &input.cd, workdir, &options)
+                    .await?;
+                let compressed = match pass.agent_messages.take() {
+                    Some(compressed) if pass.success => compressed,
+                    _ => {
+                        pass.success = false;
+                        pass.error = Some(format!(
+                            "Compressing CONTEXT failed: {}",
+                            pass.error.as_deref().unwrap_or("no reply")
+                        ));
+                        return Ok(pass);
+                    }
+                };
+                let original = serde_json::to_string_pretty(value).map_or(0, |json| json.len());
+                compression = Some((compressed.len() as f64 / original.max(1) as f64, pass.usage));
+                full_prompt = Cow::Owned(context::embed_compressed_context(
+                    &input.prompt,
+                    &compressed,
+                ));
+            }
+        }
+        let full_prompt = self.check_content_policy(full_prompt)?;
+
+        audit::log_prompt(
+            &self.config,
+            &request_id,
+            &full_prompt,
+            &input.cd,
+            session_id,
+            &input.tags,
+        );
+
+        // Only looked up now so that cached responses are subject to the same
+        // budget, quota and policy as fresh ones. Paginated transcripts are
+        // stored per request, so those are never cached
+        let cache = self.cache.as_ref().filter(|_| !input.paginate_messages);
+        if let Some(mut cached) = cache.and_then(|c| c.get(&fingerprint)) {
+            cached.cached = true;
+            return Ok(cached);
+        }
+
+        let chunks = match self.config.max_prompt_bytes {
+            Some(max) if input.chunk_prompt && full_prompt.len() > max => {
+                if input.raw_prompt {
+                    chunking::split_prompt(&full_prompt, max)
+                } else {
+                    chunking::split_framed(&full_prompt, max)
+                }
+            }
+            _ => vec![full_prompt.as_ref()],
+        };
+        let total = chunks.len();
+        let prompts: Vec<Cow<str>> = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                if total > 1 && !input.raw_prompt {
+                    Cow::Owned(chunking::frame_chunk(chunk, index, total))
+                } else {
+                    Cow::Borrowed(*chunk)
+                }
+            })
+            .collect();
+        let (prompt, leading) = prompts.split_last().expect("at least one chunk");
+
+        run.artifacts = output_dir.map(|dir| {
+            let before = Snapshot::take(&dir);
+            (dir, before)
+        });
+        if !self.config.allowed_write_extensions.is_empty() {
+            run.write_guard = Some((
+                workdir.to_path_buf(),
+                WriteGuard::take(workdir, self.config.revert_forbidden_writes),
+            ));
+        }
+        if input.return_effective_prompt {
+            run.effective_prompt =
+                Some(self.effective_prompt(&prompt_arg(prompt, input.raw_prompt)));
+        }
+        let mut chunk_usage = None;
+        let failed_chunk = self
+            .send_leading_chunks(
+                leading,
+                total,
+                &input.cd,
+                workdir,
+                &mut options,
+                &mut chunk_usage,
+            )
+            .await?;
+
+        let mut result = match failed_chunk {
+            Some(failed) => failed,
+            None => {
+                let mut result = self.run_turn(prompt, &input.cd, workdir, &options).await?;
+
+                if let Some(fallback) = fallback_model {
+                    if result.failure_kind == Some(FailureKind::FirstEventTimeout) {
+                        run.warnings.push(format!(
+                            "{} sent no event in time; fell back to {}",
+                            run.effective_model
+                                .as_deref()
+                                .unwrap_or("The default model"),
+                            fallback
+                        ));
+                        options.model = Some(fallback.clone());
+                        options.first_event_timeout = None;
+                        run.effective_model = Some(fallback);
+                        let mut usage = result.usage;
+                        result = self.run_turn(prompt, &input.cd, workdir, &options).await?;
+                        add_usage(&mut usage, &result);
+                        result.usage = usage;
+                    }
+                }
+
+                if !input.stop_phrase.is_empty() {
+                    result = self
+                        .continue_until_stop_phrase(result, input, workdir, options)
+                        .await?;
+                }
+                result
+            }
+        };
+
+        if total > 1 {
+            if let Some(u) = &chunk_usage {
+                result.usage.get_or_insert_with(Default::default).add(u);
+            }
+            result.chunks = Some(total);
+        }
+        if let Some((ratio, usage)) = compression {
+            if let Some(u) = &usage {
+                result.usage.get_or_insert_with(Default::default).add(u);
+            }
+            result.compression_ratio = Some(ratio);
+        }
+
+        let post_process = input.post_process.as_deref().filter(|_| result.success);
+        if let Some((name, command)) =
+            post_process.and_then(|name| Some((name, self.config.post_processor(name)?)))
+        {
+            post_process::apply(name, command, workdir, &mut result).await;
+        }
+
+        Ok(result)
+    }
+
+    /// Apply the post-run steps every request goes through, whether it
+    /// succeeded, failed, timed out, was cancelled or stopped before gemini ran.
+    fn finalize(
+        &self,
+        input: &GeminiToolInput,
+        run: RequestState,
+        outcome: Result<GeminiResult, GeminiError>,
+    ) -> GeminiResult {
+        let mut result = outcome.unwrap_or_else(|e| GeminiResult::from_error(&e));
+        result.request_id = Some(run.request_id.clone());
+        result.session = result
+            .session_id
+            .as_deref()
+            .and_then(|sid| self.sessions.handle(sid));
+        if result.cached {
+            return result;
+        }
+
+        if let Some((dir, before)) = &run.artifacts {
+            result.artifacts = before.changed(dir);
+        }
+        if let Some((workdir, guard)) = &run.write_guard {
+            let forbidden = guard.forbidden(workdir, &self.config.allowed_write_extensions);
+            if !forbidden.is_empty() {
+                if self.config.revert_forbidden_writes {
+                    result.reverted_writes = guard.revert(workdir, &forbidden);
+                }
+                let message = format!(
+                    "gemini wrote files without an allowed extension ({}): {}",
+                    self.config.allowed_write_extensions.join(", "),
+                    forbidden
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                result.success = false;
+                result.failure_kind = Some(FailureKind::ForbiddenWrite);
+                result.error = Some(match result.error.take() {
+                    Some(error) => format!("{}\n\n{}", message, error),
+                    None => message,
+                });
+                result.forbidden_writes = forbidden;
+            }
+        }
+        result.effective_prompt = run.effective_prompt;
+        result.sandbox_decision = run.sandbox_decision;
+
+        if let Some(min) = input.min_output_chars.filter(|_| result.success) {
+            let chars = result
+                .agent_messages
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .chars()
+                .count();
+            if chars < min {
+                result.success = false;
+                result.failure_kind = Some(FailureKind::OutputTooShort);
+                result.error = Some(format!(
+                    "gemini's reply has {} characters, fewer than the required {}",
+                    chars, min
+                ));
+            }
+        }
+
+        if input.expect_json && result.success {
+            match extract_json(result.agent_messages.as_deref().unwrap_or_default()) {
+                Ok(value) => result.result_json = Some(value),
+                Err(e) => {
+                    result.success = false;
+                    result.failure_kind = Some(FailureKind::InvalidJson);
+                    result.error = Some(format!("gemini's reply is not valid JSON: {}", e));
+                }
+            }
+        }
+
+        let current = result.session_id.as_deref();
+        if !input.session_id.is_empty() && current.is_some_and(|sid| sid != input.session_id) {
+            result.original_session_id = Some(input.session_id.clone());
+            result.current_session_id = result.session_id.clone();
+            result.warnings.push(if result.success {
+                "gemini continued the session under a new id; resume `current_session_id` from now on".to_string()
+            } else {
+                "gemini started a new session id before failing; resume `original_session_id` to retry the turn from the last complete state, or `current_session_id` to build on the partial turn".to_string()
+            });
+        }
+
+        if input.paginate_messages {
+            let messages = result.all_messages.take().unwrap_or_default();
+            self.transcripts.insert(run.request_id, messages);
+        }
+
+        if let Some(usage) = &result.usage {
+            let cost = self.spend.record(run.effective_model.as_deref(), usage);
+            result.cost = (cost > 0.0).then_some(cost);
+        }
+        result.effective_model = run.effective_model;
+        result.binary = input.binary.clone();
+        result.seed = run.seed;
+        result.warnings.extend(run.warnings);
+        result.request_fingerprint = run.fingerprint.clone();
+        let cache = self.cache.as_ref().filter(|_| !input.paginate_messages);
+        if let (Some(cache), Some(fingerprint)) = (cache, run.fingerprint) {
+            if result.success {
+                cache.insert(fingerprint, result.clone());
+            }
+        }
+        result
+    }
+
+    /// The configured prompt passing, with `Auto` resolved from the probed
+    /// capabilities of `binary`; `--prompt` until they are known.
+    fn prompt_passing(&self, binary: Option<&str>) -> PromptPassing {
+        match self.config.prompt_passing {
+            PromptPassing::Auto => self
+                .known_capabilities(binary)
+                .map_or(PromptPassing::Flag, Capabilities::preferred_prompt_passing),
+            mode => mode,
+        }
+    }
+
+    /// `prompt` redacted with the audit patterns and cut to
+    /// [`MAX_EFFECTIVE_PROMPT_BYTES`].
+    fn effective_prompt(&self, prompt: &str) -> String {
+        let redacted = audit::redact(prompt, &self.config.redact_patterns);
+        if redacted.len() <= MAX_EFFECTIVE_PROMPT_BYTES {
+            return redacted.into_owned();
+        }
+        let end = floor_char_boundary(&redacted, MAX_EFFECTIVE_PROMPT_BYTES);
+        format!(
+            "{}... [{} more bytes]",
+            &redacted[..end],
+            redacted.len() - end
+        )
+    }
+
+    /// `result` as the JSON text of the tool result, with `_debug` describing
+    /// the envelope first when `debug_envelope` is set. `structured` is
+    /// whether the result also goes out as structured content.
+    fn envelope_json(&self, result: &mut GeminiResult, pretty: bool, structured: bool) -> String {
+        let json_str = result_json(result, pretty);
+        if !self.config.debug_envelope {
+            return json_str;
+        }
+        result.debug = Some(DebugEnvelope {
+            content: vec![ContentBlockInfo {
+                kind: "text".to_string(),
+                bytes: json_str.len(),
+            }],
+            structured_content_bytes: structured
+                .then(|| serde_json::to_vec(&*result).map_or(0, |v| v.len())),
+            is_error: false,
+        });
+        result_json(result, pretty)
+    }
+
+    /// Keep the response under `max_payload_bytes` by moving `all_messages`,
+    /// then the tail of `agent_messages`, into the transcript store, from where
+    /// `gemini_messages_page` returns them. A `structured` result is sent
+    /// twice, as text and as structured content, so both copies count.
+    fn cap_payload(&self, result: &mut GeminiResult, pretty: bool, structured: bool) {
+        let Some(max) = self.config.max_payload_bytes else {
+            return;
+        };
+        let copies = if structured { 2 } else { 1 };
+        let size = |result: &GeminiResult| {
+            let text = to_json(result, pretty).map_or(0, |json| json.len());
+            let structured = if structured {
+                serde_json::to_vec(result).map_or(0, |json| json.len())
+            } else {
+                0
+            };
+            text + structured
+        };
+        if size(result) <= max {
+            return;
+        }
+        let Some(request_id) = result.request_id.clone() else {
+            return;
+        };
+
+        let mut overflow = self.transcripts.take(&request_id).unwrap_or_default();
+        overflow.extend(result.all_messages.take().unwrap_or_default());
+        result.continuation_token = Some(request_id.clone());
+        result.warnings.push(format!(
+            "Response exceeded {} bytes; fetch the rest with gemini_messages_page",
+            max
+        ));
+
+        if let Some(text) = result.agent_messages.take() {
+            result.agent_messages = Some(String::new());
+            let budget = max.saturating_sub(size(result)) / copies;
+            let mut keep = floor_char_boundary(&text, budget);
+            // JSON escaping can make the text longer than its byte count
+            loop {
+                result.agent_messages = Some(text[..keep].to_string());
+                let excess = size(result).saturating_sub(max);
+                if excess == 0 || keep == 0 {
+                    break;
+                }
+                keep = floor_char_boundary(&text, keep.saturating_sub(excess.div_ceil(copies)));
+            }
+
+            let piece_bytes = (max / 2).max(1);
+            let mut rest = &text[keep..];
+            while !rest.is_empty() {
+                let mut end = floor_char_boundary(rest, piece_bytes);
+                if end == 0 {
+                    end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+                }
+                overflow.push(serde_json::json!({
+                    "type": "agent_messages",
+                    "content": &rest[..end],
+                }));
+                rest = &rest[end..];
+            }
+        }
+
+        self.transcripts.insert(request_id, overflow);
+    }
+
+    /// `prompt` as the content policy lets it through, if there is one.
+    fn check_content_policy<'p>(&self, prompt: Cow<'p, str>) -> Result<Cow<'p, str>, GeminiError> {
+        let Some(policy) = &self.content_policy else {
+            return Ok(prompt);
+        };
+        match policy.check(&prompt) {
+            PolicyDecision::Allow => Ok(prompt),
+            PolicyDecision::Deny { reason } => Err(GeminiError::PolicyDenied(reason)),
+            PolicyDecision::Transform(prompt) => Ok(Cow::Owned(prompt)),
+        }
+    }
+
+    /// Have gemini compress `context` for `prompt` in a turn of its own, in a
+    /// new session whatever the request resumes.
+    async fn compress_context(
+        &self,
+        prompt: &str,
+        context: &serde_json::Value,
+        workspace: &Path,
+        cwd: &Path,
+        options: &GeminiOptions,
+    ) -> Result<GeminiResult, GeminiError> {
+        let template = self
+            .config
+            .compression_prompt
+            .as_deref()
+            .unwrap_or(context::DEFAULT_COMPRESSION_PROMPT);
+        let pass_prompt = self.check_content_policy(Cow::Owned(context::compression_prompt(
+            template,
+            prompt,
+            context,
+            self.config.compression_target_bytes,
+        )))?;
+        // Runs under the caller's restrictions, but in a fresh session and
+        // without streaming or shaping its reply for the caller
+        let pass_options = GeminiOptions {
+            session_id: None,
+            return_all_messages: false,
+            event_summary: false,
+            structured: false,
+            content_roles: Vec::new(),
+            prefix_roles: false,
+            message_join: String::new(),
+            max_assistant_messages: None,
+            stop_sequences: Vec::new(),
+            sinks: Sinks::default(),
+            event_sink: None,
+            status_sink: None,
+            stderr_sink: None,
+            event_seq: EventSeq::default(),
+            ..options.clone()
+        };
+        self.run_turn(&pass_prompt, workspace, cwd, &pass_options)
+            .await
+    }
+
+    /// Send the `leading` parts of a prompt split in `total`, each resuming the
+    /// session the first one started. Returns the result of a part that failed.
+    async fn send_leading_chunks(
+        &self,
+        leading: &[Cow<'_, str>],
+        total: usize,
+        workspace: &Path,
+        cwd: &Path,
+        options: &mut GeminiOptions,
+        usage: &mut Option<TokenUsage>,
+    ) -> Result<Option<GeminiResult>, GeminiError> {
+        for (index, prompt) in leading.iter().enumerate() {
+            let mut part = self.run_turn(prompt, workspace, cwd, options).await?;
+            add_usage(usage, &part);
+
+            match part.session_id.clone() {
+                Some(sid) if part.success => options.session_id = Some(sid),
+                _ => {
+                    part.success = false;
+                    part.error = Some(format!(
+                        "Part {} of {} of the prompt failed: {}",
+                        index + 1,
+                        total,
+                        part.error.as_deref().unwrap_or("no session id")
+                    ));
+                    part.chunks = Some(total);
+                    return Ok(Some(part));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run a single gemini turn in `cwd` and record it in the session store
+    /// against `workspace`, which `cwd` is a scratch copy of or equals.
+    ///
+    /// Only failures that happened before gemini created a session are
+    /// retried; anything later may already have had side effects.
+    async fn run_turn(
+        &self,
+        prompt: &str,
+        workspace: &Path,
+        cwd: &Path,
+        options: &GeminiOptions,
+    ) -> Result<GeminiResult, GeminiError> {
+        let retry_policy = &self.config.retry;
+        let mut retry = 0;
+        let mut key_retries = 0;
+        let mut options = Cow::Borrowed(options);
+        // Attempts that are retried still used tokens
+        let mut retried_usage: Option<TokenUsage> = None;
+        let mut result = loop {
+            if let Some(keys) = &self.keys {
+                options.to_mut().api_key = Some(keys.pick());
+            }
+            let result = execute_gemini(prompt, cwd, &options).await?;
+
+            // Move on to another key when this one is out of quota
+            if result.failure_kind == Some(FailureKind::QuotaExceeded) {
+                if let (Some(keys), Some(key)) = (&self.keys, &options.api_key) {
+                    keys.cool_down(key);
+                    if key_retries + 1 < keys.len() && keys.available() > 0 {
+                        key_retries += 1;
+                        add_usage(&mut retried_usage, &result);
+                        tracing::warn!(
+                            "gemini API key hit its quota, retrying with another key ({}/{})",
+                            key_retries,
+                            keys.len() - 1
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            if result.success
+                || result.session_id.is_some()
+                || matches!(
+                    result.failure_kind,
+                    Some(
+                        FailureKind::Cancelled
+                            | FailureKind::FirstEventTimeout
+                            | FailureKind::ReauthRequired
+                    )
+                )
+                || retry >= retry_policy.attempts
+            {
+                break result;
+            }
+
+            add_usage(&mut retried_usage, &result);
+            let delay = retry_policy.delay(retry);
+            retry += 1;
+            tracing::warn!(
+                "gemini failed before starting a session, retrying in {:?} ({}/{})",
+                delay,
+                retry,
+                retry_policy.attempts
+            );
+            tokio::time::sleep(delay).await;
+        };
+        if let Some(usage) = &retried_usage {
+            result.usage.get_or_insert_with(Default::default).add(usage);
+        }
+
+        if let Some(sid) = &result.session_id {
+            self.sessions
+                .record_turn(sid, workspace, options.model.as_deref());
+        }
+
+        Ok(result)
+    }
+
+    /// Keep resuming the session with a continuation prompt until the stop
+    /// phrase appears in the output or `max_turns` is reached.
+    async fn continue_until_stop_phrase(
+        &self,
+        first: GeminiResult,
+        input: &GeminiToolInput,
+        workdir: &Path,
+        mut options: GeminiOptions,
+    ) -> Result<GeminiResult, GeminiError> {
+        let max_turns = input.max_turns.unwrap_or(DEFAULT_MAX_TURNS).max(1);
+        let continuation = format!(
+            "Continue with the task. Reply with \"{}\" once it is fully complete.",
+            input.stop_phrase
+        );
+
+        let mut agent_messages = first.agent_messages.clone().unwrap_or_default();
+        let mut all_messages = first.all_messages.clone();
+        let mut usage = first.usage;
+        let mut result = first;
+        let mut turns = 1;
+        let mut stopped = agent_messages.contains(&input.stop_phrase);
+
+        while !stopped && result.success && !result.stopped_early && turns < max_turns {
+            let Some(sid) = result.session_id.clone() else {
+                break;
+            };
+            if let Some(limit) = self.config.session_resume_limit {
+                let window_secs = self.config.session_resume_window_secs;
+                if !self
+                    .sessions
+                    .try_resume(&sid, limit, Duration::from_secs(window_secs))
+                {
+                    return Err(GeminiError::SessionQuotaExceeded {
+                        session_id: sid,
+                        limit,
+                        window_secs,
+                    });
+                }
+            }
+            options.session_id = Some(sid);
+
+            result = self
+                .run_turn(&continuation, &input.cd, workdir, &options)
+                .await?;
+            turns += 1;
+
+            if let Some(text) = &result.agent_messages {
+                stopped = text.contains(&input.stop_phrase);
+                if !agent_messages.is_empty() {
+                    agent_messages.push_str("\n\n");
+                }
+                agent_messages.push_str(text);
+            }
+            if let (Some(all), Some(more)) = (&mut all_messages, result.all_messages.take()) {
+                all.extend(more);
+            }
+            add_usage(&mut usage, &result);
+        }
+
+        if !stopped && result.success {
+            result.warnings.push(format!(
+                "Stop phrase \"{}\" not seen after {} turns",
+                input.stop_phrase, turns
+            ));
+        }
+
+        result.agent_messages = (!agent_messages.is_empty()).then_some(agent_messages);
+        result.all_messages = all_messages;
+        result.usage = usage;
+        result.turns = Some(turns);
+        Ok(result)
+    }
+}
+
+impl Default for GeminiServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[tool_handler]
+impl rmcp::ServerHandler for GeminiServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            instructions: Some(
+                "Gemini MCP Server - Wraps Gemini CLI as a standard MCP protocol interface".into(),
+            ),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_logging()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .enable_resources_list_changed()
+                .build(),
+            ..Default::default()
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let resources = self
+            .live
+            .request_ids()
+            .into_iter()
+            .map(|request_id| {
+                let mut resource =
+                    RawResource::new(live_result_uri(&request_id), format!("gemini {request_id}"));
+                resource.description = Some("Accumulating result of a gemini call".to_string());
+                resource.mime_type = Some("application/json".to_string());
+                resource.no_annotation()
+            })
+            .collect();
+        Ok(ListResourcesResult {
+            resources,
+            ..Default::default()
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        let live = self.live.get(&request.uri).ok_or_else(|| {
+            McpError::resource_not_found(
+                format!("Unknown or expired result: {}", request.uri),
+                None,
+            )
+        })?;
+        let json_str = serde_json::to_string(&live)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: request.uri,
+                mime_type: Some("application/json".to_string()),
+                text: json_str,
+                meta: None,
+            }],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.live.subscribe(&request.uri);
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.live.unsubscribe(&request.uri);
+        Ok(())
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        *self.log_level.lock().unwrap_or_else(|e| e.into_inner()) = request.level;
+        Ok(())
+    }
+}
+
+/// Check that the gemini executable is available before serving.
+///
+/// A missing executable is fatal when `require_gemini` is set and only
+/// logged as a warning otherwise.
+pub fn preflight(config: &ServerConfig) -> anyhow::Result<()> {
+    match find_gemini_executable() {
+        Ok(path) => {
+            tracing::info!("Found gemini executable at {}", path);
+            Ok(())
+        }
+        Err(e) if config.require_gemini => Err(e.into()),
+        Err(_) => {
+            tracing::warn!(
+                "gemini executable not found in PATH; every tool call will fail until it is installed"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Create and run the MCP server over stdio transport.
+pub async fn run_server(config: ServerConfig) -> anyhow::Result<()> {
+    tracing::info!("Starting Gemini MCP Server...");
+
+    preflight(&config)?;
+
+    let server = GeminiServer::with_config(config);
+    if server.config.prompt_passing == PromptPassing::Auto {
+        // Learn early whether each gemini still wants `--prompt`
+        let probing = server.clone();
+        tokio::spawn(async move {
+            let binaries = probing.config.binaries.iter().map(|b| Some(b.name.as_str()));
+            for binary in std::iter::once(None).chain(binaries) {
+                if let Err(e) = probing.capabilities_of(binary).await {
+                    tracing::warn!(
+                        "Could not probe the capabilities of gemini {}: {}",
+                        binary.unwrap_or("from PATH"),
+                        e
+                    );
+                }
+            }
+        });
+    }
+    server.run(rmcp::transport::stdio()).await
 }
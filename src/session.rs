@@ -0,0 +1,178 @@
+//! In-memory bookkeeping for gemini sessions created through this server.
+
+use crate::server::GeminiToolInput;
+use rmcp::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Number of sessions tracked before the least recently used one is evicted.
+pub const DEFAULT_SESSION_CAPACITY: usize = 1024;
+
+/// What the server knows about a gemini session.
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    /// Working directory the session was created in.
+    pub cwd: PathBuf,
+    /// Model the session was created with, if one was requested.
+    pub model: Option<String>,
+    pub created_at: SystemTime,
+    pub last_used: SystemTime,
+    /// Number of completed turns, including the first one.
+    pub turns: u32,
+    /// When the session was resumed by a caller, oldest first.
+    pub resumes: VecDeque<Instant>,
+}
+
+/// A session as returned to callers, carrying what resuming it needs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionHandle {
+    pub id: String,
+    /// Model the session was created with, if one was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Working directory the session was created in.
+    pub cwd: PathBuf,
+    /// Creation time in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Number of completed turns so far.
+    pub turns: u32,
+}
+
+impl SessionHandle {
+    /// Input for the next turn of the session: `prompt` in the session's
+    /// directory, with its model.
+    pub fn resume(&self, prompt: impl Into<String>) -> GeminiToolInput {
+        let mut input: GeminiToolInput = serde_json::from_value(serde_json::json!({
+            "PROMPT": prompt.into(),
+            "cd": self.cwd,
+        }))
+        .expect("PROMPT and cd are the only required fields");
+        input.session_id = self.id.clone();
+        input.model = self.model.clone().unwrap_or_default();
+        input
+    }
+}
+
+impl SessionRecord {
+    /// The handle of this record's session `id`.
+    pub fn handle(&self, id: &str) -> SessionHandle {
+        SessionHandle {
+            id: id.to_string(),
+            model: self.model.clone(),
+            cwd: self.cwd.clone(),
+            created_at: self
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |age| age.as_secs()),
+            turns: self.turns,
+        }
+    }
+}
+
+/// Bounded store of sessions keyed by session id.
+#[derive(Debug)]
+pub struct SessionStore {
+    capacity: usize,
+    sessions: Mutex<HashMap<String, SessionRecord>>,
+}
+
+impl SessionStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Look up a session by id.
+    pub fn get(&self, session_id: &str) -> Option<SessionRecord> {
+        self.lock().get(session_id).cloned()
+    }
+
+    /// The handle of a tracked session.
+    pub fn handle(&self, session_id: &str) -> Option<SessionHandle> {
+        self.lock()
+            .get(session_id)
+            .map(|record| record.handle(session_id))
+    }
+
+    /// Record a completed turn, creating the session on first use.
+    pub fn record_turn(&self, session_id: &str, cwd: &Path, model: Option<&str>) {
+        let now = SystemTime::now();
+        let mut sessions = self.lock();
+
+        if let Some(record) = sessions.get_mut(session_id) {
+            record.turns += 1;
+            record.last_used = now;
+            return;
+        }
+
+        if sessions.len() >= self.capacity {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, r)| r.last_used)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = oldest {
+                sessions.remove(&id);
+            }
+        }
+
+        sessions.insert(
+            session_id.to_string(),
+            SessionRecord {
+                cwd: cwd.to_path_buf(),
+                model: model.map(str::to_string),
+                created_at: now,
+                last_used: now,
+                turns: 1,
+                resumes: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Count a resume of the session against a quota of `limit` resumes per
+    /// `window`. Returns `false`, without counting it, once the quota is used up.
+    pub fn try_resume(&self, session_id: &str, limit: u32, window: Duration) -> bool {
+        let now = Instant::now();
+        let mut sessions = self.lock();
+        let Some(record) = sessions.get_mut(session_id) else {
+            // Sessions created outside this server are not tracked.
+            return true;
+        };
+
+        while record
+            .resumes
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= window)
+        {
+            record.resumes.pop_front();
+        }
+
+        if record.resumes.len() >= limit as usize {
+            return false;
+        }
+        record.resumes.push_back(now);
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SessionRecord>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_SESSION_CAPACITY)
+    }
+}
+
+/// Whether two workspace paths refer to the same directory.
+pub fn same_workspace(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
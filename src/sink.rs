@@ -0,0 +1,157 @@
+//! Consumers of gemini's raw output, fed every line in a single pass.
+//!
+//! The read loop hands each non-empty output line to every sink in
+//! [`Sinks`] before handling it, so the stream can be streamed to the client,
+//! appended to a log file and buffered at the same time without being read
+//! twice. Lines holding an event come with the event's sequence number in the
+//! request, the `_seq` of its `all_messages` entry.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Receives raw gemini output lines, in order.
+pub trait LineSink: Send + Sync {
+    /// Handle one line, without its trailing newline.
+    fn line(&self, line: &str);
+
+    /// Handle one line along with its event sequence number, `None` for a
+    /// line that is not an event. The number is dropped by default.
+    fn sequenced_line(&self, line: &str, seq: Option<u64>) {
+        let _ = seq;
+        self.line(line)
+    }
+}
+
+impl<T: LineSink + ?Sized> LineSink for Arc<T> {
+    fn line(&self, line: &str) {
+        (**self).line(line)
+    }
+
+    fn sequenced_line(&self, line: &str, seq: Option<u64>) {
+        (**self).sequenced_line(line, seq)
+    }
+}
+
+/// Forwards lines to a channel; lines sent after the receiver is gone are dropped.
+impl LineSink for mpsc::UnboundedSender<String> {
+    fn line(&self, line: &str) {
+        let _ = self.send(line.to_string());
+    }
+}
+
+/// Forwards lines to a channel with `"_seq"` injected into event lines, so
+/// they can be matched with the `all_messages` entries of the result.
+#[derive(Debug, Clone)]
+pub struct SequencedSender(pub mpsc::UnboundedSender<String>);
+
+impl LineSink for SequencedSender {
+    fn line(&self, line: &str) {
+        self.0.line(line)
+    }
+
+    fn sequenced_line(&self, line: &str, seq: Option<u64>) {
+        match seq {
+            Some(seq) => self.0.line(&with_seq(line, seq)),
+            None => self.0.line(line),
+        }
+    }
+}
+
+/// The JSON object `line` with `"_seq": seq` as its first member, the rest of
+/// the line left as gemini wrote it.
+pub fn with_seq(line: &str, seq: u64) -> String {
+    match line.strip_prefix('{') {
+        Some(rest) if rest.trim_start().starts_with('}') => format!("{{\"_seq\":{}{}", seq, rest),
+        Some(rest) => format!("{{\"_seq\":{},{}", seq, rest),
+        None => line.to_string(),
+    }
+}
+
+/// Calls a function for every line.
+pub struct Callback<F>(pub F);
+
+impl<F: Fn(&str) + Send + Sync> LineSink for Callback<F> {
+    fn line(&self, line: &str) {
+        (self.0)(line)
+    }
+}
+
+/// Keeps every line in memory.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    lines: Mutex<Vec<String>>,
+}
+
+impl LineBuffer {
+    /// The lines received so far.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl LineSink for LineBuffer {
+    fn line(&self, line: &str) {
+        self.lines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(line.to_string());
+    }
+}
+
+/// Appends lines to a file.
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it if needed.
+    pub fn append(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl LineSink for FileSink {
+    fn line(&self, line: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            tracing::warn!("Failed to append gemini output to log file: {}", e);
+        }
+    }
+}
+
+/// The sinks an invocation fans its output out to.
+#[derive(Clone, Default)]
+pub struct Sinks(Vec<Arc<dyn LineSink>>);
+
+impl Sinks {
+    /// Add a sink.
+    pub fn push(&mut self, sink: impl LineSink + 'static) {
+        self.0.push(Arc::new(sink));
+    }
+
+    /// Whether no sink is configured.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Hand `line`, the `seq`-th event if it is one, to every sink.
+    pub(crate) fn send(&self, line: &str, seq: Option<u64>) {
+        for sink in &self.0 {
+            sink.sequenced_line(line, seq);
+        }
+    }
+}
+
+impl fmt::Debug for Sinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sinks({})", self.0.len())
+    }
+}
@@ -0,0 +1,154 @@
+//! Tracking what gemini's token usage costs, and capping it per time window.
+//!
+//! Each run's tokens are priced at its model's cost per 1k tokens, `*`
+//! pricing the models without a cost of their own. Once the cost of the runs
+//! in the current window reaches the budget, new requests are refused until
+//! older runs fall out of the window.
+
+use crate::gemini::TokenUsage;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default time window the spend budget applies to.
+pub const DEFAULT_SPEND_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Cost of a model's tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelCost {
+    /// Model name, or `*` for every model without its own cost.
+    pub model: String,
+    pub per_1k_tokens: f64,
+}
+
+impl FromStr for ModelCost {
+    type Err = String;
+
+    /// Parse `MODEL=COST`, e.g. `gemini-2.5-pro=0.01`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (model, cost) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected MODEL=COST, got {:?}", s))?;
+        let model = model.trim();
+        if model.is_empty() {
+            return Err(format!("missing MODEL in {:?}", s));
+        }
+        let per_1k_tokens: f64 = cost
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid COST {:?}: {}", cost, e))?;
+        if !per_1k_tokens.is_finite() || per_1k_tokens < 0.0 {
+            return Err(format!("COST must not be negative, got {:?}", cost));
+        }
+        Ok(Self {
+            model: model.to_string(),
+            per_1k_tokens,
+        })
+    }
+}
+
+/// Spend as reported by `gemini_metrics`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendReport {
+    /// Cost of every run since the server started.
+    pub total: f64,
+    /// Cost of the runs in the current window.
+    pub window: f64,
+    pub window_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<f64>,
+    /// What is left of the budget in the current window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct SpendState {
+    total: f64,
+    /// Cost of each priced run in the window, oldest first.
+    recent: VecDeque<(Instant, f64)>,
+}
+
+/// Running cost of the server's gemini runs.
+#[derive(Debug)]
+pub struct SpendTracker {
+    costs: Vec<ModelCost>,
+    budget: Option<f64>,
+    window: Duration,
+    state: Mutex<SpendState>,
+}
+
+impl SpendTracker {
+    pub fn new(costs: Vec<ModelCost>, budget: Option<f64>, window: Duration) -> Self {
+        Self {
+            costs,
+            budget,
+            window,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Price `usage` for `model` (`None` for gemini's default) and add it to
+    /// the spend, returning its cost.
+    pub fn record(&self, model: Option<&str>, usage: &TokenUsage) -> f64 {
+        let tokens = if usage.total_tokens > 0 {
+            usage.total_tokens
+        } else {
+            usage.input_tokens + usage.output_tokens
+        };
+        let cost = self.cost_per_1k(model) * tokens as f64 / 1000.0;
+        if cost > 0.0 {
+            let mut state = self.lock();
+            state.total += cost;
+            state.recent.push_back((Instant::now(), cost));
+        }
+        cost
+    }
+
+    /// Whether the spend in the current window has reached the budget, and
+    /// if so, how much it is.
+    pub fn exceeded(&self) -> Option<f64> {
+        let budget = self.budget?;
+        let spent = self.window_spend(&mut self.lock());
+        (spent >= budget).then_some(spent)
+    }
+
+    pub fn report(&self) -> SpendReport {
+        let mut state = self.lock();
+        let window = self.window_spend(&mut state);
+        SpendReport {
+            total: state.total,
+            window,
+            window_secs: self.window.as_secs(),
+            budget: self.budget,
+            remaining: self.budget.map(|budget| (budget - window).max(0.0)),
+        }
+    }
+
+    fn cost_per_1k(&self, model: Option<&str>) -> f64 {
+        let cost = |name: &str| self.costs.iter().find(|c| c.model == name);
+        model
+            .and_then(cost)
+            .or_else(|| cost("*"))
+            .map_or(0.0, |c| c.per_1k_tokens)
+    }
+
+    /// Drop the runs that left the window and sum the rest.
+    fn window_spend(&self, state: &mut SpendState) -> f64 {
+        let now = Instant::now();
+        while state
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= self.window)
+        {
+            state.recent.pop_front();
+        }
+        state.recent.iter().map(|(_, cost)| cost).sum()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SpendState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
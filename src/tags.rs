@@ -0,0 +1,78 @@
+//! Grouping invocations by the caller's `TAGS`.
+//!
+//! Every tag is counted on its own as `key=value`, so a call tagged with a
+//! team and a feature shows up under both.
+
+use crate::gemini::GeminiResult;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// `tags` as `key=value` pairs joined by commas, in key order.
+pub fn format_tags(tags: &BTreeMap<String, String>) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Calls made with one tag, as reported by `gemini_metrics`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TagReport {
+    pub calls: u64,
+    pub failures: u64,
+    /// Mean wall-clock time of the calls.
+    pub mean_duration_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct TagStats {
+    calls: u64,
+    failures: u64,
+    total_duration: Duration,
+}
+
+/// Call counts and latency per tag.
+#[derive(Debug, Default)]
+pub struct TagMetrics {
+    stats: Mutex<HashMap<String, TagStats>>,
+}
+
+impl TagMetrics {
+    /// Count a call tagged with `tags` that produced `result` in `elapsed`.
+    pub fn record(
+        &self,
+        tags: &BTreeMap<String, String>,
+        result: &GeminiResult,
+        elapsed: Duration,
+    ) {
+        if tags.is_empty() {
+            return;
+        }
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, value) in tags {
+            let entry = stats.entry(format!("{}={}", key, value)).or_default();
+            entry.calls += 1;
+            entry.failures += u64::from(!result.success);
+            entry.total_duration += elapsed;
+        }
+    }
+
+    /// Every tag seen so far, keyed `key=value`.
+    pub fn report(&self) -> BTreeMap<String, TagReport> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats
+            .iter()
+            .map(|(tag, stats)| {
+                let mean = stats.total_duration.as_millis() / u128::from(stats.calls.max(1));
+                let report = TagReport {
+                    calls: stats.calls,
+                    failures: stats.failures,
+                    mean_duration_ms: u64::try_from(mean).unwrap_or(u64::MAX),
+                };
+                (tag.clone(), report)
+            })
+            .collect()
+    }
+}
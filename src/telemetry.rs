@@ -0,0 +1,146 @@
+//! Tracing spans for gemini invocations, optionally exported via OpenTelemetry.
+//!
+//! A `gemini.invoke` span is always created; with the `otel` cargo feature it
+//! is exported over OTLP and parented to the W3C trace context (`traceparent` /
+//! `tracestate`) found in the MCP request's `_meta`.
+
+use crate::gemini::GeminiResult;
+use crate::tags::format_tags;
+use rmcp::model::Meta;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Name of the span created for each `gemini` tool call.
+pub const INVOCATION_SPAN: &str = "gemini.invoke";
+
+/// Create the span for a `gemini` call, continuing the caller's trace if any.
+pub fn invocation_span(meta: &Meta) -> Span {
+    let span = tracing::info_span!(
+        INVOCATION_SPAN,
+        gen_ai.system = "gemini",
+        gen_ai.request.model = Empty,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        gen_ai.usage.total_tokens = Empty,
+        success = Empty,
+        failure_kind = Empty,
+        duration_ms = Empty,
+        tags = Empty,
+        otel.status_code = Empty,
+    );
+
+    #[cfg(feature = "otel")]
+    otel::set_parent(&span, meta);
+    #[cfg(not(feature = "otel"))]
+    let _ = meta;
+
+    span
+}
+
+/// Record the caller's `TAGS` on the span of its call.
+pub fn record_tags(span: &Span, tags: &BTreeMap<String, String>) {
+    if !tags.is_empty() {
+        span.record("tags", format_tags(tags).as_str());
+    }
+}
+
+/// Record the outcome of a `gemini` call on its span.
+pub fn record_result(span: &Span, result: &GeminiResult, elapsed: Duration) {
+    span.record("success", result.success);
+    span.record("duration_ms", int(elapsed.as_millis()));
+    if let Some(model) = &result.effective_model {
+        span.record("gen_ai.request.model", model.as_str());
+    }
+    if let Some(usage) = &result.usage {
+        span.record("gen_ai.usage.input_tokens", int(usage.input_tokens));
+        span.record("gen_ai.usage.output_tokens", int(usage.output_tokens));
+        span.record("gen_ai.usage.total_tokens", int(usage.total_tokens));
+    }
+    if let Some(serde_json::Value::String(kind)) = result
+        .failure_kind
+        .and_then(|k| serde_json::to_value(k).ok())
+    {
+        span.record("failure_kind", kind.as_str());
+    }
+    span.record(
+        "otel.status_code",
+        if result.success { "OK" } else { "ERROR" },
+    );
+}
+
+/// Counters are recorded as `i64`: OpenTelemetry has no unsigned integer
+/// attributes and `u64` values would be exported as strings.
+fn int(value: impl TryInto<i64>) -> i64 {
+    value.try_into().unwrap_or(i64::MAX)
+}
+
+#[cfg(feature = "otel")]
+pub use otel::{init_tracer_provider, layer};
+
+#[cfg(feature = "otel")]
+mod otel {
+    use opentelemetry::propagation::Extractor;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use rmcp::model::Meta;
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    /// Reads W3C trace context headers from MCP `_meta`.
+    struct MetaExtractor<'a>(&'a Meta);
+
+    impl Extractor for MetaExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0 .0.get(key)?.as_str()
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0 .0.keys().map(String::as_str).collect()
+        }
+    }
+
+    pub(super) fn set_parent(span: &Span, meta: &Meta) {
+        let cx = opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&MetaExtractor(meta))
+        });
+        if let Err(e) = span.set_parent(cx) {
+            tracing::debug!("Failed to continue the caller's trace: {}", e);
+        }
+    }
+
+    /// Build a tracer provider exporting over OTLP/HTTP and install the W3C
+    /// trace context propagator.
+    ///
+    /// The endpoint is taken from the standard `OTEL_EXPORTER_OTLP_*`
+    /// environment variables.
+    pub fn init_tracer_provider() -> anyhow::Result<SdkTracerProvider> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build();
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        Ok(provider)
+    }
+
+    /// A tracing layer sending spans to the given provider.
+    pub fn layer<S>(
+        provider: &SdkTracerProvider,
+    ) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::SdkTracer>
+    where
+        S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    }
+}
@@ -0,0 +1,79 @@
+//! Server-side storage of full event transcripts for paginated retrieval.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Number of transcripts kept before the oldest one is evicted.
+pub const DEFAULT_TRANSCRIPT_CAPACITY: usize = 32;
+
+/// Default number of events returned per page.
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+
+/// A slice of a stored transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagesPage {
+    pub request_id: String,
+    pub offset: usize,
+    pub total: usize,
+    pub messages: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// Bounded, in-memory store of transcripts keyed by request id.
+#[derive(Debug)]
+pub struct TranscriptStore {
+    capacity: usize,
+    entries: Mutex<VecDeque<(String, Vec<serde_json::Value>)>>,
+}
+
+impl TranscriptStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Store a transcript, evicting the oldest one when the store is full.
+    pub fn insert(&self, request_id: String, messages: Vec<serde_json::Value>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(id, _)| *id != request_id);
+        entries.push_back((request_id, messages));
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+
+    /// Remove and return a stored transcript.
+    pub fn take(&self, request_id: &str) -> Option<Vec<serde_json::Value>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let index = entries.iter().position(|(id, _)| id == request_id)?;
+        entries.remove(index).map(|(_, messages)| messages)
+    }
+
+    /// Return up to `limit` events starting at `offset`, or `None` for an unknown id.
+    pub fn page(&self, request_id: &str, offset: usize, limit: usize) -> Option<MessagesPage> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (_, messages) = entries.iter().find(|(id, _)| id == request_id)?;
+
+        let total = messages.len();
+        let start = offset.min(total);
+        let end = start.saturating_add(limit.max(1)).min(total);
+
+        Some(MessagesPage {
+            request_id: request_id.to_string(),
+            offset: start,
+            total,
+            messages: messages[start..end].to_vec(),
+            next_offset: (end < total).then_some(end),
+        })
+    }
+}
+
+impl Default for TranscriptStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRANSCRIPT_CAPACITY)
+    }
+}
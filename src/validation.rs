@@ -0,0 +1,372 @@
+//! Validation of gemini tool inputs, shared by the `gemini` and `gemini_validate` tools.
+
+use crate::config::ServerConfig;
+use crate::context::embed_context;
+use crate::error::GeminiError;
+use crate::limits::MEMORY_LIMIT_SUPPORTED;
+use crate::server::GeminiToolInput;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+/// Whether `path`, relative to `cd`, names a file inside `cd`, also after
+/// following the symlinks among its existing parents.
+fn stays_under_cd(cd: &Path, path: &Path) -> bool {
+    let lexical = path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !lexical {
+        return false;
+    }
+    let Some(parent) = cd.join(path).parent().map(Path::to_path_buf) else {
+        return false;
+    };
+    match (parent.canonicalize(), cd.canonicalize()) {
+        (Ok(parent), Ok(cd)) => parent.starts_with(cd),
+        // Not created yet; FileSink fails on a missing directory anyway
+        _ => true,
+    }
+}
+
+/// How serious a validation issue is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The input would be rejected.
+    Error,
+    /// The input is accepted but likely not what the caller intended.
+    Warning,
+}
+
+/// A single problem found in a tool input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// All issues found in a tool input.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub(crate) fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.push(field, Severity::Error, message);
+    }
+
+    pub(crate) fn warning(&mut self, field: &str, message: impl Into<String>) {
+        self.push(field, Severity::Warning, message);
+    }
+
+    fn push(&mut self, field: &str, severity: Severity, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            field: field.to_string(),
+            severity,
+            message: message.into(),
+        });
+        self.valid = !self.has_errors();
+    }
+
+    /// Whether any issue is an error.
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    /// Join the error messages into a single human-readable string.
+    pub fn error_summary(&self) -> String {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .map(|i| i.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Validate a tool input without touching the gemini executable.
+pub fn validate_input(input: &GeminiToolInput, config: &ServerConfig) -> ValidationReport {
+    let mut report = ValidationReport {
+        valid: true,
+        issues: Vec::new(),
+    };
+
+    if input.prompt.trim().is_empty() {
+        report.error("PROMPT", "PROMPT must not be empty");
+    }
+
+    let prompt_len = match &input.context {
+        Some(value) => embed_context(&input.prompt, value).len(),
+        None => input.prompt.len(),
+    };
+    let compressible = input.compress_context && input.context.is_some();
+    match config.max_prompt_bytes {
+        Some(max) if prompt_len > max && !input.chunk_prompt && !compressible => report.error(
+            "PROMPT",
+            format!(
+                "{} is {} bytes, over the server's limit of {}; set chunk_prompt to send it in parts{}",
+                if input.context.is_some() {
+                    "PROMPT with CONTEXT"
+                } else {
+                    "PROMPT"
+                },
+                prompt_len,
+                max,
+                if input.context.is_some() {
+                    ", or compress_context to compress CONTEXT"
+                } else {
+                    ""
+                }
+            ),
+        ),
+        None if input.chunk_prompt => report.warning(
+            "chunk_prompt",
+            "chunk_prompt has no effect because the server sets no prompt size limit",
+        ),
+        None if input.compress_context => report.warning(
+            "compress_context",
+            "compress_context has no effect because the server sets no prompt size limit",
+        ),
+        _ => {}
+    }
+    if input.compress_context && input.context.is_none() {
+        report.warning(
+            "compress_context",
+            "compress_context only compresses CONTEXT, which is not set",
+        );
+    }
+
+    if !input.cd.exists() {
+        report.error(
+            "cd",
+            GeminiError::WorkspaceNotFound(input.cd.to_string_lossy().to_string()).to_string(),
+        );
+    } else if !input.cd.is_dir() {
+        report.error(
+            "cd",
+            format!(
+                "Workspace path is not a directory: {}",
+                input.cd.to_string_lossy()
+            ),
+        );
+    }
+
+    if let Some(dir) = &input.output_dir {
+        let dir = input.cd.join(dir);
+        if dir.exists() && !dir.is_dir() {
+            report.error(
+                "OUTPUT_DIR",
+                format!("OUTPUT_DIR is not a directory: {}", dir.to_string_lossy()),
+            );
+        }
+    }
+
+    if let Some(log_file) = &input.log_file {
+        if input.read_only {
+            report.error("log_file", "log_file writes to the workspace, which read_only forbids");
+        } else if !stays_under_cd(&input.cd, log_file) {
+            report.error(
+                "log_file",
+                format!(
+                    "log_file must be a relative path inside cd: {}",
+                    log_file.to_string_lossy()
+                ),
+            );
+        }
+    }
+
+    if input.raw_prompt && input.context.is_some() {
+        report.error(
+            "CONTEXT",
+            "CONTEXT is embedded in the prompt, which raw_prompt forbids",
+        );
+    }
+
+    if input.raw_prompt && config.forbid_raw_prompt {
+        report.error("raw_prompt", "raw_prompt is forbidden by the server");
+    }
+
+    if !input.subcommand.is_empty() && !config.subcommands.contains(&input.subcommand) {
+        let allowed = if config.subcommands.is_empty() {
+            "none; see --allow-subcommand".to_string()
+        } else {
+            config.subcommands.join(", ")
+        };
+        report.error(
+            "SUBCOMMAND",
+            format!(
+                "SUBCOMMAND {:?} is not allowed (allowed: {})",
+                input.subcommand, allowed
+            ),
+        );
+    }
+
+    if let Some(binary) = input.binary.as_deref() {
+        if config.binary(binary).is_none() {
+            let names: Vec<&str> = config.binaries.iter().map(|b| b.name.as_str()).collect();
+            report.error(
+                "binary",
+                format!(
+                    "binary {:?} is not configured (configured: {})",
+                    binary,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ),
+            );
+        }
+    }
+
+    if let Some(name) = input.post_process.as_deref() {
+        if config.post_processor(name).is_none() {
+            let names: Vec<&str> = config
+                .post_processors
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
+            report.error(
+                "post_process",
+                format!(
+                    "post_process {:?} is not configured (configured: {})",
+                    name,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ),
+            );
+        }
+    }
+
+    if input.session_id.trim() != input.session_id {
+        report.warning(
+            "SESSION_ID",
+            "SESSION_ID has leading or trailing whitespace",
+        );
+    }
+
+    if input
+        .request_id
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control())
+    {
+        report.error(
+            "REQUEST_ID",
+            "REQUEST_ID must not contain whitespace or control characters",
+        );
+    }
+
+    if input.model.chars().any(char::is_whitespace) {
+        report.error("model", "model must not contain whitespace");
+    }
+
+    for (field, tools) in [
+        ("ALLOWED_TOOLS", &input.allowed_tools),
+        ("DENIED_TOOLS", &input.denied_tools),
+    ] {
+        if tools.iter().any(|t| t.trim().is_empty()) {
+            report.error(
+                field,
+                format!("{} must not contain empty tool names", field),
+            );
+        }
+    }
+
+    for tool in input
+        .allowed_tools
+        .iter()
+        .filter(|t| input.denied_tools.contains(t))
+    {
+        report.error(
+            "DENIED_TOOLS",
+            format!("Tool {} is both allowed and denied", tool),
+        );
+    }
+
+    if input.content_roles.iter().any(|r| r.trim().is_empty()) {
+        report.error(
+            "content_roles",
+            "content_roles must not contain empty roles",
+        );
+    }
+
+    if input.prefix_roles && input.content_roles.len() < 2 {
+        report.warning(
+            "prefix_roles",
+            "prefix_roles has no effect unless several content_roles are collected",
+        );
+    }
+
+    if input.return_all_messages && input.paginate_messages {
+        report.warning(
+            "return_all_messages",
+            "return_all_messages is ignored when paginate_messages is set",
+        );
+    }
+
+    if input.auto_model && input.model.is_empty() && config.auto_model_for(&input.prompt).is_none()
+    {
+        report.warning(
+            "auto_model",
+            "No auto-model threshold matches this prompt; gemini's default model is used",
+        );
+    }
+
+    if input.timeout_secs == Some(0) {
+        report.error("TIMEOUT_SECS", "TIMEOUT_SECS must be greater than 0");
+    }
+
+    if input.max_assistant_messages == Some(0) {
+        report.error(
+            "max_assistant_messages",
+            "max_assistant_messages must be greater than 0",
+        );
+    }
+
+    if input.stop_sequences.iter().any(String::is_empty) {
+        report.error("STOP_SEQUENCES", "STOP_SEQUENCES entries must not be empty");
+    }
+
+    if input.memory_limit_mb == Some(0) {
+        report.error("memory_limit_mb", "memory_limit_mb must be greater than 0");
+    } else if input.memory_limit_mb.is_some() && !MEMORY_LIMIT_SUPPORTED {
+        report.warning(
+            "memory_limit_mb",
+            "memory_limit_mb is not enforced on this platform",
+        );
+    }
+
+    if input.fallback_model.chars().any(char::is_whitespace) {
+        report.error(
+            "fallback_model",
+            "fallback_model must not contain whitespace",
+        );
+    }
+
+    if input.first_event_timeout_secs == Some(0) {
+        report.error(
+            "first_event_timeout_secs",
+            "first_event_timeout_secs must be greater than 0",
+        );
+    } else if input.first_event_timeout_secs.is_some() && input.fallback_model.is_empty() {
+        report.warning(
+            "first_event_timeout_secs",
+            "first_event_timeout_secs has no effect without fallback_model",
+        );
+    }
+
+    if !["", " ", "\n"].contains(&input.message_join.as_str()) {
+        report.error("message_join", r#"message_join must be "", " " or "\n""#);
+    }
+
+    if input.max_turns.is_some() && input.stop_phrase.is_empty() {
+        report.warning("max_turns", "max_turns has no effect without stop_phrase");
+    }
+
+    report
+}
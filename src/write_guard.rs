@@ -0,0 +1,150 @@
+//! Restricting the file types gemini may write in its workspace.
+//!
+//! The workspace is snapshotted before the run and compared afterwards, so a
+//! write is only noticed once it has happened. In a git repository, forbidden
+//! writes can then be undone: new files are deleted and tracked files that had
+//! no local changes before the run are checked out again.
+
+use crate::artifacts::Snapshot;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Whether `path` has one of the `allowed` extensions, compared without a
+/// leading dot and ignoring case. Files without an extension, such as `.env`,
+/// are never allowed.
+pub(crate) fn is_allowed(path: &Path, allowed: &[String]) -> bool {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    allowed
+        .iter()
+        .any(|a| a.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/// `path` as written by gemini, resolved against `cwd`, when it lies outside
+/// every one of `roots`. `..` components are resolved lexically, since the file
+/// may not exist, and symlinks in the existing part of the path are followed.
+pub(crate) fn outside_roots(path: &str, cwd: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let resolved = resolve(&cwd.join(path));
+    let inside = roots.iter().any(|root| resolved.starts_with(resolve(root)));
+    (!inside).then_some(resolved)
+}
+
+/// `path` with `.` and `..` removed and its longest existing prefix
+/// canonicalized.
+fn resolve(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            other => lexical.push(other),
+        }
+    }
+    let mut existing = lexical.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |path, part| path.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return lexical,
+        }
+    }
+}
+
+/// The workspace as it was before the run.
+#[derive(Debug)]
+pub(crate) struct WriteGuard {
+    before: Snapshot,
+    /// Files under the workspace differing from git's `HEAD`, relative to the
+    /// workspace; `None` outside a git repository.
+    dirty: Option<HashSet<PathBuf>>,
+}
+
+impl WriteGuard {
+    /// Record the state of `cwd`, and its git status when `revert` is wanted.
+    pub(crate) fn take(cwd: &Path, revert: bool) -> Self {
+        Self {
+            before: Snapshot::take(cwd),
+            dirty: revert.then(|| dirty_files(cwd)).flatten(),
+        }
+    }
+
+    /// Files under `cwd` created or modified since [`WriteGuard::take`]
+    /// without an `allowed` extension.
+    pub(crate) fn forbidden(&self, cwd: &Path, allowed: &[String]) -> Vec<PathBuf> {
+        self.before
+            .changed(cwd)
+            .into_iter()
+            .filter(|path| !is_allowed(path, allowed))
+            .collect()
+    }
+
+    /// Undo the writes to `paths` where that is safe, returning those undone.
+    /// Only possible in a git repository, and only for new files and for
+    /// tracked files without local changes before the run.
+    pub(crate) fn revert(&self, cwd: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let Some(dirty) = &self.dirty else {
+            return Vec::new();
+        };
+        paths
+            .iter()
+            .filter(|path| {
+                let Ok(relative) = path.strip_prefix(cwd) else {
+                    return false;
+                };
+                if !self.before.contains(path) {
+                    std::fs::remove_file(path).is_ok()
+                } else {
+                    !dirty.contains(relative)
+                        && git(cwd, &["ls-files", "--error-unmatch", "--"], relative).is_some()
+                        && git(cwd, &["checkout", "HEAD", "--"], relative).is_some()
+                }
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Files under `cwd` differing from `HEAD`, relative to `cwd`.
+fn dirty_files(cwd: &Path) -> Option<HashSet<PathBuf>> {
+    let output = git(
+        cwd,
+        &["diff", "--name-only", "-z", "--relative", "HEAD", "--"],
+        Path::new("."),
+    )?;
+    Some(
+        output
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+/// Run `git <args> <path>` in `cwd`, returning its output if it succeeded.
+fn git(cwd: &Path, args: &[&str], path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .arg(path)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
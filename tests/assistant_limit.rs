@@ -0,0 +1,77 @@
+//! Stopping after a bounded number of assistant messages.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions};
+
+const THREE_MESSAGES: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"first"}
+{"type":"message","role":"assistant","content":"second"}
+{"type":"message","role":"assistant","content":"third"}
+{"type":"turn.completed"}
+"#;
+
+#[tokio::test]
+async fn stops_after_max_assistant_messages() {
+    common::install_fake_gemini();
+    let ws = common::workspace(THREE_MESSAGES);
+
+    let options = GeminiOptions {
+        max_assistant_messages: Some(1),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success);
+    assert!(result.stopped_early);
+    assert_eq!(result.session_id.as_deref(), Some("sess-1"));
+    assert_eq!(result.agent_messages.as_deref(), Some("first"));
+}
+
+#[tokio::test]
+async fn full_turn_is_not_stopped_early() {
+    common::install_fake_gemini();
+    let ws = common::workspace(THREE_MESSAGES);
+
+    let options = GeminiOptions {
+        max_assistant_messages: Some(5),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(!result.stopped_early);
+    assert_eq!(result.agent_messages.as_deref(), Some("firstsecondthird"));
+}
+
+#[tokio::test]
+async fn only_assistant_messages_count_towards_the_limit() {
+    common::install_fake_gemini();
+    let ws = common::workspace(
+        r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"user","content":"question"}
+{"type":"message","role":"tool","content":"listing"}
+{"type":"message","role":"assistant","content":"first"}
+{"type":"message","role":"tool","content":"more"}
+{"type":"message","role":"assistant","content":"second"}
+{"type":"turn.completed"}
+"#,
+    );
+
+    let options = GeminiOptions {
+        content_roles: vec![
+            "assistant".to_string(),
+            "tool".to_string(),
+            "user".to_string(),
+        ],
+        max_assistant_messages: Some(1),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.stopped_early);
+    assert_eq!(
+        result.agent_messages.as_deref(),
+        Some("questionlistingfirst")
+    );
+}
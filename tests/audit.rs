@@ -0,0 +1,79 @@
+//! Redaction of prompts in the audit log.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::audit::{redact, AUDIT_TARGET};
+use gemini_mcp::ServerConfig;
+use regex::Regex;
+use serde_json::json;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Collects everything written by the fmt subscriber.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn patterns() -> Vec<Regex> {
+    vec![
+        Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap(),
+        Regex::new(r"sk-[A-Za-z0-9]{8,}").unwrap(),
+    ]
+}
+
+#[test]
+fn redact_replaces_every_match() {
+    let text = "mail alice@example.com, key sk-abcdef123456, cc bob@example.org";
+    assert_eq!(
+        redact(text, &patterns()),
+        "mail [REDACTED], key [REDACTED], cc [REDACTED]"
+    );
+    assert_eq!(redact("nothing to hide", &patterns()), "nothing to hide");
+}
+
+#[tokio::test]
+async fn audit_entry_is_redacted_but_prompt_is_sent_verbatim() {
+    common::install_fake_gemini();
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let _guard = tracing::subscriber::set_default(
+        tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish(),
+    );
+
+    let client = common::connect(ServerConfig {
+        audit_prompts: true,
+        redact_patterns: patterns(),
+        ..Default::default()
+    })
+    .await;
+    let prompt = "Email alice@example.com using token sk-abcdef123456";
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = common::call(&client, "gemini", json!({"PROMPT": prompt, "cd": ws})).await;
+    assert_eq!(result["success"], true);
+
+    let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let entry = log
+        .lines()
+        .find(|line| line.contains(AUDIT_TARGET))
+        .expect("no audit entry was written");
+    assert!(entry.contains("Email [REDACTED] using token [REDACTED]"));
+    assert!(!log.contains("alice@example.com"));
+    assert!(!log.contains("sk-abcdef123456"));
+
+    let args = common::gemini_args(&ws);
+    assert_eq!(args[0], "--prompt");
+    assert_eq!(args[1], prompt);
+}
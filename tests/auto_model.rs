@@ -0,0 +1,113 @@
+//! `auto_model`: picking the model by prompt size.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{ModelThreshold, ServerConfig};
+use serde_json::json;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        auto_models: vec![
+            "0=gemini-2.5-flash".parse().unwrap(),
+            "100=gemini-2.5-pro".parse().unwrap(),
+        ],
+        ..Default::default()
+    }
+}
+
+fn model_arg(ws: &std::path::Path) -> Option<String> {
+    let args = common::gemini_args(ws);
+    let at = args
+        .iter()
+        .position(|arg| arg == "--model" || arg == "-m")?;
+    args.get(at + 1).cloned()
+}
+
+#[test]
+fn thresholds_pick_the_largest_not_exceeding_the_prompt() {
+    let config = config();
+    assert_eq!(config.auto_model_for("short"), Some("gemini-2.5-flash"));
+    assert_eq!(
+        config.auto_model_for(&"x".repeat(99)),
+        Some("gemini-2.5-flash")
+    );
+    assert_eq!(
+        config.auto_model_for(&"x".repeat(100)),
+        Some("gemini-2.5-pro")
+    );
+    // Characters, not bytes
+    assert_eq!(
+        config.auto_model_for(&"é".repeat(60)),
+        Some("gemini-2.5-flash")
+    );
+    assert_eq!(ServerConfig::default().auto_model_for("short"), None);
+}
+
+#[test]
+fn thresholds_parse_min_chars_and_model() {
+    let threshold: ModelThreshold = " 20000 = gemini-2.5-pro ".parse().unwrap();
+    assert_eq!(threshold.min_chars, 20000);
+    assert_eq!(threshold.model, "gemini-2.5-pro");
+    assert!("gemini-2.5-pro".parse::<ModelThreshold>().is_err());
+    assert!("many=gemini-2.5-pro".parse::<ModelThreshold>().is_err());
+    assert!("100=".parse::<ModelThreshold>().is_err());
+}
+
+#[tokio::test]
+async fn model_follows_the_prompt_size() {
+    common::install_fake_gemini();
+    let client = common::connect(config()).await;
+
+    for (prompt, model) in [
+        ("short".to_string(), "gemini-2.5-flash"),
+        ("x".repeat(150), "gemini-2.5-pro"),
+    ] {
+        let ws = common::workspace(common::DEFAULT_STREAM);
+        let result = common::call(
+            client.peer(),
+            "gemini",
+            json!({"PROMPT": prompt, "cd": ws, "auto_model": true}),
+        )
+        .await;
+
+        assert_eq!(result["success"], true, "{result}");
+        assert_eq!(result["effective_model"], model);
+        assert_eq!(model_arg(&ws).as_deref(), Some(model));
+    }
+}
+
+#[tokio::test]
+async fn explicit_model_wins_over_auto_model() {
+    common::install_fake_gemini();
+    let client = common::connect(config()).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "short", "cd": ws, "auto_model": true, "model": "gemini-custom"}),
+    )
+    .await;
+
+    assert_eq!(result["effective_model"], "gemini-custom", "{result}");
+    assert_eq!(model_arg(&ws).as_deref(), Some("gemini-custom"));
+}
+
+#[tokio::test]
+async fn without_auto_model_no_model_is_passed() {
+    common::install_fake_gemini();
+    let client = common::connect(config()).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "short", "cd": ws}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("effective_model").is_none(), "{result}");
+    assert_eq!(model_arg(&ws), None);
+}
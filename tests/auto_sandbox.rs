@@ -0,0 +1,92 @@
+//! `auto_sandbox`: enabling the sandbox for prompts that look like edits.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn call_auto_sandbox(config: ServerConfig, prompt: &str) -> (serde_json::Value, Vec<String>) {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "auto_sandbox": true}),
+    )
+    .await;
+    (result, common::gemini_args(&ws))
+}
+
+#[tokio::test]
+async fn sandbox_is_enabled_for_a_prompt_that_modifies_files() {
+    let (result, args) = call_auto_sandbox(
+        ServerConfig::default(),
+        "Refactor the parser and delete the old module.",
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(args.contains(&"--sandbox".to_string()), "{args:?}");
+    assert_eq!(
+        result["sandbox_decision"],
+        json!({"enabled": true, "keyword": "delete"})
+    );
+}
+
+#[tokio::test]
+async fn sandbox_is_left_off_for_a_read_only_question() {
+    let (result, args) =
+        call_auto_sandbox(ServerConfig::default(), "Explain how the parser works.").await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(!args.contains(&"--sandbox".to_string()), "{args:?}");
+    assert_eq!(result["sandbox_decision"], json!({"enabled": false}));
+}
+
+#[tokio::test]
+async fn sandbox_keywords_are_configurable() {
+    let config = ServerConfig {
+        sandbox_keywords: vec!["explain".to_string()],
+        ..Default::default()
+    };
+    let (result, args) = call_auto_sandbox(config, "Explain, then delete it.").await;
+
+    assert!(args.contains(&"--sandbox".to_string()), "{args:?}");
+    assert_eq!(result["sandbox_decision"]["keyword"], "explain");
+}
+
+#[test]
+fn keywords_match_the_start_of_words_ignoring_case() {
+    let config = ServerConfig::default();
+    assert_eq!(config.sandbox_keyword_in("DELETED files"), Some("delete"));
+    assert_eq!(
+        config.sandbox_keyword_in("Please refactoring"),
+        Some("refactor")
+    );
+    assert_eq!(config.sandbox_keyword_in("What does this do?"), None);
+    // Inside a word is not a match
+    assert_eq!(config.sandbox_keyword_in("undeleted"), None);
+}
+
+#[test]
+fn keywords_without_a_star_match_whole_words_only() {
+    let config = ServerConfig::default();
+    assert_eq!(config.sandbox_keyword_in("Fix the bug"), Some("fix"));
+    assert_eq!(config.sandbox_keyword_in("It fixed nothing"), Some("fixed"));
+    assert_eq!(config.sandbox_keyword_in("Explain this fixture"), None);
+    assert_eq!(config.sandbox_keyword_in("Which editor do you use?"), None);
+    assert_eq!(config.sandbox_keyword_in("Summarize the changelog"), None);
+
+    let config = ServerConfig {
+        sandbox_keywords: vec!["deploy".to_string(), "migrat*".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(config.sandbox_keyword_in("deployment notes"), None);
+    assert_eq!(config.sandbox_keyword_in("Deploy it"), Some("deploy"));
+    assert_eq!(
+        config.sandbox_keyword_in("Run the MIGRATIONS"),
+        Some("migrat")
+    );
+}
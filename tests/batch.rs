@@ -0,0 +1,189 @@
+//! `gemini_batch` and `gemini_batch_retry`: re-running only what failed
+//! transiently.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::path::Path;
+
+/// Number of times the fake gemini ran in `workspace`.
+fn runs(workspace: &Path) -> usize {
+    std::fs::read_to_string(workspace.join("runs")).map_or(0, |runs| runs.lines().count())
+}
+
+/// A workspace that counts the runs of the fake gemini, printing `stream`.
+fn counting_workspace(stream: &str) -> std::path::PathBuf {
+    let ws = common::workspace(stream);
+    std::fs::write(ws.join("script.sh"), "echo run >> runs\n").unwrap();
+    ws
+}
+
+#[tokio::test]
+async fn retry_reruns_only_transient_failures() {
+    common::install_fake_gemini();
+    let ok = counting_workspace(common::DEFAULT_STREAM);
+    // Hangs past its timeout on the first run
+    let slow = counting_workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(slow.join("hang"), "").unwrap();
+    // Fails for good: gemini never answers
+    let silent = counting_workspace(
+        "{\"type\":\"init\",\"session_id\":\"sess-1\"}\n{\"type\":\"turn.completed\"}\n",
+    );
+
+    let client = common::connect(ServerConfig::default()).await;
+    let batch = common::call(
+        client.peer(),
+        "gemini_batch",
+        json!({"entries": [
+            {"PROMPT": "a", "cd": ok},
+            {"PROMPT": "b", "cd": slow, "TIMEOUT_SECS": 1, "kill_mode": "immediate"},
+            {"PROMPT": "c", "cd": silent},
+        ]}),
+    )
+    .await;
+
+    assert_eq!(batch["success"], false, "{batch}");
+    let results = batch["results"].as_array().unwrap();
+    assert_eq!(results[0]["success"], true);
+    assert_eq!(results[1]["failure_kind"], "timeout");
+    assert_eq!(results[2]["failure_kind"], "no_agent_messages");
+    assert!(batch.get("retried").is_none(), "{batch}");
+
+    // The slow entry recovers
+    std::fs::remove_file(slow.join("hang")).unwrap();
+    std::fs::write(slow.join("stream.jsonl"), common::DEFAULT_STREAM).unwrap();
+
+    let retried = common::call(
+        client.peer(),
+        "gemini_batch_retry",
+        json!({"batch_id": batch["batch_id"]}),
+    )
+    .await;
+
+    assert_eq!(retried["batch_id"], batch["batch_id"]);
+    assert_eq!(retried["retried"], json!([1]));
+    let results = retried["results"].as_array().unwrap();
+    assert_eq!(results[0]["success"], true);
+    assert_eq!(results[1]["success"], true, "{retried}");
+    assert_eq!(results[2]["failure_kind"], "no_agent_messages");
+    assert_eq!((runs(&ok), runs(&slow), runs(&silent)), (1, 2, 1));
+}
+
+#[tokio::test]
+async fn retry_rejects_an_unknown_batch() {
+    let client = common::connect(ServerConfig::default()).await;
+    let result = client
+        .peer()
+        .call_tool(rmcp::model::CallToolRequestParam {
+            name: "gemini_batch_retry".into(),
+            arguments: json!({"batch_id": "nope"}).as_object().cloned(),
+            task: None,
+        })
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn oversized_batches_are_rejected() {
+    common::install_fake_gemini();
+    let ws = counting_workspace(common::DEFAULT_STREAM);
+    let config = ServerConfig {
+        max_batch_entries: 2,
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+    let entry = json!({"PROMPT": "a", "cd": ws});
+
+    let result = client
+        .peer()
+        .call_tool(rmcp::model::CallToolRequestParam {
+            name: "gemini_batch".into(),
+            arguments: json!({"entries": [entry, entry, entry]})
+                .as_object()
+                .cloned(),
+            task: None,
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(runs(&ws), 0);
+}
+
+#[tokio::test]
+async fn entries_wait_for_a_free_slot() {
+    common::install_fake_gemini();
+    let workspaces: Vec<_> = (0..3)
+        .map(|_| {
+            let ws = common::workspace(common::DEFAULT_STREAM);
+            std::fs::write(
+                ws.join("script.sh"),
+                "date +%s%N > started; sleep 0.3; date +%s%N > ended\n",
+            )
+            .unwrap();
+            ws
+        })
+        .collect();
+    let config = ServerConfig {
+        batch_concurrency: 1,
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let entries: Vec<_> = workspaces
+        .iter()
+        .map(|ws| json!({"PROMPT": "a", "cd": ws}))
+        .collect();
+    let batch = common::call(client.peer(), "gemini_batch", json!({"entries": entries})).await;
+    assert_eq!(batch["success"], true, "{batch}");
+
+    let time = |ws: &Path, file: &str| -> u128 {
+        std::fs::read_to_string(ws.join(file))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    };
+    let mut spans: Vec<_> = workspaces
+        .iter()
+        .map(|ws| (time(ws, "started"), time(ws, "ended")))
+        .collect();
+    spans.sort();
+    for pair in spans.windows(2) {
+        assert!(pair[0].1 <= pair[1].0, "runs overlap: {spans:?}");
+    }
+}
+
+#[tokio::test]
+async fn each_entry_is_capped_to_max_payload_bytes() {
+    common::install_fake_gemini();
+    let text = "x".repeat(5000);
+    let stream = format!(
+        "{}\n{}\n{}\n",
+        json!({"type": "init", "session_id": "sess-1"}),
+        json!({"type": "message", "role": "assistant", "content": text}),
+        json!({"type": "turn.completed"}),
+    );
+    let big = common::workspace(&stream);
+    let small = common::workspace(common::DEFAULT_STREAM);
+    let config = ServerConfig {
+        max_payload_bytes: Some(2000),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let batch = common::call(
+        client.peer(),
+        "gemini_batch",
+        json!({"entries": [{"PROMPT": "a", "cd": big}, {"PROMPT": "b", "cd": small}]}),
+    )
+    .await;
+
+    let results = batch["results"].as_array().unwrap();
+    assert!(results[0].to_string().len() <= 2000, "{}", results[0]);
+    assert_eq!(results[0]["continuation_token"], results[0]["request_id"]);
+    assert_eq!(results[1]["agent_messages"], "Hello world");
+    assert!(results[1].get("continuation_token").is_none());
+}
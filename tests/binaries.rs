@@ -0,0 +1,206 @@
+//! Selecting one of several configured gemini executables with `binary`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::config::GeminiBinary;
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// A gemini that marks the workspace with `name` before behaving like the
+/// fake one.
+fn marking_gemini(name: &str) -> PathBuf {
+    let bin = common::scratch_dir("bin").join(format!("gemini-{name}"));
+    std::fs::write(
+        &bin,
+        format!("#!/bin/sh\necho {name} > ran\nexec gemini \"$@\"\n"),
+    )
+    .unwrap();
+    std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+    bin
+}
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        binaries: ["stable", "canary"]
+            .iter()
+            .map(|name| GeminiBinary {
+                name: name.to_string(),
+                path: marking_gemini(name),
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn named_binary_is_resolved_and_used() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "canary"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["binary"], "canary");
+    assert_eq!(std::fs::read_to_string(ws.join("ran")).unwrap(), "canary\n");
+}
+
+#[tokio::test]
+async fn gemini_from_path_runs_by_default() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("binary").is_none(), "{result}");
+    assert!(!ws.join("ran").exists());
+}
+
+#[tokio::test]
+async fn unknown_binary_is_rejected() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "nightly"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "invalid_input");
+    let error = result["error"].as_str().unwrap();
+    assert!(error.contains("stable, canary"), "{error}");
+    assert!(!ws.join("args").exists());
+}
+
+#[test]
+fn binary_parses_name_and_path() {
+    let binary: GeminiBinary = "canary = /opt/gemini/bin/gemini".parse().unwrap();
+    assert_eq!(binary.name, "canary");
+    assert_eq!(binary.path, PathBuf::from("/opt/gemini/bin/gemini"));
+    assert!("canary".parse::<GeminiBinary>().is_err());
+    assert!("=/opt/gemini".parse::<GeminiBinary>().is_err());
+}
+
+/// A gemini with its own `--help` and `--version` that otherwise behaves like
+/// the fake one.
+fn probed_gemini(name: &str, help: &str) -> PathBuf {
+    let dir = common::scratch_dir("bin");
+    std::fs::write(dir.join("help.txt"), help).unwrap();
+    let bin = dir.join(format!("gemini-{name}"));
+    std::fs::write(
+        &bin,
+        format!(
+            "#!/bin/sh\n\
+             if [ \"$1\" = \"--help\" ]; then cat '{}'; exit 0; fi\n\
+             if [ \"$1\" = \"--version\" ]; then echo {name}-1.0; exit 0; fi\n\
+             exec gemini \"$@\"\n",
+            dir.join("help.txt").display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+    bin
+}
+
+const MODERN_HELP: &str = r#"Options:
+      --seed                      Random seed                           [number]
+      --temperature               Sampling temperature                  [number]
+  -o, --output-format             The format of the CLI output.
+                                  [string] [choices: "text", "json", "stream-json"]
+"#;
+
+const LEGACY_HELP: &str = r#"Options:
+  -o, --output-format             The format of the CLI output.
+                                  [string] [choices: "text", "json"]
+"#;
+
+fn probed_config() -> ServerConfig {
+    ServerConfig {
+        binaries: vec![
+            GeminiBinary {
+                name: "modern".to_string(),
+                path: probed_gemini("modern", MODERN_HELP),
+            },
+            GeminiBinary {
+                name: "legacy".to_string(),
+                path: probed_gemini("legacy", LEGACY_HELP),
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn seed_follows_the_selected_binary() {
+    common::install_fake_gemini();
+    let client = common::connect(probed_config()).await;
+
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "modern", "SEED": 7}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+    assert!(common::gemini_args(&ws).contains(&"--seed".to_string()));
+
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "legacy", "SEED": 7}),
+    )
+    .await;
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+    assert!(!ws.join("args").exists());
+}
+
+#[tokio::test]
+async fn health_reports_each_binary() {
+    common::install_fake_gemini();
+    let client = common::connect(probed_config()).await;
+
+    let health = common::call(client.peer(), "gemini_health", json!({})).await;
+
+    let binaries = &health["binaries"];
+    assert_eq!(binaries["modern"]["status"], "ok", "{health}");
+    assert_eq!(binaries["modern"]["version"], "modern-1.0");
+    assert_eq!(binaries["modern"]["compatible"], true);
+    assert_eq!(binaries["legacy"]["status"], "down", "{health}");
+    assert_eq!(binaries["legacy"]["compatible"], false);
+    // Only calls selecting it fail
+    assert_eq!(health["status"], "degraded", "{health}");
+
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "legacy"}),
+    )
+    .await;
+    assert_eq!(result["success"], false, "{result}");
+    assert!(!ws.join("args").exists());
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "binary": "modern"}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+}
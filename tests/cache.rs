@@ -0,0 +1,106 @@
+//! Request fingerprints and the response cache.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn identical_requests_share_a_fingerprint_and_hit_the_cache() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let config = ServerConfig {
+        response_cache_ttl_secs: Some(60),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let first = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "first"}),
+    )
+    .await;
+    assert_eq!(first["success"], true, "{first}");
+    assert!(first.get("cached").is_none());
+    std::fs::remove_file(ws.join("args")).unwrap();
+
+    let second = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "second", "pretty": true}),
+    )
+    .await;
+    assert_eq!(second["request_fingerprint"], first["request_fingerprint"]);
+    assert_eq!(second["cached"], true);
+    assert_eq!(second["request_id"], "second");
+    assert_eq!(second["agent_messages"], first["agent_messages"]);
+    assert!(!ws.join("args").exists(), "gemini ran for a cached request");
+
+    let other = common::call(client.peer(), "gemini", json!({"PROMPT": "bye", "cd": ws})).await;
+    assert_ne!(other["request_fingerprint"], first["request_fingerprint"]);
+    assert!(other.get("cached").is_none());
+    assert!(ws.join("args").exists());
+}
+
+#[tokio::test]
+async fn cached_responses_are_still_checked_by_the_content_policy() {
+    use gemini_mcp::{GeminiServer, PolicyDecision};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let deny = Arc::new(AtomicBool::new(false));
+    let policy = {
+        let deny = deny.clone();
+        move |_: &str| {
+            if deny.load(Ordering::SeqCst) {
+                PolicyDecision::Deny {
+                    reason: "denied after caching".to_string(),
+                }
+            } else {
+                PolicyDecision::Allow
+            }
+        }
+    };
+    let server = GeminiServer::with_config(ServerConfig {
+        response_cache_ttl_secs: Some(60),
+        ..Default::default()
+    })
+    .with_content_policy(policy);
+    let client = common::connect_server(server).await;
+    let request = json!({"PROMPT": "hi", "cd": ws});
+
+    let first = common::call(client.peer(), "gemini", request.clone()).await;
+    assert_eq!(first["success"], true, "{first}");
+
+    deny.store(true, Ordering::SeqCst);
+    let second = common::call(client.peer(), "gemini", request).await;
+    assert_eq!(second["success"], false, "{second}");
+    assert_eq!(second["failure_kind"], "policy_denied");
+    assert!(second.get("cached").is_none());
+}
+
+#[tokio::test]
+async fn cached_responses_count_against_the_session_resume_limit() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig {
+        response_cache_ttl_secs: Some(60),
+        session_resume_limit: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let request = json!({"PROMPT": "hi", "cd": ws, "SESSION_ID": "sess-1"});
+
+    let first = common::call(client.peer(), "gemini", request.clone()).await;
+    assert_eq!(first["success"], true, "{first}");
+    let resumed = common::call(client.peer(), "gemini", request.clone()).await;
+    assert_eq!(resumed["cached"], true, "{resumed}");
+
+    let over = common::call(client.peer(), "gemini", request).await;
+    assert_eq!(over["success"], false, "{over}");
+    assert_eq!(over["failure_kind"], "session_quota_exceeded");
+}
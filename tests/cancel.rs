@@ -0,0 +1,51 @@
+//! Cancelling a run by request id through `GeminiServer::cancel`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{GeminiServer, ServerConfig};
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn run_is_cancelled_by_request_id() {
+    common::install_fake_gemini();
+    let ws = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(ws.join("hang"), "").unwrap();
+    let server = GeminiServer::with_config(ServerConfig::default());
+    let client = common::connect_server(server.clone()).await;
+
+    let peer = client.peer().clone();
+    let call = tokio::spawn(async move {
+        common::call(
+            &peer,
+            "gemini",
+            json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "req-cancel"}),
+        )
+        .await
+    });
+    let mut cancelled = false;
+    for _ in 0..100 {
+        if server.cancel("req-cancel") {
+            cancelled = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(cancelled, "the run never started");
+
+    let result = tokio::time::timeout(Duration::from_secs(10), call)
+        .await
+        .expect("the run did not stop")
+        .unwrap();
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "cancelled");
+    assert_eq!(result["request_id"], "req-cancel");
+}
+
+#[tokio::test]
+async fn unknown_request_id_is_not_found() {
+    let server = GeminiServer::with_config(ServerConfig::default());
+
+    assert!(!server.cancel("no-such-request"));
+}
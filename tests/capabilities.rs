@@ -0,0 +1,49 @@
+//! Reading gemini's capabilities from its help text.
+
+use gemini_mcp::capabilities::{parse_output_formats, STREAM_JSON};
+use gemini_mcp::Capabilities;
+
+const HELP: &str = r#"Usage: gemini [options] [command]
+
+Options:
+  -m, --model                     Model                                 [string]
+  -o, --output-format             The format of the CLI output.
+                                  [string] [choices: "text", "json",
+                                  "stream-json"]
+  -r, --resume                    Resume a previous session.            [string]
+"#;
+
+#[test]
+fn output_formats_are_read_from_wrapped_choices() {
+    assert_eq!(parse_output_formats(HELP), ["text", "json", STREAM_JSON]);
+}
+
+#[test]
+fn older_gemini_without_stream_json_is_detected() {
+    let help = r#"Options:
+  -o, --output-format   The format of the CLI output. [string] [choices: "text", "json"]
+  -r, --resume          Resume a previous session. [string] [choices: "latest"]
+"#;
+    let capabilities = Capabilities {
+        version: Some("0.4.1".to_string()),
+        output_formats: parse_output_formats(help),
+        ..Default::default()
+    };
+
+    assert_eq!(capabilities.output_formats, ["text", "json"]);
+    assert!(!capabilities.supports_output_format(STREAM_JSON));
+}
+
+#[test]
+fn help_without_choices_assumes_support() {
+    let help = "Options:\n  -o, --output-format   The format of the CLI output. [string]\n";
+
+    let capabilities = Capabilities {
+        version: None,
+        output_formats: parse_output_formats(help),
+        ..Default::default()
+    };
+
+    assert!(capabilities.output_formats.is_empty());
+    assert!(capabilities.supports_output_format(STREAM_JSON));
+}
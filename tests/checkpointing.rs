@@ -0,0 +1,45 @@
+//! `checkpointing`: passing `--checkpointing` and returning the checkpoint id.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+const STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"checkpoint","checkpoint_id":"ckpt-1"}
+{"type":"message","role":"assistant","content":"Edited main.rs"}
+{"type":"checkpoint","checkpoint_id":"ckpt-2"}
+{"type":"turn.completed"}
+"#;
+
+#[tokio::test]
+async fn flag_is_passed_and_the_last_checkpoint_id_returned() {
+    common::install_fake_gemini();
+    let ws = common::workspace(STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "edit", "cd": ws, "checkpointing": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(common::gemini_args(&ws).contains(&"--checkpointing".to_string()));
+    assert_eq!(result["checkpoint_id"], "ckpt-2");
+}
+
+#[tokio::test]
+async fn flag_is_omitted_by_default() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(!common::gemini_args(&ws).contains(&"--checkpointing".to_string()));
+    assert!(result.get("checkpoint_id").is_none());
+}
@@ -0,0 +1,183 @@
+//! Sending an oversized prompt as consecutive turns of one session.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::chunking::{split_framed, split_prompt};
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// Appends its arguments to `calls` (one call per block, ended by `--`), then
+/// answers on session `sess-1`.
+const LOGGING_GEMINI: &str = r#"#!/bin/sh
+printf '%s\n' "$@" >> calls
+echo -- >> calls
+echo '{"type":"init","session_id":"sess-1"}'
+echo '{"type":"message","role":"assistant","content":"OK"}'
+echo '{"type":"turn.completed","usage":{"input_tokens":10,"output_tokens":1}}'
+"#;
+
+fn install_logging_gemini() {
+    common::install_gemini("chunking", LOGGING_GEMINI);
+}
+
+#[test]
+fn prompts_split_on_paragraphs_and_concatenate_back() {
+    let prompt = "first paragraph\n\nsecond paragraph\nstill second\n\nthird";
+    let chunks = split_prompt(prompt, 31);
+
+    assert_eq!(
+        chunks,
+        [
+            "first paragraph\n\n",
+            "second paragraph\nstill second\n\n",
+            "third"
+        ]
+    );
+    assert_eq!(chunks.concat(), prompt);
+    assert_eq!(split_prompt("short", 36), ["short"]);
+}
+
+#[tokio::test]
+async fn oversized_prompt_is_sent_in_order_on_one_session() {
+    install_logging_gemini();
+    let ws = common::scratch_dir("ws");
+    let paragraphs: Vec<String> = (1..=3)
+        .map(|i| format!("paragraph {i} {}", "x".repeat(100)))
+        .collect();
+    let prompt = paragraphs.join("\n\n");
+
+    let config = ServerConfig {
+        max_prompt_bytes: Some(230),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let rejected = common::call(client.peer(), "gemini", json!({"PROMPT": prompt, "cd": ws})).await;
+    assert_eq!(rejected["failure_kind"], "invalid_input");
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "chunk_prompt": true}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["chunks"], 3);
+    assert_eq!(result["usage"]["input_tokens"], 30);
+
+    let calls = std::fs::read_to_string(ws.join("calls")).unwrap();
+    let calls: Vec<&str> = calls.split("--\n").filter(|c| !c.is_empty()).collect();
+    assert_eq!(calls.len(), 3);
+    for (i, (call, paragraph)) in calls.iter().zip(&paragraphs).enumerate() {
+        assert!(
+            call.contains(&format!("part {} of 3", i + 1)) || call.contains("(3 of 3)"),
+            "{call}"
+        );
+        assert!(
+            call.contains(paragraph.as_str()),
+            "part {} missing from {call}",
+            i + 1
+        );
+        assert_eq!(call.contains("--resume\nsess-1\n"), i > 0, "{call}");
+    }
+}
+
+#[test]
+fn framed_split_leaves_room_for_the_frame() {
+    let prompt = "word ".repeat(2000);
+    for max in [150, 300, 1000] {
+        let chunks = split_framed(&prompt, max);
+        assert_eq!(chunks.concat(), prompt);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() < max - 100), "{max}");
+    }
+}
+
+#[tokio::test]
+async fn framed_parts_fit_max_prompt_bytes() {
+    install_logging_gemini();
+    let ws = common::scratch_dir("ws");
+    let prompt = (1..=40)
+        .map(|i| format!("line {i} {}", "y".repeat(30)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let max = 400;
+    let config = ServerConfig {
+        max_prompt_bytes: Some(max),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "chunk_prompt": true}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let calls = std::fs::read_to_string(ws.join("calls")).unwrap();
+    let calls: Vec<&str> = calls.split("--\n").filter(|c| !c.is_empty()).collect();
+    assert_eq!(result["chunks"], calls.len());
+    assert!(calls.len() > 1);
+    for call in &calls {
+        let framed = call
+            .strip_prefix("--prompt\n")
+            .and_then(|rest| rest.split("\n-o\n").next())
+            .unwrap_or_else(|| panic!("no prompt in {call}"));
+        assert!(framed.contains(" of a long prompt"), "{framed}");
+        assert!(framed.len() <= max, "{} bytes: {framed}", framed.len());
+    }
+}
+
+#[tokio::test]
+async fn raw_prompt_sends_parts_without_framing() {
+    install_logging_gemini();
+    let ws = common::scratch_dir("ws");
+    let prompt = format!("{}\n\n{}", "a".repeat(40), "b".repeat(40));
+    let config = ServerConfig {
+        max_prompt_bytes: Some(60),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "chunk_prompt": true, "raw_prompt": true}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let calls = std::fs::read_to_string(ws.join("calls")).unwrap();
+    let calls: Vec<&str> = calls.split("--\n").filter(|c| !c.is_empty()).collect();
+    assert_eq!(calls.len(), 2);
+    let prompt_of = |call: &str| call.split("\n-o\n").next().unwrap().to_string();
+    assert_eq!(
+        prompt_of(calls[0]),
+        format!("--prompt\n{}\n\n", "a".repeat(40))
+    );
+    assert!(prompt_of(calls[1]).ends_with(&format!("--prompt\n{}", "b".repeat(40))));
+}
+
+#[tokio::test]
+async fn raw_prompt_can_be_forbidden() {
+    install_logging_gemini();
+    let ws = common::scratch_dir("ws");
+    let config = ServerConfig {
+        forbid_raw_prompt: true,
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "raw_prompt": true}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(!ws.join("calls").exists(), "gemini should not have run");
+}
@@ -0,0 +1,61 @@
+//! Parsing citation and source-grounding events.
+
+use gemini_mcp::{replay_stream, Citation, GeminiOptions};
+
+#[test]
+fn citations_are_parsed_and_kept_out_of_agent_messages() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Rust 1.0 shipped in 2015."}
+{"type":"citation","url":"https://blog.rust-lang.org/2015/05/15/Rust-1.0.html","title":"Announcing Rust 1.0","content":"We are very proud to announce the 1.0 release"}
+{"type":"grounding","sources":[{"web":{"uri":"https://www.rust-lang.org","title":"Rust"}},{"title":"Rust 1.0 release notes","snippet":"May 15, 2015"}]}
+{"type":"citation","url":"https://blog.rust-lang.org/2015/05/15/Rust-1.0.html","title":"Announcing Rust 1.0","content":"We are very proud to announce the 1.0 release"}
+{"type":"turn.completed"}
+"#;
+    let options = GeminiOptions {
+        return_all_messages: true,
+        ..Default::default()
+    };
+
+    let result = replay_stream(output, &options);
+
+    assert!(result.success);
+    assert_eq!(
+        result.agent_messages.as_deref(),
+        Some("Rust 1.0 shipped in 2015.")
+    );
+    assert_eq!(
+        result.citations.unwrap(),
+        [
+            Citation {
+                url: Some("https://blog.rust-lang.org/2015/05/15/Rust-1.0.html".to_string()),
+                title: Some("Announcing Rust 1.0".to_string()),
+                snippet: Some("We are very proud to announce the 1.0 release".to_string()),
+            },
+            Citation {
+                url: Some("https://www.rust-lang.org".to_string()),
+                title: Some("Rust".to_string()),
+                snippet: None,
+            },
+            Citation {
+                url: None,
+                title: Some("Rust 1.0 release notes".to_string()),
+                snippet: Some("May 15, 2015".to_string()),
+            },
+        ]
+    );
+    // The raw events are still there
+    let all = result.all_messages.unwrap();
+    assert_eq!(all.iter().filter(|e| e["type"] == "citation").count(), 2);
+}
+
+#[test]
+fn turns_without_citations_have_none() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"hi"}
+{"type":"turn.completed"}
+"#;
+    assert_eq!(
+        replay_stream(output, &GeminiOptions::default()).citations,
+        None
+    );
+}
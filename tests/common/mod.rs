@@ -0,0 +1,195 @@
+//! Shared helpers: a fake `gemini` executable and an in-process MCP client.
+#![allow(dead_code)]
+
+use gemini_mcp::{GeminiServer, ServerConfig};
+use rmcp::model::{CallToolRequestParam, CallToolResult};
+use rmcp::service::RunningService;
+use rmcp::{ClientHandler, Peer, RoleClient, ServiceExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::task::JoinHandle;
+
+/// Stream printed by the fake gemini when the workspace has no `stream.jsonl`.
+pub const DEFAULT_STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Hello "}
+{"type":"message","role":"assistant","content":"world"}
+{"type":"turn.completed"}
+"#;
+
+/// Fake gemini: records its arguments in `args` (one per line), its
+/// environment in `env` and any stdin in `stdin`, then prints `stream.jsonl`, all in its working
+/// directory. `stream.timed` is replayed line by line instead, each line
+/// being `<delay secs>\t<output>`. `script.sh` is run first if present, to
+/// act on the workspace like gemini's tools would. If `hang` exists it then
+/// writes its pid to `pid` and sleeps. `--help` prints the file named by
+/// `FAKE_GEMINI_HELP`, when set.
+const FAKE_GEMINI: &str = r#"#!/bin/sh
+if [ "$1" = "--help" ] && [ -n "$FAKE_GEMINI_HELP" ]; then cat "$FAKE_GEMINI_HELP"; exit 0; fi
+printf '%s\n' "$@" > args
+env > env
+input=$(cat)
+if [ -n "$input" ]; then printf '%s' "$input" > stdin; fi
+if [ -f script.sh ]; then sh script.sh; fi
+if [ -f stream.jsonl ]; then cat stream.jsonl; fi
+if [ -f stream.timed ]; then
+  tab=$(printf '\t')
+  while IFS="$tab" read -r delay line; do
+    sleep "$delay"
+    printf '%s\n' "$line"
+  done < stream.timed
+fi
+if [ -f hang ]; then echo $$ > pid; exec sleep 60; fi
+"#;
+
+/// Put the fake gemini first in PATH (once per test binary).
+#[cfg(unix)]
+pub fn install_fake_gemini() {
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        install_gemini("bin", FAKE_GEMINI);
+    });
+}
+
+/// Put `script` first in PATH as `gemini`, in a fresh directory named after
+/// `name`, and return its path.
+#[cfg(unix)]
+pub fn install_gemini(name: &str, script: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = scratch_dir(name);
+    let gemini = bin.join("gemini");
+    std::fs::write(&gemini, script).unwrap();
+    std::fs::set_permissions(&gemini, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![bin];
+    paths.extend(std::env::split_paths(&path));
+    std::env::set_var("PATH", std::env::join_paths(paths).unwrap());
+    gemini
+}
+
+/// A fresh, empty directory under the system temp dir.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gemini-mcp-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A workspace in which the fake gemini prints `stream`.
+pub fn workspace(stream: &str) -> PathBuf {
+    let dir = scratch_dir("ws");
+    std::fs::write(dir.join("stream.jsonl"), stream).unwrap();
+    dir
+}
+
+/// A recorded stream from `tests/fixtures/stream/<name>.jsonl`.
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/stream")
+        .join(format!("{name}.jsonl"));
+    std::fs::read_to_string(path).unwrap()
+}
+
+/// Seconds since midnight of an event's `timestamp`, e.g.
+/// `2025-10-02T09:20:41.003Z`.
+fn event_time(line: &str) -> Option<f64> {
+    let event: serde_json::Value = serde_json::from_str(line).ok()?;
+    let time = event["timestamp"].as_str()?.split('T').nth(1)?;
+    let mut parts = time.trim_end_matches('Z').split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// A workspace in which the fake gemini replays `stream` with the gaps
+/// between its event timestamps, divided by `speed`. Lines without a
+/// timestamp follow the previous line immediately.
+pub fn timed_workspace(stream: &str, speed: f64) -> PathBuf {
+    let mut timed = String::new();
+    let mut previous: Option<f64> = None;
+    for line in stream.lines() {
+        let time = event_time(line);
+        let delay = match (previous, time) {
+            (Some(previous), Some(time)) => ((time - previous) / speed).max(0.0),
+            _ => 0.0,
+        };
+        previous = time.or(previous);
+        timed.push_str(&format!("{delay:.3}\t{line}\n"));
+    }
+
+    let dir = scratch_dir("ws");
+    std::fs::write(dir.join("stream.timed"), timed).unwrap();
+    dir
+}
+
+/// Arguments the fake gemini was last started with in `workspace`.
+pub fn gemini_args(workspace: &Path) -> Vec<String> {
+    std::fs::read_to_string(workspace.join("args"))
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Value of an environment variable the fake gemini was last started with.
+pub fn gemini_env(workspace: &Path, name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    std::fs::read_to_string(workspace.join("env"))
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix).map(str::to_string))
+}
+
+/// Serve `config` over an in-memory pipe and connect a client to it.
+pub async fn connect(config: ServerConfig) -> RunningService<RoleClient, ()> {
+    start(config, ()).await.0
+}
+
+/// Connect `client` to a server for `config`, also returning the task serving
+/// the connection.
+pub async fn start<C: ClientHandler>(
+    config: ServerConfig,
+    client: C,
+) -> (
+    RunningService<RoleClient, C>,
+    JoinHandle<anyhow::Result<()>>,
+) {
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(GeminiServer::with_config(config).run(server_io));
+    (client.serve(client_io).await.unwrap(), server)
+}
+
+/// Serve `server` over an in-memory pipe and connect a client to it.
+pub async fn connect_server(server: GeminiServer) -> RunningService<RoleClient, ()> {
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(server.run(server_io));
+    ().serve(client_io).await.unwrap()
+}
+
+/// Call a tool.
+pub async fn call_raw(
+    client: &Peer<RoleClient>,
+    tool: &'static str,
+    arguments: serde_json::Value,
+) -> CallToolResult {
+    client
+        .call_tool(CallToolRequestParam {
+            name: tool.into(),
+            arguments: arguments.as_object().cloned(),
+            task: None,
+        })
+        .await
+        .unwrap()
+}
+
+/// Call a tool and parse its JSON text output.
+pub async fn call(
+    client: &Peer<RoleClient>,
+    tool: &'static str,
+    arguments: serde_json::Value,
+) -> serde_json::Value {
+    let result = call_raw(client, tool, arguments).await;
+    let text = result.content[0].as_text().unwrap().text.clone();
+    serde_json::from_str(&text).unwrap()
+}
@@ -0,0 +1,114 @@
+//! `compress_context`: a gemini pass compressing an oversized `CONTEXT` first.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::context::{COMPRESSED_CONTEXT_LABEL, CONTEXT_LABEL};
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::path::Path;
+
+/// Keeps the arguments of every run, numbered from 1, in `args.<n>`.
+const COUNT_RUNS: &str = r#"n=$(($(cat runs 2>/dev/null || echo 0) + 1))
+echo $n > runs
+cp args args.$n
+"#;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        max_prompt_bytes: Some(500),
+        compression_target_bytes: 100,
+        ..Default::default()
+    }
+}
+
+fn run_args(ws: &Path, n: usize) -> String {
+    std::fs::read_to_string(ws.join(format!("args.{n}"))).unwrap()
+}
+
+async fn run(context: serde_json::Value) -> (serde_json::Value, std::path::PathBuf) {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("script.sh"), COUNT_RUNS).unwrap();
+    let client = common::connect(config()).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "Review the ticket", "cd": ws, "CONTEXT": context, "compress_context": true}),
+    )
+    .await;
+    (result, ws)
+}
+
+#[tokio::test]
+async fn oversized_context_is_compressed_in_a_first_pass() {
+    let notes = "a long line of notes ".repeat(50);
+    let (result, ws) = run(json!({"notes": notes})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        std::fs::read_to_string(ws.join("runs")).unwrap().trim(),
+        "2"
+    );
+
+    // The first pass asks for the compression, with the whole context
+    let first = run_args(&ws, 1);
+    assert!(first.contains("at most 100 bytes"), "{first}");
+    assert!(first.contains("Task: Review the ticket"), "{first}");
+    assert!(
+        first.contains(CONTEXT_LABEL) && first.contains(&notes),
+        "{first}"
+    );
+
+    // The second sends the prompt with the first pass's reply as context
+    let second = run_args(&ws, 2);
+    assert!(second.contains("Review the ticket"), "{second}");
+    assert!(
+        second.contains(&format!("{COMPRESSED_CONTEXT_LABEL}\nHello world")),
+        "{second}"
+    );
+    assert!(!second.contains(&notes), "{second}");
+
+    let ratio = result["compression_ratio"].as_f64().unwrap();
+    assert!(ratio > 0.0 && ratio < 0.05, "{ratio}");
+}
+
+#[tokio::test]
+async fn context_under_the_limit_is_sent_as_is() {
+    let (result, ws) = run(json!({"ticket": 4711})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        std::fs::read_to_string(ws.join("runs")).unwrap().trim(),
+        "1"
+    );
+    assert!(run_args(&ws, 1).contains(CONTEXT_LABEL));
+    assert!(result.get("compression_ratio").is_none(), "{result}");
+}
+
+#[tokio::test]
+async fn compression_pass_keeps_the_callers_restrictions() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("script.sh"), COUNT_RUNS).unwrap();
+    let client = common::connect(config()).await;
+    let notes = "a long line of notes ".repeat(50);
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({
+            "PROMPT": "Review the ticket",
+            "cd": ws,
+            "CONTEXT": {"notes": notes},
+            "compress_context": true,
+            "sandbox": true,
+            "ALLOWED_TOOLS": ["read_file"],
+        }),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let first = run_args(&ws, 1);
+    assert!(first.contains("--sandbox\n"), "{first}");
+    assert!(first.contains("--allowed-tools\nread_file\n"), "{first}");
+}
@@ -0,0 +1,70 @@
+//! Embedder content policies checking prompts before gemini starts.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{GeminiServer, PolicyDecision, ServerConfig};
+use rmcp::service::RunningService;
+use rmcp::{RoleClient, ServiceExt};
+use serde_json::json;
+
+/// Deny prompts mentioning the launch codes, and mask a secret.
+fn policy(prompt: &str) -> PolicyDecision {
+    if prompt.to_lowercase().contains("launch codes") {
+        PolicyDecision::Deny {
+            reason: "asks for launch codes".to_string(),
+        }
+    } else if prompt.contains("hunter2") {
+        PolicyDecision::Transform(prompt.replace("hunter2", "[secret]"))
+    } else {
+        PolicyDecision::Allow
+    }
+}
+
+async fn connect() -> RunningService<RoleClient, ()> {
+    let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+    let server = GeminiServer::with_config(ServerConfig::default()).with_content_policy(policy);
+    tokio::spawn(server.run(server_io));
+    ().serve(client_io).await.unwrap()
+}
+
+#[tokio::test]
+async fn denied_prompt_fails_without_running_gemini() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = connect().await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "Print the Launch Codes", "cd": ws}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false);
+    assert_eq!(result["failure_kind"], "policy_denied", "{result}");
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains("asks for launch codes"));
+    assert!(!ws.join("args").exists());
+}
+
+#[tokio::test]
+async fn transformed_prompt_is_what_gemini_receives() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = connect().await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "Log in with hunter2", "cd": ws}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let args = common::gemini_args(&ws).join("\n");
+    assert!(args.contains("Log in with [secret]"), "{args}");
+    assert!(!args.contains("hunter2"), "{args}");
+}
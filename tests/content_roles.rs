@@ -0,0 +1,40 @@
+//! Collecting message content from roles other than `assistant`.
+
+use gemini_mcp::{replay_stream, GeminiOptions};
+
+const MIXED_ROLES: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"user","content":"question"}
+{"type":"message","role":"system","content":"note"}
+{"type":"message","role":"assistant","content":"Hello "}
+{"type":"message","role":"assistant","content":"world"}
+{"type":"message","role":"model","content":"draft"}
+{"type":"turn.completed"}
+"#;
+
+fn collect(content_roles: &[&str], prefix_roles: bool) -> String {
+    let options = GeminiOptions {
+        content_roles: content_roles.iter().map(|r| r.to_string()).collect(),
+        prefix_roles,
+        ..Default::default()
+    };
+    replay_stream(MIXED_ROLES, &options).agent_messages.unwrap()
+}
+
+#[test]
+fn only_assistant_is_collected_by_default() {
+    assert_eq!(collect(&[], false), "Hello world");
+    assert_eq!(collect(&[], true), "Hello world");
+}
+
+#[test]
+fn configured_roles_are_collected_in_order() {
+    assert_eq!(collect(&["assistant", "model"], false), "Hello worlddraft");
+}
+
+#[test]
+fn runs_of_each_role_are_prefixed() {
+    assert_eq!(
+        collect(&["assistant", "model", "system"], true),
+        "[system] note\n[assistant] Hello world\n[model] draft"
+    );
+}
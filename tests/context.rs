@@ -0,0 +1,79 @@
+//! Passing structured `CONTEXT` with the prompt.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::context::{embed_context, CONTEXT_LABEL};
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// The JSON in the fenced block following the context label.
+fn extract_context(prompt: &str) -> serde_json::Value {
+    let block = prompt.split(CONTEXT_LABEL).nth(1).unwrap().trim();
+    let fence_len = block.chars().take_while(|c| *c == '`').count();
+    let fence = "`".repeat(fence_len);
+    let json = block
+        .strip_prefix(&format!("{fence}json\n"))
+        .unwrap()
+        .strip_suffix(&format!("\n{fence}"))
+        .unwrap();
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn context_round_trips_even_with_backtick_fences_inside() {
+    let context = json!({
+        "ticket": 4711,
+        "files": ["src/lib.rs"],
+        "note": "see ```rust\nfn main() {}\n``` and \"quotes\"",
+        "nested": {"flag": true, "ratio": 0.5, "none": null},
+    });
+
+    let prompt = embed_context("Fix the bug.", &context);
+
+    assert!(prompt.starts_with("Fix the bug.\n\nContext (JSON):\n````json\n"));
+    assert_eq!(extract_context(&prompt), context);
+}
+
+#[tokio::test]
+async fn context_is_appended_to_the_prompt_gemini_receives() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+    let context = json!({"user": {"id": 7, "roles": ["admin"]}});
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "Summarize the user.", "cd": ws, "CONTEXT": context}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let args = common::gemini_args(&ws).join("\n");
+    let prompt = args
+        .split("--prompt\n")
+        .nth(1)
+        .unwrap()
+        .split("\n-o\n")
+        .next()
+        .unwrap();
+    assert!(prompt.starts_with("Summarize the user.\n\n"), "{prompt}");
+    assert_eq!(extract_context(prompt), context);
+}
+
+#[tokio::test]
+async fn context_conflicts_with_raw_prompt() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "CONTEXT": {"a": 1}, "raw_prompt": true}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+}
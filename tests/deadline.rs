@@ -0,0 +1,92 @@
+//! Absolute `DEADLINE`s, timed on the monotonic clock.
+
+mod common;
+
+use gemini_mcp::{Deadline, ServerConfig};
+use serde_json::json;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn deadline_is_anchored_to_the_monotonic_clock() {
+    let before = tokio::time::Instant::now();
+    let now = SystemTime::now();
+    let deadline = Deadline::from_wall_clock(now + Duration::from_secs(10), now).unwrap();
+    let after = tokio::time::Instant::now();
+
+    assert!(deadline.instant() >= before + Duration::from_secs(10));
+    assert!(deadline.instant() <= after + Duration::from_secs(10));
+
+    let mut previous = deadline.remaining();
+    for _ in 0..5 {
+        std::thread::sleep(Duration::from_millis(10));
+        let before = tokio::time::Instant::now();
+        let remaining = deadline.remaining();
+        let after = tokio::time::Instant::now();
+        assert!(remaining < previous, "{remaining:?} >= {previous:?}");
+        assert!(remaining <= deadline.instant() - before);
+        assert!(remaining >= deadline.instant() - after);
+        previous = remaining;
+    }
+}
+
+#[test]
+fn deadline_in_the_past_reports_how_long_ago_it_passed() {
+    let now = SystemTime::now();
+    let passed = Deadline::from_wall_clock(now - Duration::from_secs(5), now).unwrap_err();
+    assert_eq!(passed, Duration::from_secs(5));
+}
+
+fn unix_secs_from_now(offset: i64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    now.checked_add_signed(offset).unwrap()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn deadline_cuts_a_run_short_of_its_timeout() {
+    common::install_fake_gemini();
+    let ws = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(ws.join("hang"), "").unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let started = Instant::now();
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({
+            "PROMPT": "hi",
+            "cd": ws,
+            "TIMEOUT_SECS": 60,
+            "DEADLINE": unix_secs_from_now(2),
+            "kill_mode": "immediate",
+        }),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "timeout", "{result}");
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn passed_deadline_is_reported_without_running_gemini() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "DEADLINE": unix_secs_from_now(-30)}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false);
+    assert_eq!(result["failure_kind"], "timeout", "{result}");
+    let error = result["error"].as_str().unwrap();
+    assert!(error.contains("clocks"), "{error}");
+    assert!(!ws.join("args").exists());
+}
@@ -0,0 +1,48 @@
+//! `--debug-envelope`: describing the tool result envelope in `_debug`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn run(config: ServerConfig) -> (String, serde_json::Value) {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config).await;
+    let result = common::call_raw(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    let text = result.content[0].as_text().unwrap().text.clone();
+    let value = serde_json::from_str(&text).unwrap();
+    (text, value)
+}
+
+#[tokio::test]
+async fn debug_field_describes_the_envelope_when_enabled() {
+    let (text, result) = run(ServerConfig {
+        debug_envelope: true,
+        ..Default::default()
+    })
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let debug = &result["_debug"];
+    assert_eq!(debug["content"].as_array().unwrap().len(), 1, "{debug}");
+    assert_eq!(debug["content"][0]["type"], "text");
+    assert_eq!(debug["is_error"], false);
+    assert!(debug.get("structured_content_bytes").is_none(), "{debug}");
+
+    // The size is that of the text without `_debug` itself
+    let mut without_debug = result.clone();
+    without_debug.as_object_mut().unwrap().remove("_debug");
+    let bytes = debug["content"][0]["bytes"].as_u64().unwrap() as usize;
+    assert_eq!(bytes, serde_json::to_string(&without_debug).unwrap().len());
+    assert!(bytes < text.len());
+}
+
+#[tokio::test]
+async fn debug_field_is_absent_by_default() {
+    let (_, result) = run(ServerConfig::default()).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("_debug").is_none(), "{result}");
+}
@@ -0,0 +1,162 @@
+//! Diagnostics for undecodable lines in gemini's output, and failing runs
+//! whose output mostly fails to decode.
+
+mod common;
+
+use gemini_mcp::{
+    execute_gemini, replay_reader, replay_stream, ErrorEntry, ErrorEntryKind, FailureKind,
+    GeminiOptions,
+};
+
+#[test]
+fn decode_errors_report_the_line_number() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+
+oops, not json
+{"type":"turn.completed"}
+"#;
+
+    let result = replay_stream(output, &GeminiOptions::default());
+    let error = result.error.unwrap();
+
+    assert!(
+        error.contains(
+            "[json decode error @ line 3] expected value at line 1 column 1: oops, not json"
+        ),
+        "unexpected error: {error}"
+    );
+}
+
+#[test]
+fn summary_line_after_completion_is_not_an_error() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"done"}
+{"type":"turn.completed"}
+Finished in 4.2s, 2 tool calls
+"#;
+
+    let result = replay_stream(output, &GeminiOptions::default());
+
+    assert!(result.success);
+    assert_eq!(result.error, None);
+    assert_eq!(result.agent_messages.as_deref(), Some("done"));
+}
+
+#[test]
+fn benign_lines_are_not_decode_errors() {
+    // No assistant text, so the decode errors would show up in `error`
+    let output = r#"{"type":"init","session_id":"sess-1"}
+oops, not json
+Finished in 4.2s, 2 tool calls
+"#;
+    let options = GeminiOptions {
+        benign_output: vec![regex::Regex::new(r"^Finished in ").unwrap()],
+        ..Default::default()
+    };
+
+    let result = replay_stream(output, &options);
+    let error = result.error.unwrap();
+
+    assert!(
+        error.contains("oops, not json"),
+        "unexpected error: {error}"
+    );
+    assert!(!error.contains("Finished in"), "unexpected error: {error}");
+}
+
+/// Yields its data, then fails instead of reaching EOF.
+struct FailingReader(&'static [u8]);
+
+impl tokio::io::AsyncRead for FailingReader {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        if self.0.is_empty() {
+            return std::task::Poll::Ready(Err(std::io::Error::other("pipe broke")));
+        }
+        buf.put_slice(self.0);
+        self.0 = &[];
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn io_and_decode_errors_are_categorized() {
+    let output = b"{\"type\":\"init\",\"session_id\":\"sess-1\"}\noops, not json\n";
+
+    let result = replay_reader(FailingReader(output), &GeminiOptions::default()).await;
+
+    assert!(!result.success);
+    assert_eq!(
+        result.errors,
+        [
+            ErrorEntry {
+                kind: ErrorEntryKind::JsonDecode,
+                message: "expected value at line 1 column 1: oops, not json".to_string(),
+                line: Some(2),
+            },
+            ErrorEntry {
+                kind: ErrorEntryKind::Io,
+                message: "pipe broke".to_string(),
+                line: None,
+            },
+        ]
+    );
+    let error = result.error.unwrap();
+    assert!(
+        error.contains("[json decode error @ line 2] expected value at line 1 column 1: oops, not json\n[io error] pipe broke"),
+        "unexpected error: {error}"
+    );
+}
+
+/// A completed turn with `garbage` undecodable lines among its events.
+#[cfg(unix)]
+fn stream(garbage: usize) -> String {
+    let mut stream = String::from("{\"type\":\"init\",\"session_id\":\"sess-1\"}\n");
+    for i in 0..garbage {
+        stream.push_str(&format!("<event {i}>\n"));
+    }
+    stream + "{\"type\":\"message\",\"role\":\"assistant\",\"content\":\"Hi\"}\n{\"type\":\"turn.completed\"}\n"
+}
+
+#[cfg(unix)]
+async fn run(garbage: usize, max_decode_errors: Option<usize>) -> gemini_mcp::GeminiResult {
+    common::install_fake_gemini();
+    let ws = common::workspace(&stream(garbage));
+    let options = GeminiOptions {
+        max_decode_errors,
+        ..Default::default()
+    };
+    execute_gemini("hi", &ws, &options).await.unwrap()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn stream_past_the_threshold_is_unparseable() {
+    let result = run(20, Some(5)).await;
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::StreamUnparseable));
+    assert_eq!(result.exit_path.as_deref(), Some("stream_unparseable"));
+    assert!(result.error.unwrap().contains("6 lines"));
+    assert!(result.agent_messages.is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn decode_errors_within_the_threshold_are_tolerated() {
+    let result = run(5, Some(5)).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.agent_messages.as_deref(), Some("Hi"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn decode_errors_are_unlimited_by_default() {
+    let result = run(100, None).await;
+
+    assert!(result.success, "{:?}", result.error);
+}
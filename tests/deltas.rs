@@ -0,0 +1,51 @@
+//! `stream_gemini`: assistant text arriving as deltas.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{stream_gemini, GeminiOptions};
+
+const STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"The answer"}
+{"type":"message","role":"user","content":"ignored"}
+{"type":"message","role":"assistant","content":" is "}
+{"type":"message","role":"assistant","content":"42.\n"}
+{"type":"turn.completed"}
+"#;
+
+#[tokio::test]
+async fn deltas_concatenate_to_the_final_text() {
+    common::install_fake_gemini();
+    let ws = common::workspace(STREAM);
+
+    let mut stream = stream_gemini("hi", &ws, GeminiOptions::default());
+    let mut deltas = Vec::new();
+    while let Some(delta) = stream.deltas.recv().await {
+        deltas.push(delta);
+    }
+    let result = stream.result.await.unwrap().unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(deltas, ["The answer", " is ", "42.\n"]);
+    assert_eq!(Some(deltas.concat()), result.agent_messages);
+}
+
+#[tokio::test]
+async fn deltas_stop_at_a_stop_sequence() {
+    common::install_fake_gemini();
+    let ws = common::workspace(STREAM);
+    let options = GeminiOptions {
+        stop_sequences: vec!["is 4".to_string()],
+        ..Default::default()
+    };
+
+    let mut stream = stream_gemini("hi", &ws, options);
+    let mut deltas = Vec::new();
+    while let Some(delta) = stream.deltas.recv().await {
+        deltas.push(delta);
+    }
+    let result = stream.result.await.unwrap().unwrap();
+
+    assert_eq!(Some(deltas.concat()), result.agent_messages);
+    assert_eq!(result.agent_messages.as_deref(), Some("The answer "));
+}
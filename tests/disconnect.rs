@@ -0,0 +1,58 @@
+//! Cleanup of gemini children when the client goes away.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use rmcp::model::CallToolRequestParam;
+use serde_json::json;
+use std::time::Duration;
+
+fn is_running(pid: i32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists.
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+#[tokio::test]
+async fn client_disconnect_kills_running_gemini() {
+    common::install_fake_gemini();
+    let ws = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(ws.join("hang"), "").unwrap();
+
+    let (client, server) = common::start(ServerConfig::default(), ()).await;
+    let peer = client.peer().clone();
+    let arguments = json!({"PROMPT": "hi", "cd": ws});
+    tokio::spawn(async move {
+        let _ = peer
+            .call_tool(CallToolRequestParam {
+                name: "gemini".into(),
+                arguments: arguments.as_object().cloned(),
+                task: None,
+            })
+            .await;
+    });
+
+    let pid_file = ws.join("pid");
+    let pid = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(pid) = std::fs::read_to_string(&pid_file) {
+                if let Ok(pid) = pid.trim().parse::<i32>() {
+                    return pid;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("gemini was not started");
+    assert!(is_running(pid));
+
+    drop(client);
+    tokio::time::timeout(Duration::from_secs(10), server)
+        .await
+        .expect("server did not shut down")
+        .unwrap()
+        .unwrap();
+
+    assert!(!is_running(pid), "gemini (pid {pid}) outlived the server");
+}
@@ -0,0 +1,63 @@
+//! Reporting the prompt gemini actually received.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn effective_prompt_shows_the_framing_of_a_split_prompt() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let prompt = format!("{}\n\n{}", "a".repeat(120), "b".repeat(120));
+    let client = common::connect(ServerConfig {
+        max_prompt_bytes: Some(235),
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "chunk_prompt": true, "return_effective_prompt": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let effective = result["effective_prompt"].as_str().unwrap();
+    assert!(
+        effective.starts_with("This is the last part (2 of 2) of a long prompt."),
+        "{effective}"
+    );
+    assert!(effective.ends_with(&"b".repeat(120)), "{effective}");
+    // It is what gemini was given
+    let args = common::gemini_args(&ws);
+    assert!(args.join("\n").contains(effective));
+}
+
+#[tokio::test]
+async fn effective_prompt_is_redacted_and_opt_in() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig {
+        redact_patterns: vec![regex::Regex::new(r"sk-[a-z0-9]+").unwrap()],
+        ..Default::default()
+    })
+    .await;
+    let prompt = "Use key sk-abc123 to call the API";
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": prompt, "cd": ws, "return_effective_prompt": true}),
+    )
+    .await;
+    assert_eq!(
+        result["effective_prompt"],
+        "Use key [REDACTED] to call the API"
+    );
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": prompt, "cd": ws})).await;
+    assert!(result.get("effective_prompt").is_none(), "{result}");
+}
@@ -0,0 +1,98 @@
+//! `--env-policy allowlist`: only allowlisted variables reach gemini.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::config::DEFAULT_ENV_ALLOWLIST;
+use gemini_mcp::{env_allowed, execute_gemini, GeminiOptions, ServerConfig};
+use serde_json::json;
+
+fn set_test_vars() {
+    std::env::set_var("ENV_POLICY_TEST_SECRET", "leaked");
+    std::env::set_var("ENV_POLICY_TEST_ALLOWED", "passed");
+    std::env::set_var("ENV_POLICY_TEST_PREFIXED_ONE", "matched");
+}
+
+#[tokio::test]
+async fn only_allowlisted_vars_reach_gemini() {
+    common::install_fake_gemini();
+    set_test_vars();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let options = GeminiOptions {
+        env_allowlist: Some(vec![
+            "PATH".to_string(),
+            "ENV_POLICY_TEST_ALLOWED".to_string(),
+            "ENV_POLICY_TEST_PREFIXED_*".to_string(),
+        ]),
+        request_id: Some("req-1".to_string()),
+        ..Default::default()
+    };
+
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(
+        common::gemini_env(&ws, "ENV_POLICY_TEST_ALLOWED").as_deref(),
+        Some("passed")
+    );
+    assert_eq!(
+        common::gemini_env(&ws, "ENV_POLICY_TEST_PREFIXED_ONE").as_deref(),
+        Some("matched")
+    );
+    assert_eq!(common::gemini_env(&ws, "ENV_POLICY_TEST_SECRET"), None);
+    assert_eq!(common::gemini_env(&ws, "HOME"), None);
+    // Set by the server itself, so passed regardless
+    assert_eq!(
+        common::gemini_env(&ws, gemini_mcp::REQUEST_ID_ENV).as_deref(),
+        Some("req-1")
+    );
+}
+
+#[tokio::test]
+async fn whole_environment_is_inherited_by_default() {
+    common::install_fake_gemini();
+    set_test_vars();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        common::gemini_env(&ws, "ENV_POLICY_TEST_SECRET").as_deref(),
+        Some("leaked")
+    );
+}
+
+#[tokio::test]
+async fn allowlist_policy_applies_to_tool_calls() {
+    common::install_fake_gemini();
+    set_test_vars();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig {
+        env_policy: "allowlist".parse().unwrap(),
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(common::gemini_env(&ws, "ENV_POLICY_TEST_SECRET"), None);
+    assert!(common::gemini_env(&ws, "PATH").is_some());
+}
+
+#[test]
+fn default_allowlist_keeps_what_gemini_needs() {
+    let allowlist: Vec<String> = DEFAULT_ENV_ALLOWLIST
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    assert!(env_allowed("PATH", &allowlist));
+    assert!(env_allowed("GEMINI_API_KEY", &allowlist));
+    assert!(env_allowed("GOOGLE_CLOUD_PROJECT", &allowlist));
+    assert!(env_allowed("LC_ALL", &allowlist));
+    assert!(!env_allowed("AWS_SECRET_ACCESS_KEY", &allowlist));
+    assert!(!env_allowed("PATHEXT_EXTRA", &allowlist));
+}
@@ -0,0 +1,145 @@
+//! `_seq`: numbering events so streamed and returned ones can be merged.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::sink::with_seq;
+use gemini_mcp::{replay_stream, GeminiOptions, ServerConfig};
+use rmcp::model::ProgressNotificationParam;
+use rmcp::service::NotificationContext;
+use rmcp::{ClientHandler, RoleClient};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Records the message of every progress notification.
+#[derive(Clone, Default)]
+struct ProgressRecorder(Arc<Mutex<Vec<String>>>);
+
+impl ClientHandler for ProgressRecorder {
+    async fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.0.lock().unwrap().extend(params.message);
+    }
+}
+
+fn seqs(events: &[serde_json::Value]) -> Vec<u64> {
+    events.iter().map(|e| e["_seq"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn streamed_and_returned_events_share_contiguous_seqs() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let recorder = ProgressRecorder::default();
+    let (client, _server) = common::start(ServerConfig::default(), recorder.clone()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "return_all_messages": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let returned = result["all_messages"].as_array().unwrap().clone();
+    assert_eq!(seqs(&returned), [1, 2, 3, 4]);
+
+    // Notifications are handled concurrently with the response
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.0.lock().unwrap().len() < returned.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let streamed: Vec<serde_json::Value> = recorder
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(streamed, returned);
+}
+
+/// Says the stop phrase on the second run, counting runs in `runs`.
+const DONE_ON_SECOND_RUN: &str = r#"n=$(($(cat runs 2>/dev/null || echo 0) + 1))
+echo $n > runs
+printf '%s\n' '{"type":"init","session_id":"sess-1"}' > stream.jsonl
+if [ $n -ge 2 ]; then
+  printf '%s\n' '{"type":"message","role":"assistant","content":"DONE"}' >> stream.jsonl
+else
+  printf '%s\n' '{"type":"message","role":"assistant","content":"Working"}' >> stream.jsonl
+fi
+printf '%s\n' '{"type":"turn.completed"}' >> stream.jsonl
+"#;
+
+#[tokio::test]
+async fn seqs_keep_counting_through_continuations() {
+    common::install_fake_gemini();
+    let ws = common::workspace("");
+    std::fs::write(ws.join("script.sh"), DONE_ON_SECOND_RUN).unwrap();
+    let recorder = ProgressRecorder::default();
+    let (client, _server) = common::start(ServerConfig::default(), recorder.clone()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "work", "cd": ws, "stop_phrase": "DONE", "return_all_messages": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        std::fs::read_to_string(ws.join("runs")).unwrap().trim(),
+        "2"
+    );
+    let returned = result["all_messages"].as_array().unwrap().clone();
+    assert_eq!(seqs(&returned), [1, 2, 3, 4, 5, 6]);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.0.lock().unwrap().len() < returned.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let streamed: Vec<serde_json::Value> = recorder
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(streamed, returned);
+}
+
+#[test]
+fn lines_that_are_not_events_are_not_counted() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+Loaded cached credentials.
+{"type":"message","role":"assistant","content":"hi"}
+{"type":"turn.completed"}
+"#;
+    let options = GeminiOptions {
+        return_all_messages: true,
+        ..Default::default()
+    };
+
+    let events = replay_stream(output, &options).all_messages.unwrap();
+
+    assert_eq!(seqs(&events), [1, 2, 3]);
+}
+
+#[test]
+fn seq_leads_the_raw_line() {
+    assert_eq!(
+        with_seq(r#"{"type":"init"}"#, 7),
+        r#"{"_seq":7,"type":"init"}"#
+    );
+    assert_eq!(with_seq("{}", 1), r#"{"_seq":1}"#);
+}
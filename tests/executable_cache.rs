@@ -0,0 +1,35 @@
+//! gemini is looked up in PATH once per PATH, not on every run.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, forget_gemini_executable, GeminiOptions};
+
+#[tokio::test]
+async fn lookup_is_performed_once_per_path() {
+    common::install_fake_gemini();
+    // A directory ahead of the fake gemini in PATH, empty for now
+    let impostor = common::install_gemini("ahead", "#!/bin/sh\necho impostor > impostor\n");
+    let parked = impostor.with_file_name("parked");
+    std::fs::rename(&impostor, &parked).unwrap();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let options = GeminiOptions::default();
+    assert!(execute_gemini("hi", &ws, &options).await.unwrap().success);
+
+    // Installed ahead of the first one without PATH changing, it is only
+    // found by a new lookup
+    std::fs::rename(&parked, &impostor).unwrap();
+    for _ in 0..3 {
+        assert!(execute_gemini("hi", &ws, &options).await.unwrap().success);
+    }
+    assert!(!ws.join("impostor").exists());
+
+    forget_gemini_executable();
+    let _ = execute_gemini("hi", &ws, &options).await;
+    assert!(ws.join("impostor").exists());
+
+    // A changed PATH is searched again
+    common::install_gemini("later", "#!/bin/sh\necho later > later\n");
+    let _ = execute_gemini("hi", &ws, &options).await;
+    assert!(ws.join("later").exists());
+}
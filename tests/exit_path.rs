@@ -0,0 +1,131 @@
+//! `exit_path`: which branch ended a gemini run.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions, GeminiResult, KillMode};
+use std::path::Path;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const INIT: &str = r#"{"type":"init","session_id":"sess-1"}"#;
+const MESSAGE: &str = r#"{"type":"message","role":"assistant","content":"Hi"}"#;
+
+/// A workspace printing `lines`, then sleeping if `hang`.
+fn workspace(lines: &[&str], hang: bool) -> std::path::PathBuf {
+    let ws = common::workspace(&(lines.join("\n") + "\n"));
+    if hang {
+        std::fs::write(ws.join("hang"), "").unwrap();
+    }
+    ws
+}
+
+async fn run(ws: &Path, options: GeminiOptions) -> GeminiResult {
+    common::install_fake_gemini();
+    execute_gemini("hi", ws, &options).await.unwrap()
+}
+
+fn exit_path(result: &GeminiResult) -> &str {
+    result.exit_path.as_deref().unwrap()
+}
+
+#[tokio::test]
+async fn turn_completed() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = run(&ws, GeminiOptions::default()).await;
+    assert_eq!(exit_path(&result), "turn_completed");
+}
+
+#[tokio::test]
+async fn eof_without_completion() {
+    let ws = workspace(&[INIT, MESSAGE], false);
+    let result = run(&ws, GeminiOptions::default()).await;
+    assert_eq!(exit_path(&result), "eof");
+}
+
+#[tokio::test]
+async fn timeout() {
+    let ws = workspace(&[INIT], true);
+    let options = GeminiOptions {
+        timeout: Some(Duration::from_millis(500)),
+        kill_mode: KillMode::Immediate,
+        ..Default::default()
+    };
+    let result = run(&ws, options).await;
+    assert_eq!(exit_path(&result), "timeout");
+}
+
+#[tokio::test]
+async fn first_event_timeout() {
+    let ws = workspace(&[], true);
+    let options = GeminiOptions {
+        first_event_timeout: Some(Duration::from_millis(300)),
+        ..Default::default()
+    };
+    let result = run(&ws, options).await;
+    assert_eq!(exit_path(&result), "first_event_timeout");
+}
+
+#[tokio::test]
+async fn cancelled() {
+    let ws = workspace(&[INIT], true);
+    let cancel = CancellationToken::new();
+    let options = GeminiOptions {
+        cancel: Some(cancel.clone()),
+        ..Default::default()
+    };
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel.cancel();
+    });
+    let result = run(&ws, options).await;
+    canceller.await.unwrap();
+    assert_eq!(exit_path(&result), "cancelled");
+}
+
+#[tokio::test]
+async fn stopped_early() {
+    let ws = workspace(&[INIT, MESSAGE, MESSAGE], false);
+    let options = GeminiOptions {
+        max_assistant_messages: Some(1),
+        ..Default::default()
+    };
+    let result = run(&ws, options).await;
+    assert_eq!(exit_path(&result), "stopped_early");
+}
+
+#[tokio::test]
+async fn stream_error() {
+    let ws = workspace(
+        &[
+            INIT,
+            r#"{"type":"error","severity":"error","message":"boom"}"#,
+        ],
+        false,
+    );
+    let options = GeminiOptions {
+        fail_fast_on_stream_error: true,
+        ..Default::default()
+    };
+    let result = run(&ws, options).await;
+    assert_eq!(exit_path(&result), "stream_error");
+}
+
+#[tokio::test]
+async fn no_completion() {
+    let ws = workspace(&[INIT, MESSAGE, MESSAGE, MESSAGE], false);
+    let options = GeminiOptions {
+        max_events_without_completion: Some(2),
+        ..Default::default()
+    };
+    let result = run(&ws, options).await;
+    assert_eq!(exit_path(&result), "no_completion");
+}
+
+#[tokio::test]
+async fn killed_after_wait_when_gemini_lingers_after_completing() {
+    let ws = workspace(&[INIT, MESSAGE, r#"{"type":"turn.completed"}"#], true);
+    let result = run(&ws, GeminiOptions::default()).await;
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(exit_path(&result), "turn_completed/killed_after_wait");
+}
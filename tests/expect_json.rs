@@ -0,0 +1,62 @@
+//! Extracting a JSON value from the reply with `expect_json`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+fn reply(content: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n",
+        r#"{"type":"init","session_id":"sess-1"}"#,
+        json!({"type": "message", "role": "assistant", "content": content}),
+        r#"{"type":"turn.completed"}"#
+    )
+}
+
+async fn run(content: &str, expect_json: bool) -> serde_json::Value {
+    common::install_fake_gemini();
+    let ws = common::workspace(&reply(content));
+    let client = common::connect(ServerConfig::default()).await;
+    common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "expect_json": expect_json}),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn last_fenced_block_is_parsed() {
+    let content = "Draft:\n```json\n{\"ok\": false}\n```\nFinal answer:\n```json\n{\"ok\": true, \"items\": [1, 2]}\n```\nDone.";
+    let result = run(content, true).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["result_json"], json!({"ok": true, "items": [1, 2]}));
+}
+
+#[tokio::test]
+async fn whole_reply_is_parsed_without_a_fence() {
+    let result = run("  [1, 2, 3]\n", true).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["result_json"], json!([1, 2, 3]));
+}
+
+#[tokio::test]
+async fn reply_without_json_fails() {
+    let result = run("I could not decide.", true).await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "invalid_json");
+    assert!(result.get("result_json").is_none(), "{result}");
+}
+
+#[tokio::test]
+async fn reply_is_not_parsed_by_default() {
+    let result = run("```json\n{}\n```", false).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("result_json").is_none(), "{result}");
+}
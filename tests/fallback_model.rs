@@ -0,0 +1,48 @@
+//! Switching to `fallback_model` when the first model is slow to respond.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// Stalls without output for `--model slow`, answers for any other model.
+const MODEL_GEMINI: &str = r#"#!/bin/sh
+case " $* " in *" --model slow "*) exec sleep 30;; esac
+echo '{"type":"init","session_id":"sess-1"}'
+echo '{"type":"message","role":"assistant","content":"fast answer"}'
+echo '{"type":"turn.completed"}'
+"#;
+
+#[tokio::test]
+async fn stalled_model_falls_back() {
+    common::install_gemini("fallback", MODEL_GEMINI);
+
+    let client = common::connect(ServerConfig::default()).await;
+    let ws = common::scratch_dir("ws");
+    let started = std::time::Instant::now();
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({
+            "PROMPT": "hi",
+            "cd": ws,
+            "model": "slow",
+            "fallback_model": "fast",
+            "first_event_timeout_secs": 1,
+        }),
+    )
+    .await;
+
+    assert!(
+        started.elapsed().as_secs() < 10,
+        "slow attempt was not killed"
+    );
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["effective_model"], "fast");
+    assert_eq!(result["agent_messages"], "fast answer");
+    assert!(result["warnings"][0]
+        .as_str()
+        .unwrap()
+        .contains("fell back to fast"));
+}
@@ -0,0 +1,120 @@
+//! Post-run steps apply however a request ends.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// Writes an artifact to `out`, like gemini's file tools would.
+const WRITE_ARTIFACT: &str = "mkdir -p out && printf 'draft\\n' > out/draft.md\n";
+
+async fn call_with(
+    ws: &std::path::Path,
+    config: ServerConfig,
+    extra: serde_json::Value,
+) -> serde_json::Value {
+    let client = common::connect(config).await;
+    let mut arguments = json!({
+        "PROMPT": "hi",
+        "cd": ws,
+        "REQUEST_ID": "req-1",
+        "OUTPUT_DIR": "out",
+        "return_effective_prompt": true,
+    });
+    arguments
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    common::call(client.peer(), "gemini", arguments).await
+}
+
+fn assert_finalized(result: &serde_json::Value, ws: &std::path::Path) {
+    assert_eq!(result["request_id"], "req-1", "{result}");
+    assert!(result["request_fingerprint"].is_string(), "{result}");
+    assert_eq!(result["effective_prompt"], "hi", "{result}");
+    assert_eq!(
+        result["artifacts"],
+        json!([ws.join("out/draft.md")]),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn finalize_runs_on_success() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("script.sh"), WRITE_ARTIFACT).unwrap();
+
+    let result = call_with(&ws, ServerConfig::default(), json!({})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_finalized(&result, &ws);
+}
+
+#[tokio::test]
+async fn finalize_runs_on_failure() {
+    common::install_fake_gemini();
+    let ws = common::workspace(r#"{"type":"message","role":"assistant","content":"no session"}"#);
+    std::fs::write(ws.join("script.sh"), WRITE_ARTIFACT).unwrap();
+
+    let result = call_with(&ws, ServerConfig::default(), json!({})).await;
+
+    assert_eq!(result["failure_kind"], "no_session_id", "{result}");
+    assert_finalized(&result, &ws);
+}
+
+#[tokio::test]
+async fn finalize_runs_on_timeout() {
+    common::install_fake_gemini();
+    let ws = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(ws.join("script.sh"), WRITE_ARTIFACT).unwrap();
+    std::fs::write(ws.join("hang"), "").unwrap();
+
+    let result = call_with(
+        &ws,
+        ServerConfig::default(),
+        json!({"TIMEOUT_SECS": 1, "kill_mode": "immediate"}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "timeout", "{result}");
+    assert_finalized(&result, &ws);
+}
+
+#[tokio::test]
+async fn finalize_runs_when_rejected_before_gemini_starts() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig {
+        session_resume_limit: Some(0),
+        ..Default::default()
+    })
+    .await;
+    let first = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    assert_eq!(first["success"], true, "{first}");
+    std::fs::remove_file(ws.join("args")).unwrap();
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "req-1", "SESSION_ID": "sess-1"}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "session_quota_exceeded", "{result}");
+    assert_eq!(result["request_id"], "req-1");
+    assert!(result["request_fingerprint"].is_string(), "{result}");
+    assert!(!ws.join("args").exists(), "gemini should not have run");
+}
+
+#[tokio::test]
+async fn finalize_runs_on_invalid_input() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = call_with(&ws, ServerConfig::default(), json!({"TIMEOUT_SECS": 0})).await;
+
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+    assert_eq!(result["request_id"], "req-1");
+}
@@ -0,0 +1,36 @@
+//! Surfacing why gemini ended the turn.
+
+use gemini_mcp::{replay_stream, GeminiOptions};
+
+fn completed_with(completion: &str) -> Option<String> {
+    let output = format!(
+        "{}\n{}\n{}\n",
+        r#"{"type":"init","session_id":"sess-1"}"#,
+        r#"{"type":"message","role":"assistant","content":"Partial answer"}"#,
+        completion
+    );
+    let result = replay_stream(&output, &GeminiOptions::default());
+    assert!(result.success, "{:?}", result.error);
+    result.finish_reason
+}
+
+#[test]
+fn finish_reason_is_read_from_the_completion_event() {
+    assert_eq!(
+        completed_with(r#"{"type":"turn.completed","finish_reason":"MAX_TOKENS"}"#).as_deref(),
+        Some("MAX_TOKENS")
+    );
+}
+
+#[test]
+fn stop_reason_spelling_is_accepted() {
+    assert_eq!(
+        completed_with(r#"{"type":"turn.completed","stop_reason":"SAFETY"}"#).as_deref(),
+        Some("SAFETY")
+    );
+}
+
+#[test]
+fn completion_without_a_reason_has_none() {
+    assert_eq!(completed_with(r#"{"type":"turn.completed"}"#), None);
+}
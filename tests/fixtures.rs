@@ -0,0 +1,44 @@
+//! Replays recorded gemini `stream-json` output and compares the parsed result
+//! against a snapshot.
+//!
+//! Fixtures live in `tests/fixtures/stream/<name>.jsonl` with the expected
+//! result next to them in `<name>.json`. Run with `UPDATE_SNAPSHOTS=1` to
+//! rewrite the snapshots after an intended change.
+
+use gemini_mcp::{replay_stream, GeminiOptions};
+use std::path::PathBuf;
+
+fn check_fixture(name: &str) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/stream");
+    let output = std::fs::read_to_string(dir.join(format!("{name}.jsonl"))).unwrap();
+    let snapshot_path = dir.join(format!("{name}.json"));
+
+    let result = replay_stream(&output, &GeminiOptions::default());
+    let actual = serde_json::to_value(&result).unwrap();
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let mut json = serde_json::to_string_pretty(&actual).unwrap();
+        json.push('\n');
+        std::fs::write(&snapshot_path, json).unwrap();
+        return;
+    }
+
+    let expected: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&snapshot_path).unwrap()).unwrap();
+    assert_eq!(actual, expected, "{name} no longer matches its snapshot");
+}
+
+#[test]
+fn tool_call_only_turn() {
+    check_fixture("tool_call_only");
+}
+
+#[test]
+fn multi_message_turn() {
+    check_fixture("multi_message");
+}
+
+#[test]
+fn error_turn() {
+    check_fixture("error_turn");
+}
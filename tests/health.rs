@@ -0,0 +1,59 @@
+//! Reporting the health of each subsystem with `gemini_health`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        api_keys: vec!["key-1".to_string(), "key-2".to_string()],
+        spend_budget: Some(1.0),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn every_subsystem_reports_a_status() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let health = common::call(client.peer(), "gemini_health", json!({})).await;
+    for subsystem in ["gemini", "auth", "concurrency", "errors", "spend"] {
+        assert_eq!(health[subsystem]["status"], "ok", "{subsystem}: {health}");
+    }
+    assert_eq!(health["status"], "ok", "{health}");
+    assert!(health["gemini"]["path"].is_string(), "{health}");
+    assert_eq!(health["gemini"]["compatible"], true);
+    assert_eq!(health["auth"]["method"], "key_pool");
+    assert_eq!(health["auth"]["keys"], 2);
+    assert_eq!(health["auth"]["available_keys"], 2);
+    assert_eq!(health["concurrency"]["active_runs"], 0);
+    assert_eq!(health["errors"]["calls"], 1);
+    assert_eq!(health["errors"]["error_rate"], 0.0);
+    assert_eq!(health["spend"]["budget"], 1.0);
+    assert_eq!(health["config"]["tool_name"], "gemini");
+}
+
+#[tokio::test]
+async fn repeated_failures_degrade_the_error_rate() {
+    common::install_fake_gemini();
+    let ws = common::workspace("");
+    let client = common::connect(config()).await;
+
+    for _ in 0..5 {
+        let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+        assert_eq!(result["success"], false, "{result}");
+    }
+
+    let health = common::call(client.peer(), "gemini_health", json!({})).await;
+    assert_eq!(health["errors"]["status"], "degraded", "{health}");
+    assert_eq!(health["errors"]["failures"], 5);
+    assert_eq!(health["errors"]["error_rate"], 1.0);
+    assert_eq!(health["status"], "degraded");
+}
@@ -0,0 +1,74 @@
+//! Rotating gemini API keys and skipping keys that ran out of quota.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{KeyPool, KeyRotation, ServerConfig};
+use serde_json::json;
+use std::time::Duration;
+
+/// Appends the key it was given to `keys`; `key-a` is out of quota.
+const QUOTA_GEMINI: &str = r#"#!/bin/sh
+echo "$GEMINI_API_KEY" >> keys
+echo '{"type":"init","session_id":"sess-1"}'
+if [ "$GEMINI_API_KEY" = key-a ]; then
+  echo '{"type":"error","severity":"error","message":"[API Error: Quota exceeded for quota metric]"}'
+  exit 1
+fi
+echo '{"type":"message","role":"assistant","content":"done"}'
+echo '{"type":"turn.completed"}'
+"#;
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|k| k.to_string()).collect()
+}
+
+#[test]
+fn keys_rotate_and_cooled_down_keys_are_skipped() {
+    let pool = KeyPool::new(
+        keys(&["a", "b", "c"]),
+        KeyRotation::RoundRobin,
+        Duration::from_secs(60),
+    )
+    .unwrap();
+    assert_eq!(
+        [pool.pick(), pool.pick(), pool.pick(), pool.pick()],
+        ["a", "b", "c", "a"]
+    );
+
+    pool.cool_down("b");
+    assert_eq!(pool.available(), 2);
+    assert_eq!([pool.pick(), pool.pick(), pool.pick()], ["c", "a", "c"]);
+
+    let lru = KeyPool::new(
+        keys(&["a", "b"]),
+        KeyRotation::LeastRecentlyUsed,
+        Duration::from_secs(60),
+    )
+    .unwrap();
+    assert_eq!([lru.pick(), lru.pick(), lru.pick()], ["a", "b", "a"]);
+}
+
+#[tokio::test]
+async fn quota_failure_moves_on_to_another_key() {
+    common::install_gemini("quota", QUOTA_GEMINI);
+
+    let config = ServerConfig {
+        api_keys: keys(&["key-a", "key-b"]),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+    let ws = common::scratch_dir("ws");
+
+    for _ in 0..2 {
+        let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+        assert_eq!(result["success"], true, "{result}");
+    }
+
+    // key-a failed once, then stayed cooled down for the second call
+    let used = std::fs::read_to_string(ws.join("keys")).unwrap();
+    assert_eq!(
+        used.lines().collect::<Vec<_>>(),
+        ["key-a", "key-b", "key-b"]
+    );
+}
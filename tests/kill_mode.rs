@@ -0,0 +1,38 @@
+//! `kill_mode`: killing a timed-out gemini right away or after a grace period.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiOptions, KillMode};
+use std::time::{Duration, Instant};
+
+/// Time a run of a gemini that never exits, timed out after a second.
+async fn timed_out_run(kill_mode: KillMode) -> Duration {
+    common::install_fake_gemini();
+    let ws = common::workspace("");
+    std::fs::write(ws.join("hang"), "").unwrap();
+    let options = GeminiOptions {
+        timeout: Some(Duration::from_secs(1)),
+        kill_mode,
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+    assert_eq!(result.failure_kind, Some(FailureKind::Timeout));
+    started.elapsed()
+}
+
+#[tokio::test]
+async fn immediate_mode_skips_the_wait() {
+    let elapsed = timed_out_run(KillMode::Immediate).await;
+
+    assert!(elapsed < Duration::from_secs(4), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn graceful_mode_waits_before_killing() {
+    let elapsed = timed_out_run(KillMode::Graceful).await;
+
+    assert!(elapsed >= Duration::from_secs(5), "{elapsed:?}");
+}
@@ -0,0 +1,115 @@
+//! `publish_resource`: the accumulating result as a subscribable MCP resource.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use rmcp::model::{
+    ReadResourceRequestParam, ResourceUpdatedNotificationParam, SubscribeRequestParam,
+};
+use rmcp::service::NotificationContext;
+use rmcp::{ClientHandler, RoleClient};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+const STREAM: &str = r#"{"type":"init","session_id":"sess-1","timestamp":"2025-10-02T09:20:41.000Z"}
+{"type":"message","role":"assistant","content":"Hello ","timestamp":"2025-10-02T09:20:41.150Z"}
+{"type":"message","role":"assistant","content":"world","timestamp":"2025-10-02T09:20:41.300Z"}
+{"type":"turn.completed","timestamp":"2025-10-02T09:20:41.450Z"}
+"#;
+
+const URI: &str = "gemini://results/req-live";
+
+/// Reads the resource on every update notification.
+#[derive(Clone, Default)]
+struct ResourceReader(Arc<Mutex<Vec<JoinHandle<serde_json::Value>>>>);
+
+impl ClientHandler for ResourceReader {
+    async fn on_resource_updated(
+        &self,
+        params: ResourceUpdatedNotificationParam,
+        context: NotificationContext<RoleClient>,
+    ) {
+        let peer = context.peer.clone();
+        self.0.lock().unwrap().push(tokio::spawn(async move {
+            let read = peer
+                .read_resource(ReadResourceRequestParam { uri: params.uri })
+                .await
+                .unwrap();
+            let text = match &read.contents[0] {
+                rmcp::model::ResourceContents::TextResourceContents { text, .. } => text.clone(),
+                other => panic!("unexpected contents {other:?}"),
+            };
+            serde_json::from_str(&text).unwrap()
+        }));
+    }
+}
+
+#[tokio::test]
+async fn resource_is_created_and_updated_as_the_stream_progresses() {
+    common::install_fake_gemini();
+    let ws = common::timed_workspace(STREAM, 1.0);
+    let reader = ResourceReader::default();
+    let (client, _server) = common::start(ServerConfig::default(), reader.clone()).await;
+    client
+        .peer()
+        .subscribe(SubscribeRequestParam { uri: URI.into() })
+        .await
+        .unwrap();
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "req-live", "publish_resource": true}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["resource_uri"], URI);
+
+    let handles: Vec<_> = std::mem::take(&mut *reader.0.lock().unwrap());
+    let mut snapshots = Vec::new();
+    for handle in handles {
+        snapshots.push(handle.await.unwrap());
+    }
+    // One update per event, then one for the final result
+    assert_eq!(snapshots.len(), 5, "{snapshots:?}");
+    let texts: Vec<&str> = snapshots
+        .iter()
+        .map(|s| s["agent_messages"].as_str().unwrap())
+        .collect();
+    assert!(texts.contains(&"Hello "), "{texts:?}");
+    assert!(
+        snapshots.iter().any(|s| s["done"] == false),
+        "{snapshots:?}"
+    );
+
+    let last = client
+        .peer()
+        .read_resource(ReadResourceRequestParam { uri: URI.into() })
+        .await
+        .unwrap();
+    let rmcp::model::ResourceContents::TextResourceContents { text, .. } = &last.contents[0] else {
+        panic!("expected text contents");
+    };
+    let last: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(last["done"], true);
+    assert_eq!(last["events"], 4);
+    assert_eq!(last["agent_messages"], "Hello world");
+    assert_eq!(last["result"]["agent_messages"], "Hello world");
+
+    let listed = client.peer().list_all_resources().await.unwrap();
+    assert!(listed.iter().any(|r| r.uri == URI));
+}
+
+#[tokio::test]
+async fn unpublished_calls_have_no_resource() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert!(result.get("resource_uri").is_none(), "{result}");
+    assert!(client.peer().list_all_resources().await.unwrap().is_empty());
+}
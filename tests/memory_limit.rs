@@ -0,0 +1,31 @@
+//! Killing gemini when it exceeds its memory limit.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiOptions};
+
+/// Starts a session, then tries to buffer 256 MiB in the shell itself.
+const HUNGRY_GEMINI: &str = r#"#!/bin/sh
+echo '{"type":"init","session_id":"sess-1"}'
+x=$(head -c 268435456 /dev/zero | tr '\0' x)
+echo '{"type":"message","role":"assistant","content":"done"}'
+echo '{"type":"turn.completed"}'
+"#;
+
+#[tokio::test]
+async fn exceeding_the_memory_limit_is_reported() {
+    common::install_gemini("hungry", HUNGRY_GEMINI);
+
+    let ws = common::scratch_dir("ws");
+    let options = GeminiOptions {
+        memory_limit_mb: Some(64),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::MemoryLimit));
+    assert!(result.error.unwrap().contains("64 MiB memory limit"));
+    assert_eq!(result.agent_messages, None);
+}
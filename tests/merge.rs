@@ -0,0 +1,72 @@
+//! Combining the results of several runs.
+
+use gemini_mcp::{FailureKind, GeminiResult, TokenUsage};
+
+fn usage(input_tokens: u64, output_tokens: u64) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: input_tokens + output_tokens,
+        thinking_tokens: None,
+    })
+}
+
+#[test]
+fn merging_a_success_and_a_failure() {
+    let success = GeminiResult {
+        success: true,
+        session_id: Some("sess-1".to_string()),
+        agent_messages: Some("First part done.".to_string()),
+        all_messages: Some(vec![serde_json::json!({"type": "init"})]),
+        usage: usage(100, 20),
+        turns: Some(2),
+        warnings: vec!["resumed from another cd".to_string()],
+        ..Default::default()
+    };
+    let failure = GeminiResult {
+        success: false,
+        agent_messages: Some("Second part started".to_string()),
+        all_messages: Some(vec![serde_json::json!({"type": "error"})]),
+        usage: usage(50, 5),
+        error: Some("Process timeout. ".to_string()),
+        failure_kind: Some(FailureKind::Timeout),
+        ..Default::default()
+    };
+
+    let merged = GeminiResult::merge(&[success, failure]);
+
+    assert!(!merged.success);
+    assert_eq!(
+        merged.agent_messages.as_deref(),
+        Some("First part done.\n\nSecond part started")
+    );
+    assert_eq!(
+        merged.all_messages,
+        Some(vec![
+            serde_json::json!({"type": "init"}),
+            serde_json::json!({"type": "error"}),
+        ])
+    );
+    assert_eq!(merged.usage, usage(150, 25));
+    assert_eq!(merged.turns, Some(2));
+    assert_eq!(merged.session_id.as_deref(), Some("sess-1"));
+    assert_eq!(merged.warnings, ["resumed from another cd"]);
+    assert_eq!(merged.error.as_deref(), Some("Process timeout. "));
+    assert_eq!(merged.failure_kind, Some(FailureKind::Timeout));
+}
+
+#[test]
+fn merging_successes_succeeds() {
+    let ok = GeminiResult {
+        success: true,
+        agent_messages: Some("ok".to_string()),
+        ..Default::default()
+    };
+
+    let merged = GeminiResult::merge(&[ok.clone(), ok]);
+
+    assert!(merged.success);
+    assert_eq!(merged.agent_messages.as_deref(), Some("ok\n\nok"));
+    assert_eq!(merged.error, None);
+    assert_eq!(merged.usage, None);
+}
@@ -0,0 +1,39 @@
+//! Joining collected message chunks.
+
+use gemini_mcp::{replay_stream, GeminiOptions};
+
+/// Whole messages without the whitespace gemini's deltas carry.
+const WHOLE_MESSAGES: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"hello"}
+{"type":"message","role":"assistant","content":"world"}
+{"type":"turn.completed"}
+"#;
+
+fn joined(output: &str, message_join: &str) -> String {
+    let options = GeminiOptions {
+        message_join: message_join.to_string(),
+        ..Default::default()
+    };
+    replay_stream(output, &options).agent_messages.unwrap()
+}
+
+#[test]
+fn whole_messages_merge_without_a_separator() {
+    assert_eq!(joined(WHOLE_MESSAGES, ""), "helloworld");
+    assert_eq!(joined(WHOLE_MESSAGES, " "), "hello world");
+    assert_eq!(joined(WHOLE_MESSAGES, "\n"), "hello\nworld");
+}
+
+#[test]
+fn streamed_deltas_join_verbatim_by_default() {
+    let deltas = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Let me look.","delta":true}
+{"type":"message","role":"assistant","content":" The directory contains","delta":true}
+{"type":"message","role":"assistant","content":" `src/`.","delta":true}
+{"type":"turn.completed"}
+"#;
+    assert_eq!(
+        joined(deltas, ""),
+        "Let me look. The directory contains `src/`."
+    );
+}
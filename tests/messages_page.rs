@@ -0,0 +1,60 @@
+//! Paging through a stored transcript with `gemini_messages_page`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn stored_transcript_is_paged_through() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "req-1", "paginate_messages": true}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("all_messages").is_none(), "{result}");
+
+    let page = |offset: u64| {
+        common::call(
+            client.peer(),
+            "gemini_messages_page",
+            json!({"request_id": "req-1", "offset": offset, "limit": 3}),
+        )
+    };
+    let first = page(0).await;
+    assert_eq!(first["request_id"], "req-1");
+    assert_eq!(first["offset"], 0);
+    assert_eq!(first["total"], 4);
+    assert_eq!(first["messages"].as_array().unwrap().len(), 3);
+    assert_eq!(first["messages"][0]["type"], "init");
+    assert_eq!(first["next_offset"], 3);
+
+    let last = page(3).await;
+    assert_eq!(last["offset"], 3);
+    assert_eq!(last["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(last["messages"][0]["type"], "turn.completed");
+    assert!(last.get("next_offset").is_none(), "{last}");
+}
+
+#[tokio::test]
+async fn unknown_request_id_is_rejected() {
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = client
+        .peer()
+        .call_tool(rmcp::model::CallToolRequestParam {
+            name: "gemini_messages_page".into(),
+            arguments: json!({"request_id": "missing"}).as_object().cloned(),
+            task: None,
+        })
+        .await;
+
+    assert!(result.is_err(), "{result:?}");
+}
@@ -0,0 +1,45 @@
+//! Rejecting suspiciously short replies with `min_output_chars`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+const SHORT_REPLY: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":" ok\n"}
+{"type":"turn.completed"}
+"#;
+
+#[tokio::test]
+async fn short_reply_fails_under_the_threshold() {
+    common::install_fake_gemini();
+    let ws = common::workspace(SHORT_REPLY);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "min_output_chars": 10}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "output_too_short");
+    assert_eq!(result["agent_messages"], " ok\n");
+    assert!(
+        result["error"].as_str().unwrap().contains("2 characters"),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn short_reply_succeeds_without_a_threshold() {
+    common::install_fake_gemini();
+    let ws = common::workspace(SHORT_REPLY);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+}
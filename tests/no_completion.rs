@@ -0,0 +1,72 @@
+//! Ending runs whose stream never completes the turn.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiOptions, KillMode};
+use std::time::{Duration, Instant};
+
+/// An init event followed by `messages` assistant messages.
+fn stream(messages: usize) -> String {
+    let mut stream = r#"{"type":"init","session_id":"sess-1"}"#.to_string();
+    for i in 0..messages {
+        stream.push_str(&format!(
+            "\n{{\"type\":\"message\",\"role\":\"assistant\",\"content\":\"{i} \"}}"
+        ));
+    }
+    stream + "\n"
+}
+
+#[tokio::test]
+async fn never_completing_stream_ends_after_the_event_limit() {
+    common::install_fake_gemini();
+    let ws = common::workspace(&stream(50));
+    std::fs::write(ws.join("hang"), "").unwrap();
+
+    let options = GeminiOptions {
+        max_events_without_completion: Some(10),
+        timeout: Some(Duration::from_secs(30)),
+        kill_mode: KillMode::Immediate,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::NoCompletion));
+    assert!(result.error.unwrap().contains("11 events"));
+    assert_eq!(result.session_id.as_deref(), Some("sess-1"));
+}
+
+#[tokio::test]
+async fn completing_stream_within_the_limit_succeeds() {
+    common::install_fake_gemini();
+    let ws = common::workspace(&(stream(5) + "{\"type\":\"turn.completed\"}\n"));
+
+    let options = GeminiOptions {
+        max_events_without_completion: Some(10),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.agent_messages.as_deref(), Some("0 1 2 3 4 "));
+}
+
+#[tokio::test]
+async fn trailing_events_after_completion_do_not_count() {
+    common::install_fake_gemini();
+    let trailing = "{\"type\":\"status\",\"message\":\"flushing\"}\n".repeat(20);
+    let ws = common::workspace(&(stream(2) + "{\"type\":\"turn.completed\"}\n" + &trailing));
+
+    let options = GeminiOptions {
+        max_events_without_completion: Some(10),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.failure_kind, None);
+    assert_eq!(result.agent_messages.as_deref(), Some("0 1 "));
+}
@@ -0,0 +1,64 @@
+//! Workspace and include paths that are not valid UTF-8.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiError, GeminiOptions, NonUtf8Paths};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// A workspace named `ws-\xff` with an `out-\xfe` directory inside it.
+fn non_utf8_workspace() -> (PathBuf, PathBuf) {
+    let ws = common::scratch_dir("non-utf8").join(OsStr::from_bytes(b"ws-\xff"));
+    let out = ws.join(OsStr::from_bytes(b"out-\xfe"));
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(ws.join("stream.jsonl"), common::DEFAULT_STREAM).unwrap();
+    (ws, out)
+}
+
+#[tokio::test]
+async fn non_utf8_paths_are_passed_through_unchanged() {
+    common::install_fake_gemini();
+    let (ws, out) = non_utf8_workspace();
+
+    let options = GeminiOptions {
+        output_dir: Some(out.clone()),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    let args = std::fs::read(ws.join("args")).unwrap();
+    let args: Vec<&[u8]> = args.split(|b| *b == b'\n').collect();
+    let at = args
+        .iter()
+        .position(|arg| *arg == b"--include-directories")
+        .unwrap();
+    assert_eq!(args[at + 1], out.as_os_str().as_bytes());
+}
+
+#[tokio::test]
+async fn non_utf8_paths_are_rejected_when_configured() {
+    common::install_fake_gemini();
+    let (ws, out) = non_utf8_workspace();
+
+    let options = GeminiOptions {
+        non_utf8_paths: NonUtf8Paths::Reject,
+        ..Default::default()
+    };
+    let error = execute_gemini("hi", &ws, &options).await.unwrap_err();
+    assert!(matches!(&error, GeminiError::NonUtf8Path(path) if *path == ws));
+    assert_eq!(error.failure_kind(), FailureKind::InvalidInput);
+    assert!(error.to_string().contains(r"ws-\xFF"), "{error}");
+    assert!(!ws.join("args").exists());
+
+    // A valid workspace with a non-UTF-8 include directory is rejected too
+    let valid = common::workspace(common::DEFAULT_STREAM);
+    let options = GeminiOptions {
+        output_dir: Some(out.clone()),
+        ..options
+    };
+    let error = execute_gemini("hi", &valid, &options).await.unwrap_err();
+    assert!(matches!(&error, GeminiError::NonUtf8Path(path) if *path == out));
+}
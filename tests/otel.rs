@@ -0,0 +1,85 @@
+//! OpenTelemetry export of invocation spans (requires `--features otel`).
+#![cfg(feature = "otel")]
+
+use gemini_mcp::telemetry::{self, INVOCATION_SPAN};
+use gemini_mcp::{GeminiResult, TokenUsage};
+use opentelemetry::{KeyValue, Value};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use rmcp::model::Meta;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+/// Keeps the exported spans, without the SDK's `testing` feature.
+#[derive(Debug, Clone, Default)]
+struct RecordingExporter(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for RecordingExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.0.lock().unwrap().extend(batch);
+        Ok(())
+    }
+}
+
+#[test]
+fn invocation_span_is_exported_with_caller_as_parent() {
+    let exporter = RecordingExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+
+    let mut meta = Meta::new();
+    meta.0.insert(
+        "traceparent".to_string(),
+        format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01").into(),
+    );
+    let result = GeminiResult {
+        success: true,
+        effective_model: Some("gemini-2.5-pro".to_string()),
+        usage: Some(TokenUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            total_tokens: 15,
+            thinking_tokens: None,
+        }),
+        ..Default::default()
+    };
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = telemetry::invocation_span(&meta);
+        telemetry::record_result(&span, &result, Duration::from_millis(42));
+    });
+    provider.force_flush().unwrap();
+
+    let spans = exporter.0.lock().unwrap().clone();
+    let span = spans
+        .iter()
+        .find(|s| s.name == INVOCATION_SPAN)
+        .expect("invocation span was not exported");
+
+    assert_eq!(span.span_context.trace_id().to_string(), TRACE_ID);
+    assert_eq!(span.parent_span_id.to_string(), PARENT_SPAN_ID);
+
+    let attribute = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv: &&KeyValue| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(
+        attribute("gen_ai.request.model"),
+        Some(Value::from("gemini-2.5-pro"))
+    );
+    assert_eq!(attribute("success"), Some(Value::Bool(true)));
+    assert_eq!(attribute("duration_ms"), Some(Value::I64(42)));
+    assert_eq!(attribute("gen_ai.usage.input_tokens"), Some(Value::I64(10)));
+    assert_eq!(attribute("gen_ai.usage.output_tokens"), Some(Value::I64(5)));
+    assert_eq!(attribute("gen_ai.usage.total_tokens"), Some(Value::I64(15)));
+}
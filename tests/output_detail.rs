@@ -0,0 +1,66 @@
+//! `OUTPUT_DETAIL`: text, a compact event summary, or every event.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+const STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"tool_use","tool_name":"read_file","tool_id":"t1"}
+{"type":"message","role":"assistant","content":"The answer is a fairly long sentence that goes on well past the eighty character preview limit."}
+{"type":"turn.completed"}
+"#;
+
+async fn call_with_detail(detail: &str) -> serde_json::Value {
+    common::install_fake_gemini();
+    let ws = common::workspace(STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+    common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "OUTPUT_DETAIL": detail}),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn summary_lists_each_event_compactly() {
+    let result = call_with_detail("summary").await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        result["event_summary"],
+        json!([
+            {"type": "init"},
+            {"type": "tool_use"},
+            {
+                "type": "message",
+                "role": "assistant",
+                "preview": "The answer is a fairly long sentence that goes on well past the eighty character…"
+            },
+            {"type": "turn.completed"},
+        ])
+    );
+    assert!(result.get("all_messages").is_none(), "{result}");
+}
+
+#[tokio::test]
+async fn full_returns_every_event() {
+    let result = call_with_detail("full").await;
+
+    assert_eq!(result["all_messages"].as_array().unwrap().len(), 4);
+    assert!(result.get("event_summary").is_none(), "{result}");
+}
+
+#[tokio::test]
+async fn text_returns_neither() {
+    let result = call_with_detail("text").await;
+
+    assert!(result["agent_messages"]
+        .as_str()
+        .unwrap()
+        .starts_with("The answer"));
+    assert!(result.get("all_messages").is_none(), "{result}");
+    assert!(result.get("event_summary").is_none(), "{result}");
+}
@@ -0,0 +1,58 @@
+//! Directing generated files to `OUTPUT_DIR`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn output_dir_is_created_passed_and_reported() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(
+        ws.join("script.sh"),
+        "mkdir out/reports && printf '# Report\\n' > out/reports/summary.md\n",
+    )
+    .unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "write a report", "cd": ws, "OUTPUT_DIR": "out"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let out = ws.join("out");
+    let args = common::gemini_args(&ws);
+    let flag = args
+        .iter()
+        .position(|a| a == "--include-directories")
+        .expect("--include-directories not passed");
+    assert_eq!(args[flag + 1], out.to_string_lossy());
+    assert_eq!(
+        result["artifacts"],
+        json!([out.join("reports/summary.md")]),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn output_dir_must_not_be_a_file() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("out"), "").unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "OUTPUT_DIR": "out"}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(!ws.join("args").exists(), "gemini should not have run");
+}
@@ -0,0 +1,62 @@
+//! Decoding gemini's output from UTF-8 or a configured encoding.
+
+mod common;
+
+use gemini_mcp::{replay_reader, GeminiOptions, OutputEncoding};
+
+/// "café" in windows-1252, where é is the single byte 0xE9.
+const LATIN1_STREAM: &[u8] = b"{\"type\":\"init\",\"session_id\":\"sess-1\"}\n\
+{\"type\":\"message\",\"role\":\"assistant\",\"content\":\"caf\xe9\"}\n\
+{\"type\":\"turn.completed\"}\n";
+
+fn options(encoding: &str) -> GeminiOptions {
+    GeminiOptions {
+        output_encoding: encoding.parse().unwrap(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn non_utf8_bytes_are_replaced_when_decoding_utf8() {
+    let result = replay_reader(LATIN1_STREAM, &GeminiOptions::default()).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.agent_messages.as_deref(), Some("caf\u{FFFD}"));
+}
+
+#[tokio::test]
+async fn configured_encoding_decodes_the_output() {
+    let result = replay_reader(LATIN1_STREAM, &options("windows-1252")).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.agent_messages.as_deref(), Some("café"));
+}
+
+#[test]
+fn encodings_that_break_line_splitting_are_rejected() {
+    assert!("utf-16le".parse::<OutputEncoding>().is_err());
+    assert!("no-such-encoding".parse::<OutputEncoding>().is_err());
+    assert!("gbk".parse::<OutputEncoding>().is_ok());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn gemini_is_asked_for_utf8_only_when_decoding_utf8() {
+    use gemini_mcp::execute_gemini;
+
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        common::gemini_env(&ws, "PYTHONIOENCODING").as_deref(),
+        Some("utf-8")
+    );
+
+    execute_gemini("hi", &ws, &options("windows-1252"))
+        .await
+        .unwrap();
+    assert_eq!(common::gemini_env(&ws, "PYTHONIOENCODING"), None);
+}
@@ -0,0 +1,83 @@
+//! `--outside-writes`: file edits that resolve outside the workspace.
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiOptions, OutsideWrites};
+use std::path::Path;
+
+fn stream(path: &str) -> String {
+    format!(
+        r#"{{"type":"init","session_id":"sess-1"}}
+{{"type":"tool_use","tool_name":"write_file","tool_id":"t1","parameters":{{"file_path":"{path}","content":"x"}}}}
+{{"type":"tool_result","tool_id":"t1","status":"success"}}
+{{"type":"message","role":"assistant","content":"done"}}
+{{"type":"turn.completed"}}
+"#
+    )
+}
+
+async fn run(
+    path: &str,
+    outside_writes: OutsideWrites,
+) -> (gemini_mcp::GeminiResult, std::path::PathBuf) {
+    common::install_fake_gemini();
+    let ws = common::workspace(&stream(path));
+    let options = GeminiOptions {
+        outside_writes,
+        ..Default::default()
+    };
+    (execute_gemini("hi", &ws, &options).await.unwrap(), ws)
+}
+
+#[tokio::test]
+async fn write_outside_cd_fails_the_call() {
+    let (result, ws) = run("../escape.txt", OutsideWrites::Fail).await;
+
+    assert!(!result.success);
+    assert_eq!(
+        result.failure_kind,
+        Some(FailureKind::WriteOutsideWorkspace)
+    );
+    let escaped = ws
+        .canonicalize()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("escape.txt");
+    assert_eq!(result.outside_writes, vec![escaped]);
+    assert!(result.error.unwrap().contains("escape.txt"));
+}
+
+#[tokio::test]
+async fn warn_reports_without_failing() {
+    let (result, _) = run("/etc/gemini-escape.txt", OutsideWrites::Warn).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(
+        result.outside_writes,
+        vec![Path::new("/etc")
+            .canonicalize()
+            .unwrap()
+            .join("gemini-escape.txt")]
+    );
+    assert!(result
+        .warnings
+        .iter()
+        .any(|w| w.contains("outside the workspace")));
+}
+
+#[tokio::test]
+async fn writes_inside_cd_are_allowed() {
+    let (result, _) = run("src/../notes.txt", OutsideWrites::Fail).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.outside_writes.is_empty());
+}
+
+#[tokio::test]
+async fn outside_writes_are_ignored_by_default() {
+    let (result, _) = run("../escape.txt", OutsideWrites::default()).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.outside_writes.is_empty());
+}
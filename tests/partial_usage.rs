@@ -0,0 +1,68 @@
+//! Token usage of runs that did not finish is still reported and priced.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::retry::RetryPolicy;
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// Usage reported before gemini stalls.
+const STALLED_STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Working","usage":{"input_tokens":300,"output_tokens":100,"total_tokens":400}}
+{"type":"message","role":"assistant","content":" on it","usage":{"input_tokens":0,"output_tokens":100,"total_tokens":100}}
+"#;
+
+#[tokio::test]
+async fn timed_out_run_reports_usage_so_far() {
+    common::install_fake_gemini();
+    let ws = common::workspace(STALLED_STREAM);
+    std::fs::write(ws.join("hang"), "").unwrap();
+    let client = common::connect(ServerConfig {
+        model_costs: vec!["*=1".parse().unwrap()],
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "TIMEOUT_SECS": 1, "kill_mode": "immediate"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "timeout");
+    assert_eq!(
+        result["usage"],
+        json!({"input_tokens": 300, "output_tokens": 200, "total_tokens": 500})
+    );
+    assert_eq!(result["cost"], 0.5);
+    let metrics = common::call(client.peer(), "gemini_metrics", json!({})).await;
+    assert_eq!(metrics["spend"]["total"], 0.5);
+}
+
+#[tokio::test]
+async fn retried_attempts_count_towards_usage() {
+    common::install_fake_gemini();
+    // No session id, so the run fails before a session and is retried
+    let ws = common::workspace(
+        r#"{"type":"message","role":"assistant","content":"x","usage":{"input_tokens":10,"output_tokens":5,"total_tokens":15}}
+"#,
+    );
+    let client = common::connect(ServerConfig {
+        retry: RetryPolicy {
+            attempts: 2,
+            base_ms: 1,
+            max_ms: 1,
+            jitter: false,
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["failure_kind"], "no_session_id", "{result}");
+    assert_eq!(result["usage"]["total_tokens"], 45);
+}
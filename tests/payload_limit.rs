@@ -0,0 +1,80 @@
+//! Capping the response size and paging through the overflow.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn oversized_response_is_capped_and_the_rest_is_pageable() {
+    common::install_fake_gemini();
+    let text: String = (0..600).map(|i| format!("line {i} \"quoted\"\n")).collect();
+    let stream = format!(
+        "{}\n{}\n{}\n",
+        json!({"type": "init", "session_id": "sess-1"}),
+        json!({"type": "message", "role": "assistant", "content": text}),
+        json!({"type": "turn.completed"}),
+    );
+    let ws = common::workspace(&stream);
+    let config = ServerConfig {
+        max_payload_bytes: Some(2000),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let raw = common::call_raw(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "return_all_messages": true}),
+    )
+    .await;
+    let body = &raw.content[0].as_text().unwrap().text;
+    assert!(body.len() <= 2000, "first page is {} bytes", body.len());
+
+    let first: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(first["success"], true);
+    assert!(first.get("all_messages").is_none());
+    let token = first["continuation_token"].as_str().unwrap();
+    assert_eq!(token, first["request_id"]);
+
+    let mut events = Vec::new();
+    let mut offset = Some(0);
+    while let Some(from) = offset {
+        let page = common::call(
+            client.peer(),
+            "gemini_messages_page",
+            json!({"request_id": token, "offset": from, "limit": 1}),
+        )
+        .await;
+        events.extend(page["messages"].as_array().unwrap().clone());
+        offset = page["next_offset"].as_u64();
+    }
+
+    // The stored events come first, then the rest of the text
+    assert_eq!(events[0]["type"], "init");
+    let rest: String = events
+        .iter()
+        .filter(|e| e["type"] == "agent_messages")
+        .map(|e| e["content"].as_str().unwrap())
+        .collect();
+    let kept = first["agent_messages"].as_str().unwrap();
+    assert!(!rest.is_empty());
+    assert_eq!(format!("{kept}{rest}"), text);
+}
+
+#[tokio::test]
+async fn small_responses_are_untouched() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let config = ServerConfig {
+        max_payload_bytes: Some(2000),
+        ..Default::default()
+    };
+    let client = common::connect(config).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["agent_messages"], "Hello world");
+    assert!(result.get("continuation_token").is_none());
+}
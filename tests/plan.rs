@@ -0,0 +1,55 @@
+//! Parsing gemini's plan events into a structured plan.
+
+use gemini_mcp::{replay_stream, GeminiOptions, Plan, PlanStep};
+
+fn step(description: &str, status: Option<&str>) -> PlanStep {
+    PlanStep {
+        description: description.to_string(),
+        status: status.map(str::to_string),
+    }
+}
+
+#[test]
+fn latest_plan_event_becomes_the_plan() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"plan","steps":["Read main.rs","Fix the bug"]}
+{"type":"message","role":"assistant","content":"Starting."}
+{"type":"plan.update","steps":[{"description":"Read main.rs","status":"done"},{"title":"Fix the bug","status":"in_progress"},{"content":"Run the tests"},42]}
+{"type":"turn.completed"}
+"#;
+    let options = GeminiOptions {
+        return_all_messages: true,
+        ..Default::default()
+    };
+
+    let result = replay_stream(output, &options);
+
+    assert!(result.success);
+    assert_eq!(
+        result.plan,
+        Some(Plan {
+            steps: vec![
+                step("Read main.rs", Some("done")),
+                step("Fix the bug", Some("in_progress")),
+                step("Run the tests", None),
+            ],
+        })
+    );
+    assert_eq!(result.agent_messages.as_deref(), Some("Starting."));
+    // The raw plan events are kept
+    let all = result.all_messages.unwrap();
+    assert!(all.iter().any(|e| e["type"] == "plan"));
+    assert!(all.iter().any(|e| e["type"] == "plan.update"));
+}
+
+#[test]
+fn stream_without_plan_events_has_no_plan() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Done."}
+{"type":"turn.completed"}
+"#;
+
+    let result = replay_stream(output, &GeminiOptions::default());
+
+    assert_eq!(result.plan, None);
+}
@@ -0,0 +1,63 @@
+//! Piping the reply through a server-configured `post_process` command.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        post_processors: vec![
+            "upper=tr a-z A-Z".parse().unwrap(),
+            "reject=echo 'not formatted' >&2; exit 3".parse().unwrap(),
+        ],
+        ..Default::default()
+    }
+}
+
+async fn run(config: ServerConfig, post_process: &str) -> serde_json::Value {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config).await;
+    common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "post_process": post_process}),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn output_is_transformed() {
+    let result = run(config(), "upper").await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["agent_messages"], "HELLO WORLD");
+    assert_eq!(result["post_process_status"], 0);
+}
+
+#[tokio::test]
+async fn failing_command_fails_the_call() {
+    let result = run(config(), "reject").await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "post_process_failed");
+    assert_eq!(result["post_process_status"], 3);
+    assert!(
+        result["error"].as_str().unwrap().contains("not formatted"),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn unconfigured_command_is_rejected() {
+    let result = run(ServerConfig::default(), "upper").await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(
+        result["error"].as_str().unwrap().contains("not configured"),
+        "{result}"
+    );
+}
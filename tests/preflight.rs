@@ -0,0 +1,33 @@
+//! Checking for gemini in PATH before serving, fatally with `require_gemini`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::server::preflight;
+use gemini_mcp::{forget_gemini_executable, ServerConfig};
+
+fn config(require_gemini: bool) -> ServerConfig {
+    ServerConfig {
+        require_gemini,
+        ..Default::default()
+    }
+}
+
+// One test, as it changes PATH for the whole process
+#[test]
+fn missing_gemini_is_fatal_only_when_required() {
+    let original = std::env::var_os("PATH").unwrap_or_default();
+    std::env::set_var("PATH", common::scratch_dir("empty-bin"));
+    forget_gemini_executable();
+
+    let missing = preflight(&config(true)).unwrap_err();
+    assert!(missing.to_string().contains("gemini"), "{missing}");
+    assert!(preflight(&config(false)).is_ok());
+
+    std::env::set_var("PATH", original);
+    common::install_fake_gemini();
+    forget_gemini_executable();
+
+    assert!(preflight(&config(true)).is_ok());
+    assert!(preflight(&config(false)).is_ok());
+}
@@ -0,0 +1,36 @@
+//! `pretty`: indented JSON responses for humans reading them.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn body(arguments: serde_json::Value) -> String {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let mut arguments = arguments;
+    arguments["PROMPT"] = json!("hi");
+    arguments["cd"] = json!(ws);
+    let client = common::connect(ServerConfig::default()).await;
+    let raw = common::call_raw(client.peer(), "gemini", arguments).await;
+    raw.content[0].as_text().unwrap().text.clone()
+}
+
+#[tokio::test]
+async fn pretty_output_is_indented() {
+    let body = body(json!({"pretty": true})).await;
+
+    assert!(body.starts_with("{\n  \""), "{body}");
+    assert!(body.contains("\n  \"success\": true"), "{body}");
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(result["agent_messages"], "Hello world");
+}
+
+#[tokio::test]
+async fn output_is_compact_by_default() {
+    let body = body(json!({})).await;
+
+    assert!(!body.contains('\n'), "{body}");
+    assert!(body.contains("\"success\":true"), "{body}");
+}
@@ -0,0 +1,129 @@
+//! gemini processes are killed on every way a run can end.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions, GeminiProcess, KillMode};
+use std::path::Path;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+fn is_running(pid: i32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists.
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+/// A workspace in which the fake gemini prints `stream` and then hangs.
+fn hanging_workspace(stream: &str) -> std::path::PathBuf {
+    let ws = common::workspace(stream);
+    std::fs::write(ws.join("hang"), "").unwrap();
+    ws
+}
+
+/// The pid the hanging fake gemini wrote to `pid`.
+async fn hung_pid(ws: &Path) -> i32 {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(pid) = std::fs::read_to_string(ws.join("pid")) {
+                if let Ok(pid) = pid.trim().parse() {
+                    return pid;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("gemini did not start")
+}
+
+/// Wait for `pid` to be gone, as reaping after a kill may happen in the background.
+async fn assert_exits(pid: i32) {
+    let exited = tokio::time::timeout(Duration::from_secs(5), async {
+        while is_running(pid) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(exited.is_ok(), "gemini (pid {pid}) is still running");
+}
+
+#[tokio::test]
+async fn completed_run_kills_a_lingering_process() {
+    common::install_fake_gemini();
+    let ws = hanging_workspace(common::DEFAULT_STREAM);
+
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    assert!(result.success);
+    assert_exits(hung_pid(&ws).await).await;
+}
+
+#[tokio::test]
+async fn timed_out_run_kills_the_process() {
+    common::install_fake_gemini();
+    let ws = hanging_workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    let options = GeminiOptions {
+        timeout: Some(Duration::from_millis(500)),
+        kill_mode: KillMode::Immediate,
+        ..Default::default()
+    };
+
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(!result.success);
+    assert_exits(hung_pid(&ws).await).await;
+}
+
+#[tokio::test]
+async fn cancelled_run_kills_the_process() {
+    common::install_fake_gemini();
+    let ws = hanging_workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    let cancel = CancellationToken::new();
+    let options = GeminiOptions {
+        cancel: Some(cancel.clone()),
+        ..Default::default()
+    };
+
+    let run = tokio::spawn({
+        let ws = ws.clone();
+        async move { execute_gemini("hi", &ws, &options).await }
+    });
+    let pid = hung_pid(&ws).await;
+    cancel.cancel();
+    let result = run.await.unwrap().unwrap();
+
+    assert!(!result.success);
+    assert_exits(pid).await;
+}
+
+#[tokio::test]
+async fn dropped_run_kills_the_process() {
+    common::install_fake_gemini();
+    let ws = hanging_workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+
+    let run = tokio::spawn({
+        let ws = ws.clone();
+        async move { execute_gemini("hi", &ws, &GeminiOptions::default()).await }
+    });
+    let pid = hung_pid(&ws).await;
+    run.abort();
+
+    assert_exits(pid).await;
+}
+
+#[tokio::test]
+async fn dropping_the_wrapper_kills_the_process() {
+    let child = tokio::process::Command::new("sleep")
+        .arg("60")
+        .spawn()
+        .unwrap();
+    let process = GeminiProcess::new(child);
+    let pid = process.id().unwrap() as i32;
+    assert!(is_running(pid));
+
+    drop(process);
+
+    assert_exits(pid).await;
+}
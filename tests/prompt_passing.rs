@@ -0,0 +1,105 @@
+//! `--prompt-passing`: how the prompt reaches gemini.
+
+mod common;
+
+use gemini_mcp::capabilities::parse_prompt_flag_deprecated;
+use gemini_mcp::{execute_gemini, Capabilities, GeminiOptions, PromptPassing};
+use std::path::PathBuf;
+
+async fn run(prompt: &str, prompt_passing: PromptPassing) -> PathBuf {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let options = GeminiOptions {
+        prompt_passing,
+        ..Default::default()
+    };
+    let result = execute_gemini(prompt, &ws, &options).await.unwrap();
+    assert!(result.success, "{:?}", result.error);
+    ws
+}
+
+fn stdin(ws: &std::path::Path) -> Option<String> {
+    std::fs::read_to_string(ws.join("stdin")).ok()
+}
+
+#[tokio::test]
+async fn flag_passes_the_prompt_to_dash_dash_prompt() {
+    let ws = run("hi there", PromptPassing::Flag).await;
+
+    let args = common::gemini_args(&ws);
+    assert_eq!(args[..4], ["--prompt", "hi there", "-o", "stream-json"]);
+    assert_eq!(stdin(&ws), None);
+}
+
+#[tokio::test]
+async fn auto_without_capabilities_uses_the_flag() {
+    let ws = run("hi there", PromptPassing::Auto).await;
+
+    assert_eq!(common::gemini_args(&ws)[..2], ["--prompt", "hi there"]);
+}
+
+#[tokio::test]
+async fn positional_passes_the_prompt_last() {
+    let ws = run("hi there", PromptPassing::Positional).await;
+
+    let args = common::gemini_args(&ws);
+    assert!(!args.iter().any(|a| a == "--prompt"), "{:?}", args);
+    assert_eq!(args[..2], ["-o", "stream-json"]);
+    assert_eq!(args.last().unwrap(), "hi there");
+}
+
+#[tokio::test]
+async fn positional_prompt_starting_with_a_dash_follows_a_separator() {
+    let ws = run("-v please", PromptPassing::Positional).await;
+
+    let args = common::gemini_args(&ws);
+    assert_eq!(args[args.len() - 2..], ["--", "-v please"]);
+}
+
+#[tokio::test]
+async fn stdin_writes_the_prompt_to_stdin() {
+    let ws = run("hi there", PromptPassing::Stdin).await;
+
+    let args = common::gemini_args(&ws);
+    assert!(!args.iter().any(|a| a.contains("hi there")), "{:?}", args);
+    assert_eq!(args[..2], ["-o", "stream-json"]);
+    assert_eq!(stdin(&ws).as_deref(), Some("hi there"));
+}
+
+#[test]
+fn deprecated_prompt_flag_prefers_positional() {
+    let help = r#"Options:
+  -m, --model               Model                                       [string]
+  -p, --prompt              Prompt. Appended to input on stdin (if any).
+                            [deprecated: Use the positional prompt instead. This
+                            flag will be removed in a future version.] [string]
+  -i, --prompt-interactive  Execute the provided prompt and continue in
+                            interactive mode                            [string]
+"#;
+    let capabilities = Capabilities {
+        prompt_flag_deprecated: parse_prompt_flag_deprecated(help),
+        ..Default::default()
+    };
+
+    assert!(capabilities.prompt_flag_deprecated);
+    assert_eq!(
+        capabilities.preferred_prompt_passing(),
+        PromptPassing::Positional
+    );
+}
+
+#[test]
+fn current_prompt_flag_is_kept() {
+    let help = r#"Options:
+  -p, --prompt              Prompt. Appended to input on stdin (if any).
+                                                                        [string]
+  -i, --prompt-interactive  Execute the provided prompt and continue in
+                            interactive mode. [deprecated: soon]        [string]
+"#;
+
+    assert!(!parse_prompt_flag_deprecated(help));
+    assert_eq!(
+        Capabilities::default().preferred_prompt_passing(),
+        PromptPassing::Flag
+    );
+}
@@ -0,0 +1,50 @@
+//! `read_only`: a workspace gemini cannot write to.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiError, GeminiOptions};
+
+#[tokio::test]
+async fn writes_to_the_workspace_fail() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let outcome = common::scratch_dir("outcome").join("outcome");
+    std::fs::write(
+        ws.join("script.sh"),
+        format!(
+            "if echo changed > notes.txt 2>/dev/null; then echo written > {0}; else echo refused > {0}; fi\n",
+            outcome.display()
+        ),
+    )
+    .unwrap();
+
+    let options = GeminiOptions {
+        read_only: true,
+        ..Default::default()
+    };
+    match execute_gemini("hi", &ws, &options).await {
+        Err(GeminiError::ReadOnlyUnavailable(e)) => {
+            eprintln!("skipped: user namespaces are unavailable here ({e})");
+            return;
+        }
+        result => assert!(result.unwrap().success),
+    }
+
+    assert_eq!(std::fs::read_to_string(&outcome).unwrap().trim(), "refused");
+    assert!(!ws.join("notes.txt").exists());
+}
+
+#[tokio::test]
+async fn writes_succeed_without_read_only() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("script.sh"), "echo changed > notes.txt\n").unwrap();
+
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    assert!(result.success);
+    assert!(ws.join("notes.txt").exists());
+}
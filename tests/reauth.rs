@@ -0,0 +1,101 @@
+//! Failing fast when gemini asks to log in again mid-run.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::retry::RetryPolicy;
+use gemini_mcp::{execute_gemini, FailureKind, GeminiOptions, GeminiResult, ServerConfig};
+use serde_json::json;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Run against a gemini that hangs after `ws` is set up, as one waiting for
+/// a login would.
+async fn run_hanging(ws: &Path) -> (GeminiResult, Duration) {
+    common::install_fake_gemini();
+    std::fs::write(ws.join("hang"), "").unwrap();
+    let options = GeminiOptions {
+        timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = execute_gemini("hi", ws, &options).await.unwrap();
+    (result, started.elapsed())
+}
+
+#[tokio::test]
+async fn reauth_event_fails_fast() {
+    let ws = common::workspace(
+        r#"{"type":"init","session_id":"sess-1"}
+{"type":"error","message":"Your credentials have expired. Please re-authenticate with /auth."}
+"#,
+    );
+
+    let (result, elapsed) = run_hanging(&ws).await;
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::ReauthRequired));
+    assert_eq!(result.exit_path.as_deref(), Some("reauth_required"));
+    let error = result.error.unwrap();
+    assert!(error.contains("Please re-authenticate"), "{error}");
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+}
+
+#[tokio::test]
+async fn reauth_prompt_on_stderr_fails_fast() {
+    let ws = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    std::fs::write(
+        ws.join("script.sh"),
+        "echo 'Login required. Please log in to continue:' >&2\n",
+    )
+    .unwrap();
+
+    let (result, elapsed) = run_hanging(&ws).await;
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::ReauthRequired));
+    assert!(result.error.unwrap().contains("Login required"));
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+}
+
+#[tokio::test]
+async fn assistant_text_about_logging_in_is_not_a_signal() {
+    common::install_fake_gemini();
+    let ws = common::workspace(
+        r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"The page says: please log in again."}
+{"type":"turn.completed"}
+"#,
+    );
+
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+}
+
+#[tokio::test]
+async fn reauth_before_a_session_is_not_retried() {
+    common::install_fake_gemini();
+    let ws = common::workspace(
+        r#"{"type":"error","message":"Your credentials have expired. Please re-authenticate with /auth."}
+"#,
+    );
+    std::fs::write(ws.join("script.sh"), "echo run >> runs\n").unwrap();
+    let client = common::connect(ServerConfig {
+        retry: RetryPolicy {
+            attempts: 3,
+            base_ms: 1,
+            max_ms: 1,
+            jitter: false,
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["failure_kind"], "reauth_required", "{result}");
+    assert_eq!(std::fs::read_to_string(ws.join("runs")).unwrap(), "run\n");
+}
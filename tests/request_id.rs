@@ -0,0 +1,43 @@
+//! Propagation of the request id into the gemini process.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{ServerConfig, REQUEST_ID_ENV};
+use serde_json::json;
+
+#[tokio::test]
+async fn supplied_request_id_is_exported_to_gemini() {
+    common::install_fake_gemini();
+    let client = common::connect(ServerConfig::default()).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call(
+        &client,
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "REQUEST_ID": "req-123"}),
+    )
+    .await;
+
+    assert_eq!(result["request_id"], "req-123");
+    assert_eq!(
+        common::gemini_env(&ws, REQUEST_ID_ENV).as_deref(),
+        Some("req-123")
+    );
+}
+
+#[tokio::test]
+async fn generated_request_id_is_exported_to_gemini() {
+    common::install_fake_gemini();
+    let client = common::connect(ServerConfig::default()).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call(&client, "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    let request_id = result["request_id"].as_str().unwrap();
+    assert!(!request_id.is_empty());
+    assert_eq!(
+        common::gemini_env(&ws, REQUEST_ID_ENV).as_deref(),
+        Some(request_id)
+    );
+}
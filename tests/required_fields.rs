@@ -0,0 +1,50 @@
+//! Calls missing a required field get an error naming it.
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use rmcp::model::CallToolRequestParam;
+use rmcp::service::ServiceError;
+use serde_json::json;
+
+/// The error message of a call expected to be rejected.
+async fn rejection(tool: &'static str, arguments: serde_json::Value) -> String {
+    let client = common::connect(ServerConfig::default()).await;
+    let error = client
+        .peer()
+        .call_tool(CallToolRequestParam {
+            name: tool.into(),
+            arguments: arguments.as_object().cloned(),
+            task: None,
+        })
+        .await
+        .unwrap_err();
+    match error {
+        ServiceError::McpError(error) => error.message.to_string(),
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[tokio::test]
+async fn missing_prompt_is_named() {
+    let message = rejection("gemini", json!({"cd": "."})).await;
+    assert_eq!(message, "missing required field: PROMPT");
+}
+
+#[tokio::test]
+async fn missing_cd_is_named() {
+    let message = rejection("gemini", json!({"PROMPT": "hi"})).await;
+    assert_eq!(message, "missing required field: cd");
+}
+
+#[tokio::test]
+async fn every_missing_field_is_named() {
+    let message = rejection("gemini_validate", json!({})).await;
+    assert_eq!(message, "missing required fields: PROMPT, cd");
+}
+
+#[tokio::test]
+async fn other_tools_name_their_missing_fields() {
+    let message = rejection("gemini_batch_retry", json!({})).await;
+    assert_eq!(message, "missing required field: batch_id");
+}
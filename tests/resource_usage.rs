@@ -0,0 +1,33 @@
+//! `collect_resource_usage`: gemini's peak resident memory.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions};
+
+async fn run(collect_resource_usage: bool) -> gemini_mcp::GeminiResult {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    // Keep gemini alive long enough to be sampled a few times
+    std::fs::write(ws.join("script.sh"), "sleep 0.2\n").unwrap();
+    let options = GeminiOptions {
+        collect_resource_usage,
+        ..Default::default()
+    };
+    execute_gemini("hi", &ws, &options).await.unwrap()
+}
+
+#[tokio::test]
+async fn peak_rss_is_reported_when_requested() {
+    let result = run(true).await;
+
+    assert!(result.success, "{:?}", result.error);
+    let peak = result.peak_rss_bytes.unwrap();
+    // Even a shell keeps a few hundred KiB resident
+    assert!(peak > 100 * 1024, "{peak}");
+}
+
+#[tokio::test]
+async fn peak_rss_is_omitted_by_default() {
+    assert_eq!(run(false).await.peak_rss_bytes, None);
+}
@@ -0,0 +1,50 @@
+//! Exponential backoff with full jitter between retries.
+
+use gemini_mcp::RetryPolicy;
+use std::time::Duration;
+
+fn policy(jitter: bool) -> RetryPolicy {
+    RetryPolicy {
+        attempts: 5,
+        base_ms: 100,
+        max_ms: 1_000,
+        jitter,
+    }
+}
+
+#[test]
+fn ceiling_doubles_up_to_the_maximum() {
+    let ceilings: Vec<u64> = (0..6)
+        .map(|retry| policy(true).backoff_ceiling(retry).as_millis() as u64)
+        .collect();
+
+    assert_eq!(ceilings, [100, 200, 400, 800, 1_000, 1_000]);
+    assert_eq!(
+        policy(true).backoff_ceiling(200),
+        Duration::from_millis(1_000)
+    );
+}
+
+#[test]
+fn jittered_delays_stay_within_the_ceiling() {
+    let policy = policy(true);
+    for retry in 0..6 {
+        let ceiling = policy.backoff_ceiling(retry);
+        let delays: Vec<Duration> = (0..200).map(|_| policy.delay(retry)).collect();
+
+        assert!(delays.iter().all(|d| *d <= ceiling), "retry {retry}");
+        // Full jitter spreads the delays instead of lining them up
+        assert!(
+            delays.iter().any(|d| *d < ceiling / 2),
+            "retry {retry}: {delays:?}"
+        );
+    }
+}
+
+#[test]
+fn delays_without_jitter_are_the_ceiling() {
+    let policy = policy(false);
+    for retry in 0..6 {
+        assert_eq!(policy.delay(retry), policy.backoff_ceiling(retry));
+    }
+}
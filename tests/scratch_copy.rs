@@ -0,0 +1,160 @@
+//! `scratch_copy`: running gemini on a disposable copy of the workspace.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// A workspace in which the fake gemini edits `notes.txt` and deletes
+/// `old.txt`.
+fn editing_workspace() -> PathBuf {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("notes.txt"), "original\n").unwrap();
+    std::fs::write(ws.join("old.txt"), "old\n").unwrap();
+    std::fs::write(
+        ws.join("script.sh"),
+        "echo edited > notes.txt\nrm old.txt\n",
+    )
+    .unwrap();
+    ws
+}
+
+fn names(paths: &serde_json::Value) -> Vec<&str> {
+    paths
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p.as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn original_is_untouched_while_the_copy_is_modified() {
+    let ws = editing_workspace();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true, "keep_scratch_copy": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        std::fs::read_to_string(ws.join("notes.txt")).unwrap(),
+        "original\n"
+    );
+    assert!(ws.join("old.txt").exists());
+    assert!(!ws.join("args").exists(), "gemini ran in the original");
+
+    let copy = PathBuf::from(result["scratch_dir"].as_str().unwrap());
+    assert_ne!(copy, ws);
+    assert_eq!(
+        std::fs::read_to_string(copy.join("notes.txt")).unwrap(),
+        "edited\n"
+    );
+    assert!(!copy.join("old.txt").exists());
+
+    let changes = names(&result["scratch_changes"]);
+    assert!(changes.contains(&"notes.txt"), "{changes:?}");
+    assert!(changes.contains(&"old.txt"), "{changes:?}");
+    std::fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn copy_is_discarded_by_default() {
+    let ws = editing_workspace();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("scratch_dir").is_none(), "{result}");
+    assert!(names(&result["scratch_changes"]).contains(&"notes.txt"));
+}
+
+#[tokio::test]
+async fn oversized_workspace_is_rejected() {
+    let ws = editing_workspace();
+    let client = common::connect(ServerConfig {
+        scratch_copy_max_bytes: 4,
+        ..Default::default()
+    })
+    .await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(
+        result["error"].as_str().unwrap().contains("4 byte limit"),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn ignored_names_are_not_copied() {
+    let ws = editing_workspace();
+    std::fs::create_dir(ws.join("node_modules")).unwrap();
+    std::fs::write(ws.join("node_modules/big.js"), "x").unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true, "keep_scratch_copy": true}),
+    )
+    .await;
+
+    let copy = PathBuf::from(result["scratch_dir"].as_str().unwrap());
+    assert!(copy.join("notes.txt").exists());
+    assert!(!copy.join("node_modules").exists());
+    std::fs::remove_dir_all(copy.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn runs_in_a_copy_are_fingerprinted_and_recorded_against_cd() {
+    let ws = editing_workspace();
+    let client = common::connect(ServerConfig {
+        strict_session_cwd: true,
+        ..Default::default()
+    })
+    .await;
+
+    let first = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true}),
+    )
+    .await;
+    let second = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "scratch_copy": true}),
+    )
+    .await;
+    assert_eq!(first["success"], true, "{first}");
+    assert_eq!(second["request_fingerprint"], first["request_fingerprint"]);
+
+    let resumed = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "more", "cd": ws, "SESSION_ID": first["SESSION_ID"]}),
+    )
+    .await;
+    assert_eq!(resumed["success"], true, "{resumed}");
+}
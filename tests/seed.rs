@@ -0,0 +1,83 @@
+//! `SEED`: reproducible runs through gemini's `--seed`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::capabilities::parse_options;
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const HELP: &str = r#"Usage: gemini [options] [command]
+
+Options:
+  -m, --model                     Model                                 [string]
+      --seed                      Seed for sampling                     [number]
+      --temperature               Sampling temperature                  [number]
+  -o, --output-format             The format of the CLI output.
+                                  [string] [choices: "text", "json",
+                                  "stream-json"]
+"#;
+
+/// Have the fake gemini list `--seed` and `--temperature` in its help.
+fn fake_help() {
+    static HELP_FILE: OnceLock<()> = OnceLock::new();
+    HELP_FILE.get_or_init(|| {
+        let path = common::scratch_dir("help").join("help.txt");
+        std::fs::write(&path, HELP).unwrap();
+        std::env::set_var("FAKE_GEMINI_HELP", path);
+    });
+}
+
+async fn run(ws: &Path, seed: u64) -> (serde_json::Value, Vec<String>) {
+    common::install_fake_gemini();
+    fake_help();
+    let client = common::connect(ServerConfig::default()).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "Name a color", "cd": ws, "SEED": seed}),
+    )
+    .await;
+    (result, common::gemini_args(ws))
+}
+
+#[tokio::test]
+async fn seed_is_passed_with_temperature_zero() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let (result, args) = run(&ws, 42).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["seed"], 42);
+    let seed = args.iter().position(|a| a == "--seed").unwrap();
+    assert_eq!(args[seed + 1], "42");
+    let temperature = args.iter().position(|a| a == "--temperature").unwrap();
+    assert_eq!(args[temperature + 1], "0");
+}
+
+#[tokio::test]
+async fn same_seed_and_prompt_are_requested_identically() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let (first, first_args) = run(&ws, 7).await;
+    let (second, second_args) = run(&ws, 7).await;
+
+    assert_eq!(first["success"], true, "{first}");
+    assert_eq!(second["success"], true, "{second}");
+    assert_eq!(first_args, second_args);
+    assert_eq!(first["request_fingerprint"], second["request_fingerprint"]);
+}
+
+#[test]
+fn options_are_read_from_the_help_text() {
+    let options = parse_options(HELP);
+
+    assert_eq!(
+        options,
+        ["--model", "--seed", "--temperature", "--output-format"]
+    );
+    assert!(
+        !parse_options("Options:\n  -m, --model   Model  [string]\n")
+            .contains(&"--seed".to_string())
+    );
+}
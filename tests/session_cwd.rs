@@ -0,0 +1,56 @@
+//! Resuming a session from a different working directory.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn resume_elsewhere(strict_session_cwd: bool) -> serde_json::Value {
+    common::install_fake_gemini();
+    let first_ws = common::workspace(common::DEFAULT_STREAM);
+    let other_ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig {
+        strict_session_cwd,
+        ..Default::default()
+    })
+    .await;
+
+    let first = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": first_ws}),
+    )
+    .await;
+    assert_eq!(first["SESSION_ID"], "sess-1", "{first}");
+
+    common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "more", "cd": other_ws, "SESSION_ID": "sess-1"}),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn mismatched_cwd_is_warned_about() {
+    let result = resume_elsewhere(false).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let warnings = result["warnings"].as_array().unwrap();
+    assert!(
+        warnings.iter().any(|w| w
+            .as_str()
+            .unwrap()
+            .contains("file context may be inconsistent")),
+        "{result}"
+    );
+}
+
+#[tokio::test]
+async fn mismatched_cwd_is_refused_when_strict() {
+    let result = resume_elsewhere(true).await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "session_cwd_mismatch");
+}
@@ -0,0 +1,64 @@
+//! Returning sessions as handles that resume them.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{ServerConfig, SessionHandle};
+use serde_json::json;
+
+#[tokio::test]
+async fn returned_handle_round_trips_through_a_resume() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let first = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "model": "gemini-2.5-flash"}),
+    )
+    .await;
+    let handle: SessionHandle = serde_json::from_value(first["session"].clone()).unwrap();
+    assert_eq!(handle.id, "sess-1");
+    assert_eq!(handle.model.as_deref(), Some("gemini-2.5-flash"));
+    assert_eq!(handle.cwd, ws);
+    assert_eq!(handle.turns, 1);
+    assert!(handle.created_at > 0);
+
+    let second = common::call(
+        client.peer(),
+        "gemini_resume",
+        json!({"session": handle, "PROMPT": "and then?"}),
+    )
+    .await;
+
+    assert_eq!(second["success"], true, "{second}");
+    let args = common::gemini_args(&ws);
+    let flag = |name: &str| {
+        let at = args.iter().position(|a| a == name).unwrap();
+        args[at + 1].clone()
+    };
+    assert_eq!(flag("--resume"), "sess-1");
+    assert_eq!(flag("--model"), "gemini-2.5-flash");
+    assert!(args.iter().any(|a| a.contains("and then?")), "{args:?}");
+
+    let resumed: SessionHandle = serde_json::from_value(second["session"].clone()).unwrap();
+    assert_eq!(resumed, SessionHandle { turns: 2, ..handle });
+}
+
+#[test]
+fn resume_builds_the_next_turn_input() {
+    let handle = SessionHandle {
+        id: "sess-1".to_string(),
+        model: None,
+        cwd: "/tmp/ws".into(),
+        created_at: 1,
+        turns: 3,
+    };
+    let input = handle.resume("next");
+
+    assert_eq!(input.prompt, "next");
+    assert_eq!(input.session_id, "sess-1");
+    assert_eq!(input.cd, std::path::Path::new("/tmp/ws"));
+    assert!(input.model.is_empty());
+}
@@ -0,0 +1,74 @@
+//! Session ids under the keys different gemini versions use.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions};
+
+async fn session_id(stream: &str, pointer: Option<&str>) -> Option<String> {
+    common::install_fake_gemini();
+    let ws = common::workspace(stream);
+    let options = GeminiOptions {
+        session_id_pointer: pointer.map(str::to_string),
+        ..Default::default()
+    };
+    execute_gemini("hi", &ws, &options)
+        .await
+        .unwrap()
+        .session_id
+}
+
+fn stream(init: &str) -> String {
+    format!(
+        "{init}\n{}\n{}\n",
+        r#"{"type":"message","role":"assistant","content":"Hello"}"#,
+        r#"{"type":"turn.completed"}"#
+    )
+}
+
+#[tokio::test]
+async fn lower_case_session_id() {
+    let stream = stream(r#"{"type":"init","session_id":"sess-lower"}"#);
+    assert_eq!(
+        session_id(&stream, None).await.as_deref(),
+        Some("sess-lower")
+    );
+}
+
+#[tokio::test]
+async fn upper_case_session_id() {
+    let stream = stream(r#"{"type":"init","SESSION_ID":"sess-upper"}"#);
+    assert_eq!(
+        session_id(&stream, None).await.as_deref(),
+        Some("sess-upper")
+    );
+}
+
+#[tokio::test]
+async fn lower_case_wins_over_upper_case() {
+    let stream = stream(r#"{"type":"init","session_id":"sess-lower","SESSION_ID":"sess-upper"}"#);
+    assert_eq!(
+        session_id(&stream, None).await.as_deref(),
+        Some("sess-lower")
+    );
+}
+
+#[tokio::test]
+async fn nested_session_id_needs_a_pointer() {
+    let stream = stream(r#"{"type":"init","session":{"id":"sess-nested"}}"#);
+    assert_eq!(session_id(&stream, None).await, None);
+    assert_eq!(
+        session_id(&stream, Some("/session/id")).await.as_deref(),
+        Some("sess-nested")
+    );
+}
+
+#[tokio::test]
+async fn pointer_is_only_a_fallback() {
+    let stream =
+        stream(r#"{"type":"init","SESSION_ID":"sess-upper","session":{"id":"sess-nested"}}"#);
+    assert_eq!(
+        session_id(&stream, Some("/session/id")).await.as_deref(),
+        Some("sess-upper")
+    );
+}
@@ -0,0 +1,53 @@
+//! Capping resumes per session with `session_resume_limit`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        session_resume_limit: Some(2),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn resuming_past_the_cap_is_rejected() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let first = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    assert_eq!(first["SESSION_ID"], "sess-1", "{first}");
+
+    let resume = json!({"PROMPT": "more", "cd": ws, "SESSION_ID": "sess-1"});
+    for _ in 0..2 {
+        let result = common::call(client.peer(), "gemini", resume.clone()).await;
+        assert_eq!(result["success"], true, "{result}");
+    }
+
+    std::fs::remove_file(ws.join("args")).unwrap();
+    let result = common::call(client.peer(), "gemini", resume).await;
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "session_quota_exceeded");
+    assert!(!ws.join("args").exists(), "gemini ran past the quota");
+}
+
+#[tokio::test]
+async fn stop_phrase_continuations_count_against_the_cap() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(config()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "stop_phrase": "TASK COMPLETE", "max_turns": 5}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "session_quota_exceeded");
+}
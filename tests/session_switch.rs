@@ -0,0 +1,65 @@
+//! Reporting both session ids when a resume comes back under a new one.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn resume(stream: &str) -> serde_json::Value {
+    common::install_fake_gemini();
+    let ws = common::workspace(stream);
+    let client = common::connect(ServerConfig::default()).await;
+    common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "go on", "cd": ws, "SESSION_ID": "sess-0"}),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn failed_resume_under_a_new_id_reports_both() {
+    let result = resume("{\"type\":\"init\",\"session_id\":\"sess-1\"}\n").await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["original_session_id"], "sess-0");
+    assert_eq!(result["current_session_id"], "sess-1");
+    assert_eq!(result["SESSION_ID"], "sess-1");
+    let warnings = result["warnings"].to_string();
+    assert!(
+        warnings.contains("resume `original_session_id`"),
+        "{warnings}"
+    );
+}
+
+#[tokio::test]
+async fn successful_resume_under_a_new_id_points_to_the_new_one() {
+    let result = resume(common::DEFAULT_STREAM).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["original_session_id"], "sess-0");
+    assert_eq!(result["current_session_id"], "sess-1");
+    let warnings = result["warnings"].to_string();
+    assert!(
+        warnings.contains("resume `current_session_id`"),
+        "{warnings}"
+    );
+}
+
+#[tokio::test]
+async fn resume_keeping_its_id_reports_neither() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "go on", "cd": ws, "SESSION_ID": "sess-1"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("original_session_id").is_none(), "{result}");
+    assert!(result.get("current_session_id").is_none(), "{result}");
+}
@@ -0,0 +1,154 @@
+//! Fanning gemini's raw output out to several sinks in one pass.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FileSink, GeminiOptions, LineBuffer, ServerConfig, Sinks};
+use rmcp::model::ProgressNotificationParam;
+use rmcp::service::NotificationContext;
+use rmcp::{ClientHandler, RoleClient};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn log_lines(path: &std::path::Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn buffer_and_file_receive_the_same_lines() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let log = ws.join("raw.log");
+
+    let buffer = Arc::new(LineBuffer::default());
+    let mut sinks = Sinks::default();
+    sinks.push(Arc::clone(&buffer));
+    sinks.push(FileSink::append(&log).unwrap());
+    let options = GeminiOptions {
+        sinks,
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success);
+    let expected: Vec<String> = common::DEFAULT_STREAM.lines().map(str::to_string).collect();
+    assert_eq!(buffer.lines(), expected);
+    assert_eq!(log_lines(&log), expected);
+}
+
+/// Records the message of every progress notification.
+#[derive(Clone, Default)]
+struct ProgressRecorder(Arc<Mutex<Vec<String>>>);
+
+impl ClientHandler for ProgressRecorder {
+    async fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.0.lock().unwrap().extend(params.message);
+    }
+}
+
+#[tokio::test]
+async fn progress_and_log_file_receive_the_same_lines() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let recorder = ProgressRecorder::default();
+    let (client, _server) = common::start(ServerConfig::default(), recorder.clone()).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "log_file": "raw.log"}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let logged = log_lines(&ws.join("raw.log"));
+    assert_eq!(logged.len(), common::DEFAULT_STREAM.lines().count());
+
+    // Notifications are handled concurrently with the response
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.0.lock().unwrap().len() < logged.len() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    // Progress events also carry their `_seq`
+    let progress: Vec<String> = recorder
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(i, line)| line.replace(&format!("\"_seq\":{},", i + 1), ""))
+        .collect();
+    assert_eq!(progress, logged);
+}
+
+#[tokio::test]
+async fn log_file_must_stay_inside_cd() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let outside = common::scratch_dir("outside");
+    std::os::unix::fs::symlink(&outside, ws.join("escape")).unwrap();
+    std::fs::create_dir(ws.join("logs")).unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+
+    for log_file in [
+        outside.join("raw.log").to_string_lossy().to_string(),
+        "../raw.log".to_string(),
+        "logs/../../raw.log".to_string(),
+        "escape/raw.log".to_string(),
+    ] {
+        let result = common::call(
+            client.peer(),
+            "gemini",
+            json!({"PROMPT": "hi", "cd": ws, "log_file": log_file}),
+        )
+        .await;
+        assert_eq!(
+            result["failure_kind"], "invalid_input",
+            "{log_file}: {result}"
+        );
+        assert!(
+            !ws.join("args").exists(),
+            "gemini ran with log_file {log_file}"
+        );
+    }
+    assert!(!outside.join("raw.log").exists());
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "log_file": "logs/raw.log"}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+    assert!(ws.join("logs/raw.log").exists());
+}
+
+#[tokio::test]
+async fn log_file_is_refused_under_read_only() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "log_file": "raw.log", "read_only": true}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+    assert!(result["error"].as_str().unwrap().contains("read_only"));
+    assert!(!ws.join("raw.log").exists());
+}
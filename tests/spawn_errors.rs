@@ -0,0 +1,59 @@
+//! Reporting of permission and disk errors when starting gemini.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, FailureKind, GeminiError, GeminiOptions};
+use std::io::{Error, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+#[test]
+fn spawn_errors_map_to_specific_variants() {
+    let cwd = std::env::temp_dir();
+
+    let denied = GeminiError::from_spawn_error(
+        Error::from(ErrorKind::PermissionDenied),
+        "/usr/bin/gemini",
+        &cwd,
+    );
+    assert!(matches!(denied, GeminiError::SpawnPermissionDenied { .. }));
+    assert_eq!(denied.failure_kind(), FailureKind::PermissionDenied);
+
+    let full = GeminiError::from_spawn_error(Error::from(ErrorKind::StorageFull), "gemini", &cwd);
+    assert!(matches!(full, GeminiError::StorageFull(_)));
+    assert_eq!(full.failure_kind(), FailureKind::StorageFull);
+
+    let gone = GeminiError::from_spawn_error(
+        Error::from(ErrorKind::NotFound),
+        "gemini",
+        Path::new("/nonexistent/workspace"),
+    );
+    assert!(matches!(gone, GeminiError::WorkspaceNotFound(_)));
+}
+
+#[tokio::test]
+async fn permission_denied_spawn_is_reported() {
+    // A directory without execute permission would not stop root, but the
+    // kernel refuses to run an interpreter without any execute bit for everyone.
+    let bin = common::scratch_dir("denied");
+    let interpreter = bin.join("interpreter");
+    std::fs::write(&interpreter, "").unwrap();
+    std::fs::set_permissions(&interpreter, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let gemini = bin.join("gemini");
+    std::fs::write(&gemini, format!("#!{}\n", interpreter.display())).unwrap();
+    std::fs::set_permissions(&gemini, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::env::set_var("PATH", &bin);
+
+    let cwd = common::scratch_dir("ws");
+    let err = execute_gemini("hi", &cwd, &GeminiOptions::default())
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, GeminiError::SpawnPermissionDenied { .. }),
+        "unexpected error: {err}"
+    );
+    assert_eq!(err.failure_kind(), FailureKind::PermissionDenied);
+    assert!(err.to_string().contains(&*cwd.to_string_lossy()));
+}
@@ -0,0 +1,86 @@
+//! Pricing token usage and capping spend with `spend_budget`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::spend::ModelCost;
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// A turn using 1000 tokens.
+const USAGE_STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Hello","usage":{"input_tokens":800,"output_tokens":200,"total_tokens":1000}}
+{"type":"turn.completed"}
+"#;
+
+fn config(spend_budget: Option<f64>) -> ServerConfig {
+    ServerConfig {
+        model_costs: vec![
+            "gemini-2.5-pro=0.5".parse().unwrap(),
+            "*=0.25".parse().unwrap(),
+        ],
+        spend_budget,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn requests_fail_fast_once_the_budget_is_used_up() {
+    common::install_fake_gemini();
+    let ws = common::workspace(USAGE_STREAM);
+    let client = common::connect(config(Some(1.0))).await;
+    let request = json!({"PROMPT": "hi", "cd": ws, "model": "gemini-2.5-pro"});
+
+    for _ in 0..2 {
+        let result = common::call(client.peer(), "gemini", request.clone()).await;
+        assert_eq!(result["success"], true, "{result}");
+        assert_eq!(result["cost"], 0.5);
+    }
+
+    std::fs::remove_file(ws.join("args")).unwrap();
+    let result = common::call(client.peer(), "gemini", request).await;
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "budget_exceeded");
+    assert!(!ws.join("args").exists(), "gemini ran over budget");
+
+    let metrics = common::call(client.peer(), "gemini_metrics", json!({})).await;
+    assert_eq!(metrics["spend"]["total"], 1.0);
+    assert_eq!(metrics["spend"]["window"], 1.0);
+    assert_eq!(metrics["spend"]["budget"], 1.0);
+    assert_eq!(metrics["spend"]["remaining"], 0.0);
+}
+
+#[tokio::test]
+async fn models_without_a_cost_use_the_wildcard() {
+    common::install_fake_gemini();
+    let ws = common::workspace(USAGE_STREAM);
+    let client = common::connect(config(None)).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["cost"], 0.25, "{result}");
+    let metrics = common::call(client.peer(), "gemini_metrics", json!({})).await;
+    assert_eq!(metrics["spend"]["total"], 0.25);
+    assert!(metrics["spend"].get("budget").is_none(), "{metrics}");
+}
+
+#[tokio::test]
+async fn usage_is_free_without_costs() {
+    common::install_fake_gemini();
+    let ws = common::workspace(USAGE_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("cost").is_none(), "{result}");
+}
+
+#[test]
+fn model_cost_parses() {
+    let cost: ModelCost = "gemini-2.5-flash = 0.002".parse().unwrap();
+    assert_eq!(cost.model, "gemini-2.5-flash");
+    assert_eq!(cost.per_1k_tokens, 0.002);
+    assert!("gemini-2.5-flash".parse::<ModelCost>().is_err());
+    assert!("gemini-2.5-flash=-1".parse::<ModelCost>().is_err());
+}
@@ -0,0 +1,54 @@
+//! Reporting gemini's status events apart from assistant content.
+
+use gemini_mcp::{replay_stream, GeminiOptions};
+
+const WITH_STATUS: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"status","content":"Analyzing..."}
+{"type":"status","content":"Analyzing..."}
+{"type":"progress","message":"Searching the web..."}
+{"type":"message","role":"assistant","content":"Found it."}
+{"type":"status","status":"  "}
+{"type":"turn.completed"}
+"#;
+
+#[test]
+fn status_events_are_classified_apart_from_messages() {
+    let result = replay_stream(WITH_STATUS, &GeminiOptions::default());
+
+    assert!(result.success);
+    assert_eq!(result.agent_messages.as_deref(), Some("Found it."));
+    assert_eq!(
+        result.status_updates,
+        Some(vec![
+            "Analyzing...".to_string(),
+            "Searching the web...".to_string()
+        ])
+    );
+}
+
+#[test]
+fn status_updates_reach_the_status_sink() {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let options = GeminiOptions {
+        status_sink: Some(tx),
+        ..Default::default()
+    };
+
+    replay_stream(WITH_STATUS, &options);
+
+    assert_eq!(rx.try_recv().unwrap(), "Analyzing...");
+    assert_eq!(rx.try_recv().unwrap(), "Searching the web...");
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn streams_without_status_events_report_none() {
+    let output = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"hi"}
+{"type":"turn.completed"}
+"#;
+
+    let result = replay_stream(output, &GeminiOptions::default());
+
+    assert_eq!(result.status_updates, None);
+}
@@ -0,0 +1,97 @@
+//! Streaming gemini's stderr lines while it runs.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions, ServerConfig};
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam, SetLevelRequestParam};
+use rmcp::service::NotificationContext;
+use rmcp::{ClientHandler, RoleClient};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+const STDERR: &str = "Loaded cached credentials.\nUsing model gemini-2.5-pro\nRetrying after 429\n";
+
+fn stderr_workspace() -> PathBuf {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("stderr.txt"), STDERR).unwrap();
+    std::fs::write(ws.join("script.sh"), "cat stderr.txt >&2\n").unwrap();
+    ws
+}
+
+#[tokio::test]
+async fn stderr_lines_reach_the_sink_in_order() {
+    common::install_fake_gemini();
+    let ws = stderr_workspace();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let options = GeminiOptions {
+        stderr_sink: Some(tx),
+        ..Default::default()
+    };
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+    drop(options);
+
+    assert!(result.success);
+    let mut lines = Vec::new();
+    while let Some(line) = rx.recv().await {
+        lines.push(line);
+    }
+    assert_eq!(lines, STDERR.lines().collect::<Vec<_>>());
+}
+
+/// Records every logging notification.
+#[derive(Clone, Default)]
+struct LogRecorder(Arc<Mutex<Vec<LoggingMessageNotificationParam>>>);
+
+impl ClientHandler for LogRecorder {
+    async fn on_logging_message(
+        &self,
+        params: LoggingMessageNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.0.lock().unwrap().push(params);
+    }
+}
+
+async fn forwarded(level: Option<LoggingLevel>) -> Vec<LoggingMessageNotificationParam> {
+    common::install_fake_gemini();
+    let ws = stderr_workspace();
+
+    let recorder = LogRecorder::default();
+    let config = ServerConfig {
+        forward_stderr: true,
+        ..Default::default()
+    };
+    let (client, _server) = common::start(config, recorder.clone()).await;
+    if let Some(level) = level {
+        client
+            .peer()
+            .set_level(SetLevelRequestParam { level })
+            .await
+            .unwrap();
+    }
+    let result = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let notifications = recorder.0.lock().unwrap().clone();
+    notifications
+}
+
+#[tokio::test]
+async fn server_forwards_stderr_as_logging_notifications() {
+    let notifications = forwarded(None).await;
+
+    let lines: Vec<_> = notifications.iter().map(|n| n.data.clone()).collect();
+    assert_eq!(lines, STDERR.lines().map(|l| json!(l)).collect::<Vec<_>>());
+    assert!(notifications
+        .iter()
+        .all(|n| n.logger.as_deref() == Some("stderr") && n.level == LoggingLevel::Info));
+}
+
+#[tokio::test]
+async fn a_higher_client_level_mutes_stderr() {
+    assert!(forwarded(Some(LoggingLevel::Warning)).await.is_empty());
+}
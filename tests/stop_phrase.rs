@@ -0,0 +1,68 @@
+//! Resuming the session until gemini says the `stop_phrase`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// Counts runs in `runs` and says the stop phrase from the third one on.
+const SENTINEL_ON_THIRD_RUN: &str = r#"n=$(($(cat runs 2>/dev/null || echo 0) + 1))
+echo $n > runs
+cp args args.$n
+printf '%s\n' '{"type":"init","session_id":"sess-1"}' > stream.jsonl
+if [ $n -ge 3 ]; then
+  printf '%s\n' '{"type":"message","role":"assistant","content":"All done. TASK COMPLETE"}' >> stream.jsonl
+else
+  printf '%s\n' "{\"type\":\"message\",\"role\":\"assistant\",\"content\":\"Step $n\"}" >> stream.jsonl
+fi
+printf '%s\n' '{"type":"turn.completed"}' >> stream.jsonl
+"#;
+
+async fn run(max_turns: u32) -> (serde_json::Value, std::path::PathBuf) {
+    common::install_fake_gemini();
+    let ws = common::workspace("");
+    std::fs::write(ws.join("script.sh"), SENTINEL_ON_THIRD_RUN).unwrap();
+    let client = common::connect(ServerConfig::default()).await;
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "work", "cd": ws, "stop_phrase": "TASK COMPLETE", "max_turns": max_turns}),
+    )
+    .await;
+    (result, ws)
+}
+
+#[tokio::test]
+async fn session_is_resumed_until_the_stop_phrase_on_the_third_turn() {
+    let (result, ws) = run(10).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["turns"], 3);
+    assert_eq!(
+        result["agent_messages"],
+        "Step 1\n\nStep 2\n\nAll done. TASK COMPLETE"
+    );
+    assert_eq!(
+        std::fs::read_to_string(ws.join("runs")).unwrap().trim(),
+        "3"
+    );
+    let third = std::fs::read_to_string(ws.join("args.3")).unwrap();
+    assert!(third.contains("--resume\nsess-1\n"), "{third}");
+    assert!(third.contains("TASK COMPLETE"), "{third}");
+}
+
+#[tokio::test]
+async fn max_turns_caps_the_continuations() {
+    let (result, _) = run(2).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["turns"], 2);
+    let warnings = result["warnings"].as_array().unwrap();
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w.as_str().unwrap().contains("not seen after 2 turns")),
+        "{result}"
+    );
+}
@@ -0,0 +1,93 @@
+//! Ending generation at caller-given `STOP_SEQUENCES`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{replay_stream, GeminiOptions, ServerConfig};
+use serde_json::json;
+
+const SPLIT_MARKER: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Answer: 42\n<<E","delta":true}
+{"type":"message","role":"assistant","content":"ND>> and then some","delta":true}
+{"type":"message","role":"assistant","content":" more text","delta":true}
+{"type":"turn.completed"}
+"#;
+
+#[test]
+fn text_is_cut_at_a_sequence_split_across_chunks() {
+    let options = GeminiOptions {
+        stop_sequences: vec!["<<END>>".to_string(), "unused".to_string()],
+        return_all_messages: true,
+        event_summary: true,
+        ..Default::default()
+    };
+
+    let result = replay_stream(SPLIT_MARKER, &options);
+
+    assert!(result.success);
+    assert!(result.stopped_early);
+    assert_eq!(result.agent_messages.as_deref(), Some("Answer: 42\n"));
+    assert_eq!(result.finish_reason.as_deref(), Some("stop_sequence"));
+    // The event holding the sequence is the last one recorded
+    let messages = result.all_messages.unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[2]["content"], "ND>> and then some");
+    let summary = result.event_summary.unwrap();
+    assert_eq!(summary.len(), 3);
+    assert_eq!(summary[2].preview.as_deref(), Some("ND>> and then some"));
+}
+
+#[test]
+fn text_without_a_sequence_is_untouched() {
+    let options = GeminiOptions {
+        stop_sequences: vec!["STOP".to_string()],
+        ..Default::default()
+    };
+
+    let result = replay_stream(SPLIT_MARKER, &options);
+
+    assert!(!result.stopped_early);
+    assert_eq!(
+        result.agent_messages.as_deref(),
+        Some("Answer: 42\n<<END>> and then some more text")
+    );
+}
+
+#[tokio::test]
+async fn empty_sequences_are_rejected() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "STOP_SEQUENCES": ["END", ""]}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(
+        result["error"].as_str().unwrap().contains("STOP_SEQUENCES"),
+        "{result}"
+    );
+    assert!(!ws.join("args").exists(), "gemini should not have run");
+}
+
+#[tokio::test]
+async fn sequences_stop_a_live_run() {
+    common::install_fake_gemini();
+    let ws = common::workspace(SPLIT_MARKER);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "STOP_SEQUENCES": ["<<END>>"]}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(result["agent_messages"], "Answer: 42\n");
+    assert_eq!(result["stopped_early"], true);
+}
@@ -0,0 +1,42 @@
+//! Aborting at gemini's first error event.
+
+use gemini_mcp::{replay_stream, FailureKind, GeminiOptions};
+
+const ERROR_MID_STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Reading the file."}
+{"type":"error","severity":"error","message":"Tool read_file failed: ENOENT"}
+{"type":"message","role":"assistant","content":" It does not exist."}
+{"type":"turn.completed"}
+"#;
+
+#[test]
+fn fail_fast_aborts_at_the_error_event() {
+    let options = GeminiOptions {
+        fail_fast_on_stream_error: true,
+        return_all_messages: true,
+        ..Default::default()
+    };
+
+    let result = replay_stream(ERROR_MID_STREAM, &options);
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::StreamError));
+    assert_eq!(
+        result.error.as_deref(),
+        Some("gemini reported an error: Tool read_file failed: ENOENT")
+    );
+    let events = result.all_messages.unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[2]["type"], "error");
+}
+
+#[test]
+fn lenient_runs_continue_past_error_events() {
+    let result = replay_stream(ERROR_MID_STREAM, &GeminiOptions::default());
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(
+        result.agent_messages.as_deref(),
+        Some("Reading the file. It does not exist.")
+    );
+}
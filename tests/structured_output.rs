@@ -0,0 +1,95 @@
+//! Returning results as MCP structured content.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use rmcp::model::{ClientInfo, ProtocolVersion};
+use serde_json::json;
+
+fn client(protocol_version: ProtocolVersion) -> ClientInfo {
+    ClientInfo {
+        protocol_version,
+        ..Default::default()
+    }
+}
+
+fn structured_config() -> ServerConfig {
+    ServerConfig {
+        structured_output: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn structured_content_is_emitted_when_supported() {
+    common::install_fake_gemini();
+    let (client, _) =
+        common::start(structured_config(), client(ProtocolVersion::V_2025_06_18)).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call_raw(&client, "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    let structured = result.structured_content.expect("no structured content");
+    assert_eq!(structured["success"], true);
+    assert_eq!(structured["agent_messages"], "Hello world");
+    // The text form stays available alongside it.
+    let text: serde_json::Value =
+        serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+    assert_eq!(text, structured);
+}
+
+#[tokio::test]
+async fn older_clients_only_get_text() {
+    common::install_fake_gemini();
+    let (client, _) =
+        common::start(structured_config(), client(ProtocolVersion::V_2025_03_26)).await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call_raw(&client, "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert!(result.structured_content.is_none());
+    assert!(result.content[0].as_text().is_some());
+}
+
+#[tokio::test]
+async fn structured_content_is_off_by_default() {
+    common::install_fake_gemini();
+    let (client, _) = common::start(
+        ServerConfig::default(),
+        client(ProtocolVersion::V_2025_06_18),
+    )
+    .await;
+    let ws = common::workspace(common::DEFAULT_STREAM);
+
+    let result = common::call_raw(&client, "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    assert!(result.structured_content.is_none());
+}
+
+#[tokio::test]
+async fn both_copies_count_against_max_payload_bytes() {
+    common::install_fake_gemini();
+    let text: String = (0..300).map(|i| format!("line {i}\n")).collect();
+    let stream = format!(
+        "{}\n{}\n{}\n",
+        json!({"type": "init", "session_id": "sess-1"}),
+        json!({"type": "message", "role": "assistant", "content": text}),
+        json!({"type": "turn.completed"}),
+    );
+    let ws = common::workspace(&stream);
+    let config = ServerConfig {
+        max_payload_bytes: Some(2000),
+        ..structured_config()
+    };
+    let (client, _) = common::start(config, client(ProtocolVersion::V_2025_06_18)).await;
+
+    let result = common::call_raw(&client, "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+
+    let structured = result.structured_content.expect("no structured content");
+    let text = &result.content[0].as_text().unwrap().text;
+    let total = text.len() + structured.to_string().len();
+    assert!(total <= 2000, "{total} bytes sent");
+    assert!(structured["continuation_token"].is_string(), "{structured}");
+    assert!(!structured["agent_messages"].as_str().unwrap().is_empty());
+}
@@ -0,0 +1,54 @@
+//! `structured_turn`: the turn split into thinking, answer and actions.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+const STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"thought","content":"The user wants a notes file. "}
+{"type":"message","role":"assistant","content":"Writing the notes now. "}
+{"type":"tool_use","tool_name":"write_file","tool_id":"t1","parameters":{"file_path":"notes.txt","content":"TODO"}}
+{"type":"tool_result","tool_id":"t1","status":"success"}
+{"type":"message","role":"assistant","content":"Checking the result.","thought":true}
+{"type":"tool_use","tool_name":"run_shell_command","tool_id":"t2","parameters":{"command":"cat notes.txt"}}
+{"type":"tool_result","tool_id":"t2","status":"error"}
+{"type":"message","role":"assistant","content":"Done."}
+{"type":"turn.completed"}
+"#;
+
+async fn call(arguments: serde_json::Value) -> serde_json::Value {
+    common::install_fake_gemini();
+    let ws = common::workspace(STREAM);
+    let mut arguments = arguments;
+    arguments["PROMPT"] = json!("write notes");
+    arguments["cd"] = json!(ws);
+    let client = common::connect(ServerConfig::default()).await;
+    common::call(client.peer(), "gemini", arguments).await
+}
+
+#[tokio::test]
+async fn partitions_a_mixed_stream() {
+    let result = call(json!({"structured_turn": true})).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert_eq!(
+        result["structured"],
+        json!({
+            "thinking": "The user wants a notes file. Checking the result.",
+            "answer": "Writing the notes now. Done.",
+            "actions": [
+                {"name": "write_file", "id": "t1", "status": "success", "path": "notes.txt"},
+                {"name": "run_shell_command", "id": "t2", "status": "error"},
+            ],
+        })
+    );
+}
+
+#[tokio::test]
+async fn is_omitted_by_default() {
+    let result = call(json!({})).await;
+
+    assert!(result.get("structured").is_none(), "{result}");
+}
@@ -0,0 +1,68 @@
+//! Selecting a gemini subcommand with `SUBCOMMAND`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+fn allowing_chat() -> ServerConfig {
+    ServerConfig {
+        subcommands: vec!["chat".to_string()],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn subcommand_precedes_the_prompt_flags() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(allowing_chat()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "SUBCOMMAND": "chat", "model": "m"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], true, "{result}");
+    let args = common::gemini_args(&ws);
+    assert_eq!(args[..3], ["chat", "--prompt", "hi"]);
+    assert_eq!(args.iter().filter(|a| *a == "chat").count(), 1);
+}
+
+#[tokio::test]
+async fn no_subcommand_is_allowed_by_default() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "SUBCOMMAND": "chat"}),
+    )
+    .await;
+
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+    assert!(!ws.join("args").exists(), "gemini should not have run");
+}
+
+#[tokio::test]
+async fn unknown_subcommand_is_rejected() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(allowing_chat()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "SUBCOMMAND": "rm"}),
+    )
+    .await;
+
+    assert_eq!(result["success"], false);
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(!ws.join("args").exists(), "gemini should not have run");
+}
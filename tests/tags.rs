@@ -0,0 +1,106 @@
+//! `TAGS`: grouping calls in metrics and the audit log.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::audit::AUDIT_TARGET;
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Collects everything written by the fmt subscriber.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn tags_are_counted_in_metrics() {
+    common::install_fake_gemini();
+    let ok = common::workspace(common::DEFAULT_STREAM);
+    let silent = common::workspace(r#"{"type":"init","session_id":"sess-1"}"#);
+    let client = common::connect(ServerConfig::default()).await;
+
+    for (ws, team) in [(&ok, "search"), (&silent, "search"), (&ok, "ads")] {
+        common::call(
+            client.peer(),
+            "gemini",
+            json!({"PROMPT": "hi", "cd": ws, "TAGS": {"team": team, "feature": "summary"}}),
+        )
+        .await;
+    }
+    common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ok})).await;
+
+    let metrics = common::call(client.peer(), "gemini_metrics", json!({})).await;
+    let tags = &metrics["tags"];
+    assert_eq!(tags.as_object().unwrap().len(), 3, "{metrics}");
+    assert_eq!(tags["team=search"]["calls"], 2);
+    assert_eq!(tags["team=search"]["failures"], 1);
+    assert_eq!(tags["team=ads"]["calls"], 1);
+    assert_eq!(tags["team=ads"]["failures"], 0);
+    assert_eq!(tags["feature=summary"]["calls"], 3);
+    assert!(tags["feature=summary"]["mean_duration_ms"].is_u64());
+}
+
+#[tokio::test]
+async fn tags_are_written_to_the_audit_entry() {
+    common::install_fake_gemini();
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let _guard = tracing::subscriber::set_default(
+        tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish(),
+    );
+    let client = common::connect(ServerConfig {
+        audit_prompts: true,
+        ..Default::default()
+    })
+    .await;
+
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "TAGS": {"team": "search", "user": "u1"}}),
+    )
+    .await;
+    assert_eq!(result["success"], true, "{result}");
+
+    let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let entry = log
+        .lines()
+        .find(|line| line.contains(AUDIT_TARGET))
+        .expect("no audit entry was written");
+    assert!(entry.contains("tags=team=search,user=u1"), "{entry}");
+}
+
+#[tokio::test]
+async fn tags_do_not_change_the_fingerprint() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let untagged = common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await;
+    let tagged = common::call(
+        client.peer(),
+        "gemini",
+        json!({"PROMPT": "hi", "cd": ws, "TAGS": {"team": "search"}}),
+    )
+    .await;
+
+    assert_eq!(
+        untagged["request_fingerprint"],
+        tagged["request_fingerprint"]
+    );
+}
@@ -0,0 +1,49 @@
+//! Thinking tokens reported apart from output tokens.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions};
+
+const THINKING_STREAM: &str = r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Hmm.","usageMetadata":{"promptTokenCount":20,"candidatesTokenCount":2,"thoughtsTokenCount":40,"totalTokenCount":62}}
+{"type":"message","role":"assistant","content":" Done.","usage":{"input_tokens":5,"output_tokens":3,"thinking_tokens":7}}
+{"type":"turn.completed"}
+"#;
+
+#[tokio::test]
+async fn thinking_tokens_are_summed_separately() {
+    common::install_fake_gemini();
+    let ws = common::workspace(THINKING_STREAM);
+
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    let usage = result.usage.unwrap();
+    assert_eq!(usage.input_tokens, 25);
+    assert_eq!(usage.output_tokens, 5);
+    assert_eq!(usage.thinking_tokens, Some(47));
+    // The second event has no total, so its thinking tokens count towards it
+    assert_eq!(usage.total_tokens, 62 + 15);
+}
+
+#[tokio::test]
+async fn thinking_tokens_are_omitted_when_not_reported() {
+    common::install_fake_gemini();
+    let ws = common::workspace(
+        r#"{"type":"init","session_id":"sess-1"}
+{"type":"message","role":"assistant","content":"Hi","usage":{"input_tokens":5,"output_tokens":3}}
+{"type":"turn.completed"}
+"#,
+    );
+
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    let usage = result.usage.unwrap();
+    assert_eq!(usage.thinking_tokens, None);
+    let json = serde_json::to_value(usage).unwrap();
+    assert!(json.get("thinking_tokens").is_none(), "{json}");
+}
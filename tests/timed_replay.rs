@@ -0,0 +1,87 @@
+//! Replaying recorded streams with their original timing.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, DrainMode, FailureKind, GeminiOptions, GeminiResult, KillMode};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn timed_replay_keeps_the_recorded_gaps() {
+    common::install_fake_gemini();
+    // The fixture spans about 3.1s
+    let ws = common::timed_workspace(&common::fixture("multi_message"), 4.0);
+
+    let started = Instant::now();
+    let result = execute_gemini("hi", &ws, &GeminiOptions::default())
+        .await
+        .unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    assert!(started.elapsed() >= Duration::from_millis(700));
+    assert_eq!(
+        result.agent_messages.as_deref(),
+        Some("Let me look. The directory contains `Cargo.toml` and `src/`.")
+    );
+}
+
+#[tokio::test]
+async fn timeout_fires_while_gemini_is_idle_mid_stream() {
+    common::install_fake_gemini();
+    let ws = common::timed_workspace(&common::fixture("multi_message"), 1.0);
+
+    let options = GeminiOptions {
+        timeout: Some(Duration::from_millis(1000)),
+        kill_mode: KillMode::Immediate,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    // Times out during the 1.4s gap before the first assistant message
+    assert!(started.elapsed() < Duration::from_millis(2500));
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::Timeout));
+    assert_eq!(
+        result.session_id.as_deref(),
+        Some("0b6f8a53-91c2-4d1e-a7f4-5e3c2d1b0a99")
+    );
+}
+
+/// A turn whose final stats arrive 100ms after `turn.completed`.
+const TRAILING_STATS: &str = r#"{"type":"init","session_id":"sess-1","timestamp":"2025-10-02T09:20:41.000Z"}
+{"type":"message","role":"assistant","content":"Done.","timestamp":"2025-10-02T09:20:41.100Z"}
+{"type":"turn.completed","timestamp":"2025-10-02T09:20:41.200Z"}
+{"type":"result","stats":{"input_tokens":12,"output_tokens":3},"timestamp":"2025-10-02T09:20:41.300Z"}
+"#;
+
+async fn replay_trailing_stats(drain_mode: DrainMode) -> (GeminiResult, Duration) {
+    let ws = common::timed_workspace(TRAILING_STATS, 1.0);
+    let options = GeminiOptions {
+        drain_mode,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+    (result, started.elapsed())
+}
+
+#[tokio::test]
+async fn adaptive_drain_captures_trailing_events_sooner_than_the_fixed_delay() {
+    common::install_fake_gemini();
+
+    let (fixed, fixed_elapsed) = replay_trailing_stats(DrainMode::Fixed).await;
+    let (adaptive, adaptive_elapsed) = replay_trailing_stats(DrainMode::Adaptive).await;
+
+    for result in [&fixed, &adaptive] {
+        assert!(result.success, "{:?}", result.error);
+        let usage = result.usage.as_ref().expect("trailing stats are read");
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12, 3));
+    }
+    // The fixed delay waits 300ms after `turn.completed`; the adaptive drain
+    // stops when gemini exits right after its stats, 100ms in.
+    assert!(
+        adaptive_elapsed + Duration::from_millis(100) < fixed_elapsed,
+        "adaptive {adaptive_elapsed:?}, fixed {fixed_elapsed:?}"
+    );
+}
@@ -0,0 +1,37 @@
+//! Prompt-size based timeouts.
+
+use gemini_mcp::TimeoutPolicy;
+use std::time::Duration;
+
+#[test]
+fn timeout_scales_with_prompt_size_up_to_the_cap() {
+    let policy = TimeoutPolicy {
+        base_secs: 60,
+        per_kb_secs: 10.0,
+        max_secs: 300,
+    };
+
+    assert_eq!(policy.for_prompt(""), Duration::from_secs(60));
+    assert_eq!(
+        policy.for_prompt(&"x".repeat(1024)),
+        Duration::from_secs(70)
+    );
+    assert_eq!(
+        policy.for_prompt(&"x".repeat(10 * 1024)),
+        Duration::from_secs(160)
+    );
+    assert_eq!(
+        policy.for_prompt(&"x".repeat(100 * 1024)),
+        Duration::from_secs(300)
+    );
+}
+
+#[test]
+fn default_timeout_does_not_scale() {
+    let policy = TimeoutPolicy::default();
+    assert_eq!(policy.for_prompt(""), Duration::from_secs(300));
+    assert_eq!(
+        policy.for_prompt(&"x".repeat(100 * 1024)),
+        Duration::from_secs(300)
+    );
+}
@@ -0,0 +1,44 @@
+//! Reporting tool calls for turns that produce no assistant text.
+
+use gemini_mcp::{replay_stream, FailureKind, GeminiOptions};
+
+fn tool_call_only() -> String {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/stream/tool_call_only.jsonl"
+    );
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn failure_message_summarizes_tool_calls() {
+    let result = replay_stream(&tool_call_only(), &GeminiOptions::default());
+
+    assert!(!result.success);
+    assert_eq!(result.failure_kind, Some(FailureKind::NoAgentMessages));
+    let error = result.error.unwrap();
+    assert!(
+        error.contains("Tool calls: write_file (success)."),
+        "unexpected error: {error}"
+    );
+    assert_eq!(result.tool_calls.len(), 1);
+    assert_eq!(
+        result.tool_calls[0].id.as_deref(),
+        Some("write_file-1759396445481-0")
+    );
+}
+
+#[test]
+fn empty_messages_can_be_allowed() {
+    let options = GeminiOptions {
+        allow_empty_messages: true,
+        no_messages_template: Some("Only ran {tool_calls}".to_string()),
+        ..Default::default()
+    };
+    let result = replay_stream(&tool_call_only(), &options);
+
+    assert!(result.success);
+    assert_eq!(result.error, None);
+    assert_eq!(result.agent_messages.as_deref(), Some(""));
+    assert_eq!(result.warnings, ["Only ran write_file (success)"]);
+}
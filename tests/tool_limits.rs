@@ -0,0 +1,108 @@
+//! `ALLOWED_TOOLS` and `DENIED_TOOLS`: limiting the tools gemini may call.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::{execute_gemini, GeminiOptions, ServerConfig};
+use serde_json::json;
+
+const HELP: &str = r#"Usage: gemini [options] [command]
+
+Options:
+      --allowed-tools             Tools that are allowed to run          [array]
+  -o, --output-format             The format of the CLI output.
+                                  [string] [choices: "text", "json",
+                                  "stream-json"]
+"#;
+
+#[tokio::test]
+async fn flags_are_composed_per_tool() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let options = GeminiOptions {
+        allowed_tools: vec!["read_file".to_string(), "glob".to_string()],
+        denied_tools: vec!["run_shell_command".to_string()],
+        ..Default::default()
+    };
+
+    let result = execute_gemini("hi", &ws, &options).await.unwrap();
+
+    assert!(result.success, "{:?}", result.error);
+    let args = common::gemini_args(&ws).join(" ");
+    assert!(
+        args.contains(
+            "--allowed-tools read_file --allowed-tools glob --exclude-tools run_shell_command"
+        ),
+        "{args}"
+    );
+}
+
+#[tokio::test]
+async fn tool_both_allowed_and_denied_is_rejected() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let result = common::call(
+        client.peer(),
+        "gemini",
+        json!({
+            "PROMPT": "hi",
+            "cd": ws,
+            "ALLOWED_TOOLS": ["read_file", "write_file"],
+            "DENIED_TOOLS": ["write_file"],
+        }),
+    )
+    .await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "invalid_input");
+    assert!(
+        result["error"]
+            .as_str()
+            .unwrap()
+            .contains("write_file is both allowed and denied"),
+        "{result}"
+    );
+    assert!(
+        !ws.join("args").exists(),
+        "gemini ran with conflicting tools"
+    );
+}
+
+// One test, as it changes the fake gemini's help for the whole process
+#[tokio::test]
+async fn denied_tools_require_gemini_to_list_exclude_tools() {
+    common::install_fake_gemini();
+    let help = common::scratch_dir("help").join("help.txt");
+    std::env::set_var("FAKE_GEMINI_HELP", &help);
+    let request = |ws: &std::path::Path| json!({"PROMPT": "hi", "cd": ws, "DENIED_TOOLS": ["run_shell_command"]});
+
+    std::fs::write(&help, HELP).unwrap();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+    let result = common::call(client.peer(), "gemini", request(&ws)).await;
+    assert_eq!(result["failure_kind"], "invalid_input", "{result}");
+    assert!(
+        result["error"]
+            .as_str()
+            .unwrap()
+            .contains("--exclude-tools"),
+        "{result}"
+    );
+    assert!(!ws.join("args").exists(), "gemini ran with an unknown flag");
+
+    std::fs::write(
+        &help,
+        HELP.replace(
+            "      --allowed-tools",
+            "      --exclude-tools             Tools to exclude                       [array]\n      --allowed-tools",
+        ),
+    )
+    .unwrap();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+    let result = common::call(client.peer(), "gemini", request(&ws)).await;
+    assert_eq!(result["success"], true, "{result}");
+    assert!(common::gemini_args(&ws).contains(&"--exclude-tools".to_string()));
+}
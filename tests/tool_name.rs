@@ -0,0 +1,109 @@
+//! Registering the tools under a configured name and descriptions.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+async fn tool_names(client: &rmcp::Peer<rmcp::RoleClient>) -> Vec<String> {
+    let mut names: Vec<String> = client
+        .list_all_tools()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|tool| tool.name.to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn two_servers_register_distinct_tool_names() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let default = common::connect(ServerConfig::default()).await;
+    let sandboxed = common::connect(ServerConfig {
+        tool_name: Some("gemini_sandboxed".to_string()),
+        tool_description: Some("Runs gemini in its sandbox.".to_string()),
+        ..Default::default()
+    })
+    .await;
+
+    assert_eq!(
+        tool_names(default.peer()).await,
+        [
+            "gemini",
+            "gemini_batch",
+            "gemini_batch_retry",
+            "gemini_capabilities",
+            "gemini_health",
+            "gemini_messages_page",
+            "gemini_metrics",
+            "gemini_resume",
+            "gemini_validate"
+        ]
+    );
+    assert_eq!(
+        tool_names(sandboxed.peer()).await,
+        [
+            "gemini_sandboxed",
+            "gemini_sandboxed_batch",
+            "gemini_sandboxed_batch_retry",
+            "gemini_sandboxed_capabilities",
+            "gemini_sandboxed_health",
+            "gemini_sandboxed_messages_page",
+            "gemini_sandboxed_metrics",
+            "gemini_sandboxed_resume",
+            "gemini_sandboxed_validate"
+        ]
+    );
+
+    let tools = sandboxed.peer().list_all_tools().await.unwrap();
+    let main = tools.iter().find(|t| t.name == "gemini_sandboxed").unwrap();
+    assert_eq!(
+        main.description.as_deref(),
+        Some("Runs gemini in its sandbox.")
+    );
+
+    let result = common::call(
+        sandboxed.peer(),
+        "gemini_sandboxed",
+        json!({"PROMPT": "hi", "cd": ws}),
+    )
+    .await;
+    assert_eq!(result["agent_messages"], "Hello world", "{result}");
+
+    let old_name = sandboxed
+        .peer()
+        .call_tool(rmcp::model::CallToolRequestParam {
+            name: "gemini".into(),
+            arguments: json!({"PROMPT": "hi", "cd": ws}).as_object().cloned(),
+            task: None,
+        })
+        .await;
+    assert!(old_name.is_err());
+}
+
+#[tokio::test]
+async fn configured_field_descriptions_replace_the_schema_defaults() {
+    let client = common::connect(ServerConfig {
+        field_descriptions: vec![
+            "PROMPT=Aufgabe für gemini".parse().unwrap(),
+            "cd=Arbeitsverzeichnis".parse().unwrap(),
+        ],
+        ..Default::default()
+    })
+    .await;
+
+    let tools = client.peer().list_all_tools().await.unwrap();
+    for name in ["gemini", "gemini_validate"] {
+        let tool = tools.iter().find(|t| t.name == name).unwrap();
+        let properties = &tool.input_schema["properties"];
+        assert_eq!(properties["PROMPT"]["description"], "Aufgabe für gemini");
+        assert_eq!(properties["cd"]["description"], "Arbeitsverzeichnis");
+        assert!(properties["model"]["description"]
+            .as_str()
+            .is_some_and(|d| !d.is_empty()));
+    }
+}
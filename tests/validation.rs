@@ -0,0 +1,73 @@
+//! Reporting every problem with a request at once with `gemini_validate`.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+
+/// `(field, severity)` of every reported issue.
+fn issues(report: &serde_json::Value) -> Vec<(String, String)> {
+    report["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| {
+            (
+                issue["field"].as_str().unwrap().to_string(),
+                issue["severity"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn every_invalid_field_is_reported() {
+    common::install_fake_gemini();
+    let client = common::connect(ServerConfig::default()).await;
+
+    let report = common::call(
+        client.peer(),
+        "gemini_validate",
+        json!({
+            "PROMPT": "  ",
+            "cd": "/nonexistent/gemini-mcp-workspace",
+            "model": "gemini 2.5",
+            "TIMEOUT_SECS": 0,
+            "max_turns": 3,
+        }),
+    )
+    .await;
+
+    assert_eq!(report["valid"], false, "{report}");
+    let issues = issues(&report);
+    for expected in [
+        ("PROMPT", "error"),
+        ("cd", "error"),
+        ("model", "error"),
+        ("TIMEOUT_SECS", "error"),
+        ("max_turns", "warning"),
+    ] {
+        assert!(
+            issues.contains(&(expected.0.to_string(), expected.1.to_string())),
+            "{expected:?} missing from {issues:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn valid_request_has_no_issues() {
+    common::install_fake_gemini();
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    let client = common::connect(ServerConfig::default()).await;
+
+    let report = common::call(
+        client.peer(),
+        "gemini_validate",
+        json!({"PROMPT": "hi", "cd": ws}),
+    )
+    .await;
+
+    assert_eq!(report, json!({"valid": true, "issues": []}));
+    assert!(!ws.join("args").exists(), "gemini ran during validation");
+}
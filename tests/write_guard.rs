@@ -0,0 +1,117 @@
+//! `--allowed-write-extension`: failing, and optionally undoing, writes to
+//! other file types.
+#![cfg(unix)]
+
+mod common;
+
+use gemini_mcp::ServerConfig;
+use serde_json::json;
+use std::path::Path;
+use std::process::Command;
+
+/// Writes a Rust file and a `.env`, like a gemini editing the workspace. The
+/// fake gemini's own records are removed so that only these writes show.
+const WRITE_FILES: &str = "rm -f args env
+mkdir -p src
+echo 'fn main() {}' > src/main.rs
+echo 'TOKEN=secret' > .env
+";
+
+fn config(revert: bool) -> ServerConfig {
+    ServerConfig {
+        allowed_write_extensions: vec![".rs".to_string(), "md".to_string()],
+        revert_forbidden_writes: revert,
+        ..Default::default()
+    }
+}
+
+async fn call(ws: &Path, config: ServerConfig) -> serde_json::Value {
+    common::install_fake_gemini();
+    let client = common::connect(config).await;
+    common::call(client.peer(), "gemini", json!({"PROMPT": "hi", "cd": ws})).await
+}
+
+fn git(ws: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(ws)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {args:?}");
+}
+
+#[tokio::test]
+async fn writing_a_forbidden_extension_fails_the_call() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(ws.join("script.sh"), WRITE_FILES).unwrap();
+
+    let result = call(&ws, config(false)).await;
+
+    assert_eq!(result["success"], false, "{result}");
+    assert_eq!(result["failure_kind"], "forbidden_write");
+    assert_eq!(
+        result["forbidden_writes"],
+        json!([ws.join(".env").to_string_lossy()])
+    );
+    assert!(result["error"].as_str().unwrap().contains(".env"));
+    assert!(result.get("reverted_writes").is_none());
+    // Without reverting, the write stays
+    assert!(ws.join(".env").exists());
+}
+
+#[tokio::test]
+async fn allowed_extensions_pass() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(
+        ws.join("script.sh"),
+        "rm -f args env\necho '# Notes' > NOTES.MD\necho '' > lib.rs\n",
+    )
+    .unwrap();
+
+    let result = call(&ws, config(false)).await;
+
+    assert_eq!(result["success"], true, "{result}");
+    assert!(result.get("forbidden_writes").is_none(), "{result}");
+}
+
+#[tokio::test]
+async fn forbidden_writes_are_reverted_in_a_git_repository() {
+    let ws = common::workspace(common::DEFAULT_STREAM);
+    std::fs::write(
+        ws.join("script.sh"),
+        format!("{WRITE_FILES}echo changed > config.toml\necho changed > local.toml\n"),
+    )
+    .unwrap();
+    std::fs::write(ws.join("config.toml"), "original\n").unwrap();
+    git(&ws, &["init", "-q"]);
+    git(&ws, &["add", "."]);
+    git(&ws, &["commit", "-q", "-m", "initial"]);
+    // Modified before the run, so reverting it would lose the local change
+    std::fs::write(ws.join("local.toml"), "local\n").unwrap();
+    git(&ws, &["add", "local.toml"]);
+
+    let result = call(&ws, config(true)).await;
+
+    assert_eq!(result["failure_kind"], "forbidden_write", "{result}");
+    let path = |name: &str| ws.join(name).to_string_lossy().to_string();
+    assert_eq!(
+        result["forbidden_writes"],
+        json!([path(".env"), path("config.toml"), path("local.toml")])
+    );
+    assert_eq!(
+        result["reverted_writes"],
+        json!([path(".env"), path("config.toml")])
+    );
+    assert!(!ws.join(".env").exists());
+    assert_eq!(
+        std::fs::read_to_string(ws.join("config.toml")).unwrap(),
+        "original\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(ws.join("local.toml")).unwrap(),
+        "changed\n"
+    );
+    assert!(ws.join("src/main.rs").exists());
+}